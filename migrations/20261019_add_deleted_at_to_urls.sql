@@ -0,0 +1,6 @@
+-- Deleted links stay in the trash until they are restored or purged after the retention period
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+
+-- Create index on deletion time for the trash view and the purge job
+CREATE INDEX IF NOT EXISTS idx_urls_deleted_at ON urls(deleted_at)
+    WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,5 @@
+-- Metadata fetched from the destination in the background after a link is created
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS page_title TEXT;
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS favicon_url TEXT;
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS http_status INTEGER;
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS metadata_fetched_at TIMESTAMPTZ;
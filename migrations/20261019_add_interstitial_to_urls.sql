@@ -0,0 +1,6 @@
+-- Per-link interstitial flag for links flagged by a moderator or marked sensitive
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS interstitial BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Track how often the warning page was shown and how often visitors continued
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS interstitial_views BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS interstitial_confirmations BIGINT NOT NULL DEFAULT 0;
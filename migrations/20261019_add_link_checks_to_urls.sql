@@ -0,0 +1,11 @@
+-- Destination used when the original one is found broken
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS fallback_url TEXT;
+
+-- Result of the latest periodic check of the destination
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS last_check_status INTEGER;
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS last_check_latency_ms INTEGER;
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS last_checked_at TIMESTAMPTZ;
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS check_failures INTEGER NOT NULL DEFAULT 0;
+
+-- Create index to pick the links that were checked least recently
+CREATE INDEX IF NOT EXISTS idx_urls_last_checked_at ON urls(last_checked_at NULLS FIRST);
@@ -0,0 +1,9 @@
+-- Human-readable context attached to links by their creators
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS title TEXT;
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS description TEXT;
+
+-- Arbitrary key/value data for internal tools, such as ticket IDs or owners
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS metadata JSONB NOT NULL DEFAULT '{}'::JSONB;
+
+-- Create index on metadata for filtering links by key
+CREATE INDEX IF NOT EXISTS idx_urls_metadata ON urls USING GIN (metadata);
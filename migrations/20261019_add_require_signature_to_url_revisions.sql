@@ -0,0 +1,8 @@
+-- Revisions also capture whether a link only redirects through a signed URL
+ALTER TABLE url_revisions ADD COLUMN IF NOT EXISTS require_signature BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Earlier revisions predate the flag, so they take the link's current setting
+UPDATE url_revisions r
+SET require_signature = u.require_signature
+FROM urls u
+WHERE u.id = r.url_id AND u.require_signature
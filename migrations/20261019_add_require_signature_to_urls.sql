@@ -0,0 +1,2 @@
+-- Links that only redirect through a signed, self-expiring URL
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS require_signature BOOLEAN NOT NULL DEFAULT FALSE
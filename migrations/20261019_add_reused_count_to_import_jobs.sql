@@ -0,0 +1,2 @@
+-- Rows that matched an existing link through deduplication, counted apart from new links
+ALTER TABLE import_jobs ADD COLUMN IF NOT EXISTS reused_count INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,4 @@
+-- Open Graph overrides served to link preview crawlers instead of the destination's metadata
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS og_title TEXT;
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS og_description TEXT;
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS og_image TEXT;
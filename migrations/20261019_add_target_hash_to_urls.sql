@@ -0,0 +1,7 @@
+-- Hash of the normalized destination, used to reuse an existing link instead of creating
+-- a duplicate. Links created before this column existed have no hash and are never reused.
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS target_hash TEXT;
+
+-- Create index on target hash for finding existing links to the same destination
+CREATE INDEX IF NOT EXISTS idx_urls_target_hash ON urls(target_hash)
+    WHERE target_hash IS NOT NULL;
@@ -0,0 +1,5 @@
+-- Accounts signed in through an identity provider are matched by the `sub` claim of their
+-- tokens, and have no password
+ALTER TABLE users ADD COLUMN IF NOT EXISTS external_subject TEXT UNIQUE;
+
+ALTER TABLE users ALTER COLUMN password_hash DROP NOT NULL
@@ -0,0 +1,10 @@
+-- Collections belong to a workspace, or to no workspace like personal links. Existing
+-- collections stay outside any workspace.
+ALTER TABLE collections ADD COLUMN IF NOT EXISTS workspace_id UUID
+    REFERENCES workspaces(id) ON DELETE CASCADE;
+
+-- Names are unique within a workspace rather than across the server
+ALTER TABLE collections DROP CONSTRAINT IF EXISTS collections_name_key;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_collections_workspace_name
+    ON collections (COALESCE(workspace_id, '00000000-0000-0000-0000-000000000000'), name);
@@ -0,0 +1,2 @@
+-- Custom aliases can be longer than generated short codes
+ALTER TABLE urls ALTER COLUMN short_code TYPE VARCHAR(64);
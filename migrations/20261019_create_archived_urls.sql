@@ -0,0 +1,27 @@
+-- Links removed by the expired-link cleanup, kept for reference
+CREATE TABLE IF NOT EXISTS archived_urls (
+    id UUID PRIMARY KEY,
+    short_code VARCHAR(64) NOT NULL,
+    original_url TEXT NOT NULL,
+    visits BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    title TEXT,
+    description TEXT,
+    metadata JSONB NOT NULL DEFAULT '{}',
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    archived_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_archived_urls_short_code ON archived_urls(short_code);
+
+-- Short codes of cleaned up links, which cannot be taken again until reusable_at
+-- (never when it is NULL)
+CREATE TABLE IF NOT EXISTS retired_short_codes (
+    short_code VARCHAR(64) PRIMARY KEY,
+    retired_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    reusable_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_retired_short_codes_reusable_at ON retired_short_codes(reusable_at)
+    WHERE reusable_at IS NOT NULL
@@ -0,0 +1,27 @@
+-- Who did what through the API, for compliance. Rows are only ever added.
+CREATE TABLE IF NOT EXISTS audit_events (
+    id BIGSERIAL PRIMARY KEY,
+    occurred_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    -- As recorded for link revisions, e.g. an email or `workspace-key:<id>`
+    actor TEXT NOT NULL,
+    actor_id UUID,
+    -- `<target type>.<verb>`, e.g. `link.update`
+    action TEXT NOT NULL,
+    target_type TEXT NOT NULL,
+    target_id TEXT,
+    workspace_id UUID,
+    -- Changed fields with their values before and after
+    changes JSONB,
+    client_ip TEXT,
+    request_id TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_events_occurred_at ON audit_events(occurred_at);
+CREATE INDEX IF NOT EXISTS idx_audit_events_target ON audit_events(target_type, target_id);
+CREATE INDEX IF NOT EXISTS idx_audit_events_actor ON audit_events(actor);
+CREATE INDEX IF NOT EXISTS idx_audit_events_action ON audit_events(action);
+
+-- Updates and deletes are silently dropped, so the log cannot be rewritten through the app
+CREATE OR REPLACE RULE audit_events_no_update AS ON UPDATE TO audit_events DO INSTEAD NOTHING;
+
+CREATE OR REPLACE RULE audit_events_no_delete AS ON DELETE TO audit_events DO INSTEAD NOTHING
@@ -0,0 +1,20 @@
+-- Links each quota subject has created, for total link quotas. Kept here rather than in
+-- Redis so the counts survive a flush or failover of the cache.
+CREATE TABLE IF NOT EXISTS quota_totals (
+    subject_type TEXT NOT NULL CHECK (subject_type IN ('user', 'workspace', 'api_key')),
+    subject_id UUID NOT NULL,
+    links BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (subject_type, subject_id)
+);
+
+-- Start from the links that still exist. Links do not record the API key that created them,
+-- so API keys start over.
+INSERT INTO quota_totals (subject_type, subject_id, links)
+SELECT 'user', owner_id, COUNT(*) FROM urls
+WHERE owner_id IS NOT NULL AND workspace_id IS NULL
+GROUP BY owner_id
+UNION ALL
+SELECT 'workspace', workspace_id, COUNT(*) FROM urls
+WHERE workspace_id IS NOT NULL
+GROUP BY workspace_id
+ON CONFLICT (subject_type, subject_id) DO NOTHING
@@ -0,0 +1,13 @@
+-- Limits on the links an account, workspace or workspace API key creates and holds. Links
+-- created today are counted in Redis, links created in total in `quota_totals`, and active
+-- links in `urls`.
+CREATE TABLE IF NOT EXISTS quotas (
+    subject_type TEXT NOT NULL CHECK (subject_type IN ('user', 'workspace', 'api_key')),
+    subject_id UUID NOT NULL,
+    -- No limit when NULL
+    daily_links BIGINT CHECK (daily_links >= 0),
+    total_links BIGINT CHECK (total_links >= 0),
+    active_links BIGINT CHECK (active_links >= 0),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (subject_type, subject_id)
+)
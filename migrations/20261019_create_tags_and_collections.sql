@@ -0,0 +1,29 @@
+-- Named collections (folders) grouping links
+CREATE TABLE IF NOT EXISTS collections (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL UNIQUE,
+    description TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- A link belongs to at most one collection
+ALTER TABLE urls ADD COLUMN IF NOT EXISTS collection_id UUID
+    REFERENCES collections(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_urls_collection_id ON urls(collection_id)
+    WHERE collection_id IS NOT NULL;
+
+-- Free-form tags, shared between links
+CREATE TABLE IF NOT EXISTS tags (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS url_tags (
+    url_id UUID NOT NULL REFERENCES urls(id) ON DELETE CASCADE,
+    tag_id UUID NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    PRIMARY KEY (url_id, tag_id)
+);
+
+-- Create index on tag for filtering links by tag
+CREATE INDEX IF NOT EXISTS idx_url_tags_tag_id ON url_tags(tag_id);
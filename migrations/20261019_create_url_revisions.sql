@@ -0,0 +1,26 @@
+-- Snapshots of the redirect settings of a link, one per change
+CREATE TABLE IF NOT EXISTS url_revisions (
+    url_id UUID NOT NULL REFERENCES urls(id) ON DELETE CASCADE,
+    revision INTEGER NOT NULL,
+    change TEXT NOT NULL,
+    changed_by TEXT,
+    original_url TEXT NOT NULL,
+    expires_at TIMESTAMPTZ,
+    interstitial BOOLEAN NOT NULL,
+    fallback_url TEXT,
+    og_title TEXT,
+    og_description TEXT,
+    og_image TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (url_id, revision)
+);
+
+-- Existing links start with their current settings as the first revision
+INSERT INTO url_revisions (
+    url_id, revision, change, original_url, expires_at, interstitial,
+    fallback_url, og_title, og_description, og_image, created_at
+)
+SELECT id, 1, 'created', original_url, expires_at, interstitial,
+    fallback_url, og_title, og_description, og_image, created_at
+FROM urls
+ON CONFLICT (url_id, revision) DO NOTHING
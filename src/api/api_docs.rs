@@ -4,21 +4,153 @@ use utoipa_swagger_ui::SwaggerUi;
 
 use crate::{
     application::url_service::UrlService,
-    domain::url::{CreateUrlRequest, CreateUrlResponse, StatsResponse, Url},
+    domain::{
+        audit::{AuditEvent, AuditEventPage},
+        collection::{Collection, CreateCollectionRequest, UpdateCollectionRequest},
+        job::{JobOutcome, JobStatus},
+        maintenance::ExpiredCleanupStats,
+        qr::{QrErrorCorrection, QrFormat},
+        quota::{LimitUsage, Quota, QuotaSubject, QuotaUsage, SetQuotaRequest},
+        revision::{RevisionChange, RollbackRequest, UrlRevision},
+        signed_link::{SignLinkRecipient, SignLinkRequest, SignedLink},
+        transfer::{
+            ConflictPolicy, ExportFormat, ImportFormat, ImportJob, ImportStatus, RestoreSummary,
+        },
+        url::{
+            BatchCreateUrlRequest, BatchCreateUrlResponse, BatchItemResult, BatchMode,
+            CreateUrlRequest, CreateUrlResponse, StatsResponse, TagStats, UpdateUrlRequest, Url,
+            UrlListResponse, UrlSort,
+        },
+        user::{CreateUserRequest, LoginRequest, LoginResponse, User},
+        workspace::{
+            AcceptInvitationRequest, CreateApiKeyRequest, CreateInvitationRequest,
+            CreateWorkspaceRequest, Invitation, UpdateMemberRequest, Workspace, WorkspaceApiKey,
+            WorkspaceMember, WorkspaceRole,
+        },
+    },
 };
 
 #[derive(OpenApi)]
 #[openapi(
     paths(
         crate::api::handlers::create_short_url,
+        crate::api::handlers::create_short_urls,
         crate::api::handlers::redirect_to_url,
+        crate::api::handlers::get_qr_code,
+        crate::api::handlers::update_url,
+        crate::api::handlers::delete_url,
+        crate::api::handlers::restore_url,
+        crate::api::handlers::get_url_history,
+        crate::api::handlers::rollback_url,
+        crate::api::handlers::get_broken_links,
+        crate::api::handlers::list_urls,
         crate::api::handlers::get_stats,
+        crate::api::handlers::get_expired_cleanup_stats,
+        crate::api::handlers::list_jobs,
+        crate::api::handlers::list_collections,
+        crate::api::handlers::create_collection,
+        crate::api::handlers::get_collection,
+        crate::api::handlers::update_collection,
+        crate::api::handlers::delete_collection,
+        crate::api::handlers::import_links,
+        crate::api::handlers::list_imports,
+        crate::api::handlers::get_import,
+        crate::api::handlers::get_import_errors,
+        crate::api::handlers::export_urls,
+        crate::api::handlers::download_backup,
+        crate::api::handlers::restore_backup,
+        crate::api::handlers::login,
+        crate::api::handlers::logout,
+        crate::api::handlers::get_current_user,
+        crate::api::handlers::create_user,
+        crate::api::handlers::list_users,
+        crate::api::handlers::create_workspace,
+        crate::api::handlers::list_workspaces,
+        crate::api::handlers::list_workspace_members,
+        crate::api::handlers::update_workspace_member,
+        crate::api::handlers::remove_workspace_member,
+        crate::api::handlers::create_invitation,
+        crate::api::handlers::list_invitations,
+        crate::api::handlers::revoke_invitation,
+        crate::api::handlers::accept_invitation,
+        crate::api::handlers::create_workspace_api_key,
+        crate::api::handlers::list_workspace_api_keys,
+        crate::api::handlers::revoke_workspace_api_key,
+        crate::api::handlers::list_audit_events,
+        crate::api::handlers::export_audit_events,
+        crate::api::handlers::list_quotas,
+        crate::api::handlers::set_quota,
+        crate::api::handlers::delete_quota,
+        crate::api::handlers::get_usage,
+        crate::api::handlers::sign_url,
     ),
     components(
-        schemas(CreateUrlRequest, CreateUrlResponse, StatsResponse, Url)
+        schemas(
+            CreateUrlRequest,
+            CreateUrlResponse,
+            BatchMode,
+            BatchCreateUrlRequest,
+            BatchItemResult,
+            BatchCreateUrlResponse,
+            StatsResponse,
+            ExpiredCleanupStats,
+            JobOutcome,
+            JobStatus,
+            UpdateUrlRequest,
+            Url,
+            RevisionChange,
+            UrlRevision,
+            RollbackRequest,
+            QrFormat,
+            QrErrorCorrection,
+            TagStats,
+            UrlListResponse,
+            UrlSort,
+            Collection,
+            CreateCollectionRequest,
+            UpdateCollectionRequest,
+            ImportFormat,
+            ImportStatus,
+            ImportJob,
+            ExportFormat,
+            ConflictPolicy,
+            RestoreSummary,
+            User,
+            CreateUserRequest,
+            LoginRequest,
+            LoginResponse,
+            WorkspaceRole,
+            Workspace,
+            CreateWorkspaceRequest,
+            WorkspaceMember,
+            UpdateMemberRequest,
+            Invitation,
+            CreateInvitationRequest,
+            AcceptInvitationRequest,
+            WorkspaceApiKey,
+            CreateApiKeyRequest,
+            AuditEvent,
+            AuditEventPage,
+            QuotaSubject,
+            Quota,
+            SetQuotaRequest,
+            LimitUsage,
+            QuotaUsage,
+            SignLinkRequest,
+            SignLinkRecipient,
+            SignedLink
+        )
     ),
     tags(
-        (name = "URL Shortener API", description = "URL shortening service endpoints")
+        (name = "URL Shortener API", description = "URL shortening service endpoints"),
+        (name = "Collections", description = "Grouping short URLs into collections"),
+        (name = "Import and export", description = "Bulk import and export of short URLs"),
+        (name = "Backup", description = "Full backup and restore of the link database"),
+        (name = "Jobs", description = "Scheduled background maintenance jobs"),
+        (name = "Accounts", description = "User accounts and login sessions"),
+        (name = "Workspaces", description = "Teams sharing links, with roles, invitations and API keys"),
+        (name = "Audit", description = "Record of changes and administrative reads"),
+        (name = "Quotas", description = "Limits on the links accounts, workspaces and API keys create")
     ),
     info(
         title = "URL Shortener API",
@@ -0,0 +1,187 @@
+use std::net::{IpAddr, SocketAddr};
+
+use axum::http::{header, HeaderMap};
+use ipnet::IpNet;
+
+const X_FORWARDED_FOR: &str = "X-Forwarded-For";
+
+/// The address of the client behind any trusted proxies. Only requests coming from a
+/// trusted proxy have their `Forwarded` header, or `X-Forwarded-For` without it, read. The
+/// addresses are walked from the nearest hop back, and the first one that is not a trusted
+/// proxy is the client, so addresses a client puts in the headers itself are never used.
+pub fn resolve(peer: IpAddr, headers: &HeaderMap, trusted_proxies: &[IpNet]) -> IpAddr {
+    let is_trusted = |ip: &IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+    if !is_trusted(&peer) {
+        return peer;
+    }
+
+    let hops = if headers.contains_key(header::FORWARDED) {
+        forwarded_hops(headers)
+    } else {
+        x_forwarded_for_hops(headers)
+    };
+
+    let mut client = peer;
+    for hop in hops.iter().rev() {
+        // Obfuscated or malformed entries cannot be followed any further
+        let Some(ip) = hop else {
+            break;
+        };
+        client = *ip;
+        if !is_trusted(ip) {
+            break;
+        }
+    }
+    client
+}
+
+/// The `for` addresses of every `Forwarded` header, nearest client first
+fn forwarded_hops(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    headers
+        .get_all(header::FORWARDED)
+        .iter()
+        .flat_map(|value| value.to_str().unwrap_or_default().split(','))
+        .map(|element| {
+            element.split(';').find_map(|pair| {
+                let (name, value) = pair.split_once('=')?;
+                name.trim()
+                    .eq_ignore_ascii_case("for")
+                    .then(|| parse_node(value.trim().trim_matches('"')))?
+            })
+        })
+        .collect()
+}
+
+/// The addresses of every `X-Forwarded-For` header, nearest client first
+fn x_forwarded_for_hops(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .flat_map(|value| value.to_str().unwrap_or_default().split(','))
+        .map(|node| parse_node(node.trim()))
+        .collect()
+}
+
+/// An address, optionally with a port and IPv6 addresses in brackets
+fn parse_node(node: &str) -> Option<IpAddr> {
+    node.parse::<IpAddr>()
+        .ok()
+        .or_else(|| node.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+        .or_else(|| {
+            node.strip_prefix('[')
+                .and_then(|node| node.strip_suffix(']'))
+                .and_then(|node| node.parse().ok())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn trusted() -> Vec<IpNet> {
+        vec!["10.0.0.0/8".parse().unwrap(), "fd00::/8".parse().unwrap()]
+    }
+
+    fn header_map(entries: &[(&str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in entries {
+            headers.append(
+                header::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                HeaderValue::from_str(value).unwrap(),
+            );
+        }
+        headers
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn ignores_headers_from_untrusted_peers() {
+        let headers = header_map(&[
+            ("Forwarded", "for=198.51.100.1"),
+            ("X-Forwarded-For", "198.51.100.2"),
+        ]);
+        assert_eq!(
+            resolve(ip("203.0.113.9"), &headers, &trusted()),
+            ip("203.0.113.9")
+        );
+        assert_eq!(resolve(ip("203.0.113.9"), &headers, &[]), ip("203.0.113.9"));
+    }
+
+    #[test]
+    fn skips_entries_a_client_put_before_the_proxies() {
+        let headers = header_map(&[("X-Forwarded-For", "1.1.1.1, 203.0.113.9, 10.0.0.2")]);
+        assert_eq!(
+            resolve(ip("10.0.0.1"), &headers, &trusted()),
+            ip("203.0.113.9")
+        );
+    }
+
+    #[test]
+    fn stops_at_malformed_hops() {
+        let headers = header_map(&[("X-Forwarded-For", "203.0.113.9, garbage, 10.0.0.2")]);
+        assert_eq!(
+            resolve(ip("10.0.0.1"), &headers, &trusted()),
+            ip("10.0.0.2")
+        );
+
+        let headers = header_map(&[("Forwarded", "for=203.0.113.9, by=10.0.0.2")]);
+        assert_eq!(
+            resolve(ip("10.0.0.1"), &headers, &trusted()),
+            ip("10.0.0.1")
+        );
+    }
+
+    #[test]
+    fn reads_bracketed_ipv6_with_ports_and_stops_at_obfuscated_nodes() {
+        let headers = header_map(&[(
+            "Forwarded",
+            r#"for="[2001:db8::1]:4711", for="[fd00::2]:80""#,
+        )]);
+        assert_eq!(
+            resolve(ip("fd00::1"), &headers, &trusted()),
+            ip("2001:db8::1")
+        );
+
+        let headers = header_map(&[("X-Forwarded-For", "203.0.113.9:1234")]);
+        assert_eq!(
+            resolve(ip("10.0.0.1"), &headers, &trusted()),
+            ip("203.0.113.9")
+        );
+
+        for node in ["_hidden", "unknown", r#""_gazonk""#] {
+            let headers = header_map(&[(
+                "Forwarded",
+                &format!("for=203.0.113.9, for={}, for=10.0.0.2", node),
+            )]);
+            assert_eq!(
+                resolve(ip("10.0.0.1"), &headers, &trusted()),
+                ip("10.0.0.2")
+            );
+        }
+    }
+
+    #[test]
+    fn walks_every_forwarded_element_and_header() {
+        let headers = header_map(&[
+            ("Forwarded", "for=198.51.100.1;proto=https, For=203.0.113.9"),
+            ("Forwarded", "for=10.0.0.3;by=10.0.0.1"),
+            // Ignored when `Forwarded` is present
+            ("X-Forwarded-For", "192.0.2.1"),
+        ]);
+        assert_eq!(
+            resolve(ip("10.0.0.1"), &headers, &trusted()),
+            ip("203.0.113.9")
+        );
+
+        // Every hop is a trusted proxy, so the furthest one is the best guess
+        let headers = header_map(&[("Forwarded", "for=10.0.0.4, for=10.0.0.3")]);
+        assert_eq!(
+            resolve(ip("10.0.0.1"), &headers, &trusted()),
+            ip("10.0.0.4")
+        );
+    }
+}
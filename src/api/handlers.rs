@@ -1,38 +1,123 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::{IntoResponse, Redirect},
-    Json,
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Redirect},
+    Extension, Json,
 };
 
+use uuid::Uuid;
+
 use crate::{
-    application::url_service::UrlService,
-    domain::url::{CreateUrlRequest, CreateUrlResponse, StatsResponse},
-    error::ErrorResponse,
+    api::{middleware::bearer_token, pages},
+    application::{link_signing, qr_code::QrOptions, url_service::UrlService},
+    domain::{
+        audit::{AuditEventPage, AuditQuery},
+        collection::{Collection, CreateCollectionRequest, UpdateCollectionRequest},
+        job::JobStatus,
+        maintenance::ExpiredCleanupStats,
+        qr::{QrCodeQuery, QrFormat},
+        quota::{Quota, QuotaSubject, QuotaUsage, SetQuotaRequest},
+        revision::{RollbackRequest, UrlRevision},
+        signed_link::{SignLinkRequest, SignedLink},
+        transfer::{ExportQuery, ImportJob, ImportQuery, RestoreQuery, RestoreSummary},
+        url::{
+            BatchCreateUrlRequest, BatchCreateUrlResponse, CreateUrlRequest, CreateUrlResponse,
+            PageQuery, RedirectQuery, StatsQuery, StatsResponse, UpdateUrlRequest, Url,
+            UrlListQuery, UrlListResponse, VisitKind,
+        },
+        user::{CreateUserRequest, LoginRequest, LoginResponse, Principal, User},
+        workspace::{
+            AcceptInvitationRequest, CreateApiKeyRequest, CreateInvitationRequest,
+            CreateWorkspaceRequest, Invitation, UpdateMemberRequest, Workspace, WorkspaceApiKey,
+            WorkspaceMember,
+        },
+    },
+    error::{AppError, ErrorResponse},
 };
 
 /// Create a short URL
 ///
-/// Creates a new shortened URL from the original URL provided.
+/// Creates a new shortened URL from the original URL provided. Links created with a
+/// workspace API key, or with a session and the `X-Workspace-Id` header, belong to the
+/// workspace.
 #[utoipa::path(
     post,
     path = "/api/urls",
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Makes retries return the original response instead of creating another link")
+    ),
     request_body = CreateUrlRequest,
     responses(
-        (status = 200, description = "Short URL created successfully", body = CreateUrlResponse),
+        (status = 201, description = "Short URL created successfully", body = CreateUrlResponse),
+        (status = 200, description = "Existing short URL reused for the same destination", body = CreateUrlResponse),
         (status = 400, description = "Invalid URL provided"),
+        (status = 403, description = "Forbidden - Viewers cannot change workspace links, or the total or active link quota is reached"),
+        (status = 409, description = "A request with the same idempotency key is still being processed"),
+        (status = 422, description = "The idempotency key was already used with a different request"),
+        (status = 429, description = "The daily link quota is reached"),
         (status = 500, description = "Internal server error")
     ),
     tag = "URL Shortener API"
 )]
 pub async fn create_short_url(
     State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
     Json(request): Json<CreateUrlRequest>,
 ) -> impl IntoResponse {
     // Debug log the request
     tracing::debug!("Received create short URL request: {:?}", request);
 
-    match service.create_short_url(request).await {
+    match service.create_short_url(&principal, request).await {
+        Ok(response) => {
+            let status = if response.created {
+                StatusCode::CREATED
+            } else {
+                StatusCode::OK
+            };
+            (status, Json(response)).into_response()
+        }
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Create short URLs in bulk
+///
+/// Creates up to 5000 short URLs at once and returns one result per item, in request order.
+/// In `transactional` mode (the default) a single failing item means no link is created;
+/// in `partial` mode valid items are created and failures are reported per item.
+#[utoipa::path(
+    post,
+    path = "/api/urls/batch",
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Makes retries return the original response instead of creating the links again")
+    ),
+    request_body = BatchCreateUrlRequest,
+    responses(
+        (status = 200, description = "Batch processed, see the per-item results", body = BatchCreateUrlResponse),
+        (status = 400, description = "Empty or oversized batch"),
+        (status = 403, description = "Forbidden - Viewers cannot change workspace links, or the batch exceeds the total or active link quota"),
+        (status = 429, description = "The batch exceeds the daily link quota"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "URL Shortener API"
+)]
+pub async fn create_short_urls(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Json(request): Json<BatchCreateUrlRequest>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!(
+        "Received batch create request with {} item(s), mode {:?}",
+        request.urls.len(),
+        request.mode
+    );
+
+    match service.create_short_urls(&principal, request).await {
         Ok(response) => (StatusCode::OK, Json(response)).into_response(),
         Err(err) => {
             // Create error response with environment
@@ -44,15 +129,24 @@ pub async fn create_short_url(
 /// Redirect to original URL
 ///
 /// Redirects to the original URL associated with the provided short code.
+/// Appending `+` to the short code or passing `?preview=1` shows a preview page instead.
+/// Flagged links and links outside the allowed domains show a warning page until `?confirm=1` is passed.
+/// Link preview crawlers receive a page with the link's Open Graph overrides when it has any.
+/// Signed links (`exp`, `kid`, `sig` and optionally `rcpt`) are checked before the link is
+/// looked up, and links that require a signature cannot be opened or previewed without one.
 #[utoipa::path(
     get,
     path = "/{short_code}",
     params(
-        ("short_code" = String, Path, description = "Short code for the URL")
+        ("short_code" = String, Path, description = "Short code for the URL"),
+        RedirectQuery
     ),
     responses(
         (status = 302, description = "Redirect to the original URL"),
+        (status = 200, description = "Preview page, or warning page for flagged and external links", content_type = "text/html"),
+        (status = 403, description = "Invalid signature, or the link requires one"),
         (status = 404, description = "Short URL not found"),
+        (status = 410, description = "Short URL has been deleted, or the signed link has expired"),
         (status = 500, description = "Internal server error")
     ),
     tag = "URL Shortener API"
@@ -60,12 +154,463 @@ pub async fn create_short_url(
 pub async fn redirect_to_url(
     State(service): State<UrlService>,
     Path(short_code): Path<String>,
+    Query(query): Query<RedirectQuery>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
     // Debug log the request
     tracing::debug!("Received redirect request for short code: {}", short_code);
 
-    match service.get_url(&short_code).await {
-        Ok(url) => Redirect::temporary(&url).into_response(),
+    if let Some(code) = short_code.strip_suffix('+') {
+        return preview_url(State(service), Path(code.to_string()))
+            .await
+            .into_response();
+    }
+    if query.preview.unwrap_or(false) {
+        return preview_url(State(service), Path(short_code))
+            .await
+            .into_response();
+    }
+
+    // Forged and expired signed links are turned away without looking the link up
+    let signature = query.signature();
+    let signed = match service.verify_link_signature(&short_code, signature.as_ref()) {
+        Ok(signed) => signed,
+        Err(err) => {
+            // Create error response with environment
+            return ErrorResponse::new(err, service.get_environment()).into_response();
+        }
+    };
+
+    let target = match service.get_url(&short_code).await {
+        Ok(target) if target.require_signature && !signed => {
+            let err = AppError::Forbidden(
+                "This link can only be opened through a signed link".to_string(),
+            );
+            return ErrorResponse::new(err, service.get_environment()).into_response();
+        }
+        Ok(target) => target,
+        Err(err) => {
+            // Create error response with environment
+            return ErrorResponse::new(err, service.get_environment()).into_response();
+        }
+    };
+
+    // Link preview crawlers get the Open Graph overrides instead of the destination's metadata
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if let Some(ref card) = target.social_card {
+        if is_link_preview_crawler(user_agent) {
+            tracing::debug!("Serving social card for {} to {}", short_code, user_agent);
+            return Html(pages::social_card_page(
+                card,
+                &target.original_url,
+                &service.short_url(&short_code),
+            ))
+            .into_response();
+        }
+    }
+
+    // Flagged and external links get a warning page until the visitor confirms
+    let kind = match (target.interstitial, query.confirm.unwrap_or(false)) {
+        (false, _) => VisitKind::Redirect,
+        (true, true) => VisitKind::InterstitialConfirmed,
+        (true, false) => VisitKind::InterstitialShown,
+    };
+
+    // Failing to record analytics should not break the redirect
+    if let Err(err) = service.record_visit(target.id, kind).await {
+        tracing::warn!("Failed to record visit for {}: {}", short_code, err);
+    }
+
+    if kind == VisitKind::InterstitialShown {
+        let short_url = service.short_url(&short_code);
+        // Confirming has to carry the signature along
+        let confirm_query = match signature {
+            Some(ref signature) => format!("{}&confirm=1", link_signing::query(signature)),
+            None => "confirm=1".to_string(),
+        };
+        return Html(pages::interstitial_page(
+            &target.original_url,
+            &format!("{}?{}", short_url, confirm_query),
+            &format!("{}+", short_url),
+            service.interstitial_countdown_secs(),
+        ))
+        .into_response();
+    }
+
+    Redirect::temporary(&target.original_url).into_response()
+}
+
+/// User agents of chat apps and social networks that fetch pages to unfurl links
+const LINK_PREVIEW_CRAWLERS: &[&str] = &[
+    "facebookexternalhit",
+    "facebookcatalog",
+    "twitterbot",
+    "slackbot",
+    "slack-imgproxy",
+    "discordbot",
+    "linkedinbot",
+    "whatsapp",
+    "telegrambot",
+    "skypeuripreview",
+    "microsoftpreview",
+    "pinterest",
+    "redditbot",
+    "embedly",
+    "mastodon",
+    "applebot",
+    "vkshare",
+    "viber",
+];
+
+fn is_link_preview_crawler(user_agent: &str) -> bool {
+    let user_agent = user_agent.to_lowercase();
+    LINK_PREVIEW_CRAWLERS
+        .iter()
+        .any(|crawler| user_agent.contains(crawler))
+}
+
+/// Preview a short URL
+///
+/// Renders an HTML page describing where the short URL leads without redirecting
+/// or counting a visit. Reached through `/{short_code}+` or `/{short_code}?preview=1`.
+pub async fn preview_url(
+    State(service): State<UrlService>,
+    Path(short_code): Path<String>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received preview request for short code: {}", short_code);
+
+    match service.get_url_preview(&short_code).await {
+        Ok(url) => Html(pages::preview_page(&url, &service.short_url(&short_code))).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Get a QR code for a short URL
+///
+/// Renders a QR code encoding the short URL as PNG or SVG. The format is taken from
+/// the `format` query parameter or negotiated from the `Accept` header.
+#[utoipa::path(
+    get,
+    path = "/api/urls/{short_code}/qr",
+    params(
+        ("short_code" = String, Path, description = "Short code for the URL"),
+        QrCodeQuery
+    ),
+    responses(
+        (status = 200, description = "QR code image", content(
+            (Vec<u8> = "image/png"),
+            (String = "image/svg+xml")
+        )),
+        (status = 400, description = "Invalid rendering options"),
+        (status = 404, description = "Short URL not found"),
+        (status = 410, description = "Short URL has been deleted"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "URL Shortener API"
+)]
+pub async fn get_qr_code(
+    State(service): State<UrlService>,
+    Path(short_code): Path<String>,
+    Query(query): Query<QrCodeQuery>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received QR code request for short code: {}", short_code);
+
+    let format = query.format.unwrap_or_else(|| {
+        QrFormat::from_accept(
+            headers
+                .get(header::ACCEPT)
+                .and_then(|value| value.to_str().ok()),
+        )
+    });
+
+    let result = match QrOptions::from_query(query, format) {
+        Ok(options) => service.get_qr_code(&short_code, &options).await,
+        Err(err) => Err(err),
+    };
+
+    match result {
+        Ok(image) => (
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, format.content_type()),
+                (header::VARY, "Accept"),
+            ],
+            image,
+        )
+            .into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Update a short URL
+///
+/// Updates the settings of an existing short URL, such as flagging it for a warning page.
+#[utoipa::path(
+    patch,
+    path = "/api/urls/{short_code}",
+    params(
+        ("short_code" = String, Path, description = "Short code for the URL")
+    ),
+    request_body = UpdateUrlRequest,
+    responses(
+        (status = 200, description = "Short URL updated successfully", body = Url),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Viewers cannot change workspace links"),
+        (status = 404, description = "Short URL not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "URL Shortener API"
+)]
+pub async fn update_url(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Path(short_code): Path<String>,
+    Json(request): Json<UpdateUrlRequest>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!(
+        "Received update request for short code {}: {:?}",
+        short_code,
+        request
+    );
+
+    match service.update_url(&principal, &short_code, request).await {
+        Ok(url) => (StatusCode::OK, Json(url)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Delete a short URL
+///
+/// Moves the link to the trash. It stops redirecting right away and can be restored until it
+/// is purged after the retention period (`TRASH_RETENTION_DAYS`).
+#[utoipa::path(
+    delete,
+    path = "/api/urls/{short_code}",
+    params(
+        ("short_code" = String, Path, description = "Short code for the URL")
+    ),
+    responses(
+        (status = 204, description = "Short URL moved to the trash"),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Viewers cannot change workspace links"),
+        (status = 404, description = "Short URL not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "URL Shortener API"
+)]
+pub async fn delete_url(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Path(short_code): Path<String>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received delete request for short code: {}", short_code);
+
+    match service.delete_url(&principal, &short_code).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Restore a deleted short URL
+///
+/// Takes a link out of the trash so it redirects again.
+#[utoipa::path(
+    post,
+    path = "/api/urls/{short_code}/restore",
+    params(
+        ("short_code" = String, Path, description = "Short code for the URL")
+    ),
+    responses(
+        (status = 200, description = "Short URL restored", body = Url),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Viewers cannot change workspace links"),
+        (status = 404, description = "Short URL not found in the trash"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "URL Shortener API"
+)]
+pub async fn restore_url(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Path(short_code): Path<String>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received restore request for short code: {}", short_code);
+
+    match service.restore_deleted_url(&principal, &short_code).await {
+        Ok(url) => (StatusCode::OK, Json(url)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Get the history of a short URL
+///
+/// Returns the revisions of the link's destination, expiry, rules and flags, newest first.
+#[utoipa::path(
+    get,
+    path = "/api/urls/{short_code}/history",
+    params(
+        ("short_code" = String, Path, description = "Short code for the URL"),
+        PageQuery
+    ),
+    responses(
+        (status = 200, description = "Revisions retrieved successfully", body = Vec<UrlRevision>),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 404, description = "Short URL not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "URL Shortener API"
+)]
+pub async fn get_url_history(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Path(short_code): Path<String>,
+    Query(page): Query<PageQuery>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!(
+        "Received history request for short code {}: {:?}",
+        short_code,
+        page
+    );
+
+    match service
+        .get_url_history(&principal, &short_code, &page)
+        .await
+    {
+        Ok(revisions) => (StatusCode::OK, Json(revisions)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Roll back a short URL
+///
+/// Restores the destination, expiry, rules and flags of an earlier revision. The rollback is
+/// recorded as a new revision and takes effect immediately.
+#[utoipa::path(
+    post,
+    path = "/api/urls/{short_code}/rollback",
+    params(
+        ("short_code" = String, Path, description = "Short code for the URL")
+    ),
+    request_body = RollbackRequest,
+    responses(
+        (status = 200, description = "Short URL rolled back", body = Url),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Viewers cannot change workspace links"),
+        (status = 404, description = "Short URL or revision not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "URL Shortener API"
+)]
+pub async fn rollback_url(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Path(short_code): Path<String>,
+    Json(request): Json<RollbackRequest>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!(
+        "Received rollback request for short code {}: {:?}",
+        short_code,
+        request
+    );
+
+    match service
+        .rollback_url(&principal, &short_code, request.revision)
+        .await
+    {
+        Ok(url) => (StatusCode::OK, Json(url)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// List broken links
+///
+/// Returns active links whose latest destination check failed, most persistently broken first.
+#[utoipa::path(
+    get,
+    path = "/api/urls/broken",
+    params(PageQuery),
+    responses(
+        (status = 200, description = "Broken links retrieved successfully", body = Vec<Url>),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "URL Shortener API"
+)]
+pub async fn get_broken_links(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Query(page): Query<PageQuery>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received broken links request: {:?}", page);
+
+    match service.get_broken_links(&principal, &page).await {
+        Ok(urls) => (StatusCode::OK, Json(urls)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// List short URLs
+///
+/// Returns short URLs, newest first, optionally filtered by tag or collection. With
+/// `deleted=true` the links in the trash are listed instead. Accounts only see their own
+/// links, admins see every link.
+#[utoipa::path(
+    get,
+    path = "/api/urls",
+    params(UrlListQuery),
+    responses(
+        (status = 200, description = "Short URLs retrieved successfully", body = UrlListResponse),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "URL Shortener API"
+)]
+pub async fn list_urls(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Query(query): Query<UrlListQuery>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received list URLs request: {:?}", query);
+
+    match service.list_urls(&principal, &query).await {
+        Ok(response) => (StatusCode::OK, Json(response)).into_response(),
         Err(err) => {
             // Create error response with environment
             ErrorResponse::new(err, service.get_environment()).into_response()
@@ -75,22 +620,28 @@ pub async fn redirect_to_url(
 
 /// Get URL statistics
 ///
-/// Returns statistics about all shortened URLs in the system.
+/// Returns statistics about shortened URLs, optionally narrowed to a tag or collection.
+/// Accounts get the stats of their own links, admins those of every link.
 #[utoipa::path(
     get,
     path = "/api/stats",
+    params(StatsQuery),
     responses(
         (status = 200, description = "Statistics retrieved successfully", body = StatsResponse),
-        (status = 401, description = "Unauthorized - Invalid or missing API key"),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
         (status = 500, description = "Internal server error")
     ),
     tag = "URL Shortener API"
 )]
-pub async fn get_stats(State(service): State<UrlService>) -> impl IntoResponse {
+pub async fn get_stats(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Query(query): Query<StatsQuery>,
+) -> impl IntoResponse {
     // Debug log the request
-    tracing::debug!("Received stats request");
+    tracing::debug!("Received stats request: {:?}", query);
 
-    match service.get_stats().await {
+    match service.get_stats(&principal, &query).await {
         Ok(response) => (StatusCode::OK, Json(response)).into_response(),
         Err(err) => {
             // Create error response with environment
@@ -98,3 +649,1443 @@ pub async fn get_stats(State(service): State<UrlService>) -> impl IntoResponse {
         }
     }
 }
+
+/// Get expired-link cleanup statistics
+///
+/// Returns the cleanup settings and what the cleanup has archived, deleted and released since
+/// the server started.
+#[utoipa::path(
+    get,
+    path = "/api/stats/expired-cleanup",
+    responses(
+        (status = 200, description = "Cleanup statistics retrieved successfully", body = ExpiredCleanupStats),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Admin access required")
+    ),
+    tag = "URL Shortener API"
+)]
+pub async fn get_expired_cleanup_stats(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received expired-link cleanup stats request");
+
+    match service.get_expired_cleanup_stats(&principal).await {
+        Ok(stats) => (StatusCode::OK, Json(stats)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// List background jobs
+///
+/// Returns every scheduled maintenance job with the time, duration and outcome of its latest
+/// run on any instance.
+#[utoipa::path(
+    get,
+    path = "/api/jobs",
+    responses(
+        (status = 200, description = "Jobs retrieved successfully", body = Vec<JobStatus>),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Jobs"
+)]
+pub async fn list_jobs(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received job list request");
+
+    match service.list_jobs(&principal).await {
+        Ok(jobs) => (StatusCode::OK, Json(jobs)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// List collections
+///
+/// Returns the collections of the caller's workspace, or those outside any workspace for
+/// personal accounts, with the number of links in each. Admins outside a workspace see
+/// every collection.
+#[utoipa::path(
+    get,
+    path = "/api/collections",
+    responses(
+        (status = 200, description = "Collections retrieved successfully", body = Vec<Collection>),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Collections"
+)]
+pub async fn list_collections(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received list collections request");
+
+    match service.list_collections(&principal).await {
+        Ok(collections) => (StatusCode::OK, Json(collections)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Create a collection
+#[utoipa::path(
+    post,
+    path = "/api/collections",
+    request_body = CreateCollectionRequest,
+    responses(
+        (status = 201, description = "Collection created successfully", body = Collection),
+        (status = 400, description = "Invalid collection name"),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 409, description = "A collection with this name already exists"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Collections"
+)]
+pub async fn create_collection(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Json(request): Json<CreateCollectionRequest>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received create collection request: {:?}", request);
+
+    match service.create_collection(&principal, request).await {
+        Ok(collection) => (StatusCode::CREATED, Json(collection)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Get a collection
+#[utoipa::path(
+    get,
+    path = "/api/collections/{id}",
+    params(
+        ("id" = Uuid, Path, description = "Collection ID")
+    ),
+    responses(
+        (status = 200, description = "Collection retrieved successfully", body = Collection),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 404, description = "Collection not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Collections"
+)]
+pub async fn get_collection(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received get collection request for {}", id);
+
+    match service.get_collection(&principal, id).await {
+        Ok(collection) => (StatusCode::OK, Json(collection)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Update a collection
+#[utoipa::path(
+    patch,
+    path = "/api/collections/{id}",
+    params(
+        ("id" = Uuid, Path, description = "Collection ID")
+    ),
+    request_body = UpdateCollectionRequest,
+    responses(
+        (status = 200, description = "Collection updated successfully", body = Collection),
+        (status = 400, description = "Invalid collection name"),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 404, description = "Collection not found"),
+        (status = 409, description = "A collection with this name already exists"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Collections"
+)]
+pub async fn update_collection(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<UpdateCollectionRequest>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!(
+        "Received update request for collection {}: {:?}",
+        id,
+        request
+    );
+
+    match service.update_collection(&principal, id, request).await {
+        Ok(collection) => (StatusCode::OK, Json(collection)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Delete a collection
+///
+/// Links in the collection are kept and simply no longer belong to any collection.
+#[utoipa::path(
+    delete,
+    path = "/api/collections/{id}",
+    params(
+        ("id" = Uuid, Path, description = "Collection ID")
+    ),
+    responses(
+        (status = 204, description = "Collection deleted successfully"),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 404, description = "Collection not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Collections"
+)]
+pub async fn delete_collection(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received delete request for collection {}", id);
+
+    match service.delete_collection(&principal, id).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Import links
+///
+/// Queues an import file and returns immediately. The `format` parameter selects the source:
+///
+/// - `csv` (default): a header row with a `url` column; `alias`, `expires_at` (RFC 3339 or
+///   `YYYY-MM-DD`), `tags` (separated by `|`), `title`, `description`, `created_at` and
+///   `visits` columns are optional. Files from the CSV export can be imported as they are.
+/// - `yourls`: a SQL dump of the `yourls_url` table or a CSV with its columns.
+/// - `bitly`: the CSV export of Bitly links.
+/// - `kutt`: the JSON returned by Kutt's links API.
+///
+/// Original short codes are kept when they are valid aliases here, along with the creation
+/// time and visit count. Links whose code is already taken are reported in the error report.
+#[utoipa::path(
+    post,
+    path = "/api/imports",
+    params(ImportQuery),
+    request_body(content = String, content_type = "text/csv"),
+    responses(
+        (status = 202, description = "Import queued", body = ImportJob),
+        (status = 400, description = "Invalid or empty import file"),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Import and export"
+)]
+pub async fn import_links(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Query(query): Query<ImportQuery>,
+    body: Bytes,
+) -> impl IntoResponse {
+    let format = query.format.unwrap_or_default();
+    // Debug log the request
+    tracing::debug!(
+        "Received {} import request of {} bytes",
+        format.as_str(),
+        body.len()
+    );
+
+    match service.start_import(&principal, format, &body).await {
+        Ok(job) => (StatusCode::ACCEPTED, Json(job)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// List imports
+///
+/// Returns import jobs, newest first.
+#[utoipa::path(
+    get,
+    path = "/api/imports",
+    params(PageQuery),
+    responses(
+        (status = 200, description = "Imports retrieved successfully", body = Vec<ImportJob>),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Import and export"
+)]
+pub async fn list_imports(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Query(page): Query<PageQuery>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received list imports request: {:?}", page);
+
+    match service.list_import_jobs(&principal, &page).await {
+        Ok(jobs) => (StatusCode::OK, Json(jobs)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Get import status
+///
+/// Returns the status and progress of an import.
+#[utoipa::path(
+    get,
+    path = "/api/imports/{id}",
+    params(
+        ("id" = Uuid, Path, description = "Import ID")
+    ),
+    responses(
+        (status = 200, description = "Import retrieved successfully", body = ImportJob),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 404, description = "Import not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Import and export"
+)]
+pub async fn get_import(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received import status request for {}", id);
+
+    match service.get_import_job(&principal, id).await {
+        Ok(job) => (StatusCode::OK, Json(job)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Download import error report
+///
+/// Returns the rows of an import that could not be created, with the reason, as CSV.
+#[utoipa::path(
+    get,
+    path = "/api/imports/{id}/errors",
+    params(
+        ("id" = Uuid, Path, description = "Import ID")
+    ),
+    responses(
+        (status = 200, description = "Error report", content_type = "text/csv", body = String),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 404, description = "Import not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Import and export"
+)]
+pub async fn get_import_errors(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received import error report request for {}", id);
+
+    match service.get_import_error_report(&principal, id).await {
+        Ok(report) => (
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"import-{}-errors.csv\"", id),
+                ),
+            ],
+            report,
+        )
+            .into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Export links
+///
+/// Streams all links with their stats as CSV or JSON lines.
+#[utoipa::path(
+    get,
+    path = "/api/urls/export",
+    params(ExportQuery),
+    responses(
+        (status = 200, description = "Exported links", content((String = "text/csv"), (String = "application/x-ndjson"))),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Import and export"
+)]
+pub async fn export_urls(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Query(query): Query<ExportQuery>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received export request: {:?}", query);
+
+    let format = query.format.unwrap_or_default();
+    let stream = match service.export_urls(&principal, format).await {
+        Ok(stream) => stream,
+        Err(err) => {
+            // Create error response with environment
+            return ErrorResponse::new(err, service.get_environment()).into_response();
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"links.{}\"", format.extension()),
+            ),
+        ],
+        Body::from_stream(stream),
+    )
+        .into_response()
+}
+
+/// Download a backup
+///
+/// Streams a gzip-compressed JSON-lines archive of every collection, tag and link, including
+/// visit counts and check state. The archive is versioned and read from a consistent snapshot.
+#[utoipa::path(
+    get,
+    path = "/api/backup",
+    responses(
+        (status = 200, description = "Backup archive", content_type = "application/gzip", body = Vec<u8>),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Backup"
+)]
+pub async fn download_backup(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received backup request");
+
+    let stream = match service.backup(&principal).await {
+        Ok(stream) => stream,
+        Err(err) => {
+            // Create error response with environment
+            return ErrorResponse::new(err, service.get_environment()).into_response();
+        }
+    };
+
+    let date = time::OffsetDateTime::now_utc().date();
+    (
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "application/gzip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"backup-{}.jsonl.gz\"", date),
+            ),
+        ],
+        Body::from_stream(stream),
+    )
+        .into_response()
+}
+
+/// Restore a backup
+///
+/// Restores an archive created by `GET /api/backup` in a single transaction. With the default
+/// `fail` policy nothing is changed when a link or collection already exists; `skip` keeps the
+/// existing entries and `overwrite` replaces them with the archived ones.
+#[utoipa::path(
+    post,
+    path = "/api/restore",
+    params(RestoreQuery),
+    request_body(content = Vec<u8>, content_type = "application/gzip"),
+    responses(
+        (status = 200, description = "Backup restored", body = RestoreSummary),
+        (status = 400, description = "Invalid, incomplete or unsupported archive"),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 409, description = "An entry already exists and the policy is `fail`"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Backup"
+)]
+pub async fn restore_backup(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Query(query): Query<RestoreQuery>,
+    body: Bytes,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!(
+        "Received restore request of {} bytes: {:?}",
+        body.len(),
+        query
+    );
+
+    match service
+        .restore(&principal, &body, query.on_conflict.unwrap_or_default())
+        .await
+    {
+        Ok(summary) => (StatusCode::OK, Json(summary)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Log in
+///
+/// Checks an email and password and returns a session token. Send it as
+/// `Authorization: Bearer <token>` to act as the account.
+#[utoipa::path(
+    post,
+    path = "/api/auth/login",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Logged in", body = LoginResponse),
+        (status = 401, description = "Unknown email or wrong password"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Accounts"
+)]
+pub async fn login(
+    State(service): State<UrlService>,
+    Json(request): Json<LoginRequest>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received login request");
+
+    match service.login(request).await {
+        Ok(response) => (StatusCode::OK, Json(response)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Log out
+///
+/// Ends the session of the bearer token the request is made with.
+#[utoipa::path(
+    post,
+    path = "/api/auth/logout",
+    responses(
+        (status = 204, description = "Logged out"),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Accounts"
+)]
+pub async fn logout(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received logout request");
+
+    // Requests made with the API key have no session to end
+    let Some(token) = bearer_token(&headers) else {
+        return StatusCode::NO_CONTENT.into_response();
+    };
+    match service.logout(&principal, token).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Get the current account
+///
+/// Returns the account of the session token the request is made with.
+#[utoipa::path(
+    get,
+    path = "/api/auth/me",
+    responses(
+        (status = 200, description = "Account retrieved successfully", body = User),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 404, description = "The request is not made with a session token")
+    ),
+    tag = "Accounts"
+)]
+pub async fn get_current_user(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received current account request");
+
+    match principal {
+        Principal::User(user) => (StatusCode::OK, Json(user)).into_response(),
+        _ => ErrorResponse::new(
+            AppError::NotFound("No account is logged in".to_string()),
+            service.get_environment(),
+        )
+        .into_response(),
+    }
+}
+
+/// Create an account
+///
+/// Admins can create regular and admin accounts. Without admin credentials, regular accounts
+/// can only be created while sign-up is enabled.
+#[utoipa::path(
+    post,
+    path = "/api/users",
+    request_body = CreateUserRequest,
+    responses(
+        (status = 201, description = "Account created successfully", body = User),
+        (status = 400, description = "Invalid email or password"),
+        (status = 401, description = "Unauthorized - Invalid credentials"),
+        (status = 403, description = "Forbidden - Sign-up is disabled or admin access required"),
+        (status = 409, description = "An account with this email already exists"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Accounts"
+)]
+pub async fn create_user(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Json(request): Json<CreateUserRequest>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received create account request for {}", request.email);
+
+    match service.create_user(&principal, request).await {
+        Ok(user) => (StatusCode::CREATED, Json(user)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// List accounts
+///
+/// Returns all accounts, oldest first.
+#[utoipa::path(
+    get,
+    path = "/api/users",
+    params(PageQuery),
+    responses(
+        (status = 200, description = "Accounts retrieved successfully", body = Vec<User>),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Accounts"
+)]
+pub async fn list_users(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Query(page): Query<PageQuery>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received list accounts request: {:?}", page);
+
+    match service.list_users(&principal, &page).await {
+        Ok(users) => (StatusCode::OK, Json(users)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Create a workspace
+///
+/// Creates a workspace with the calling account as its owner. Links created while sending
+/// its ID in the `X-Workspace-Id` header belong to the workspace.
+#[utoipa::path(
+    post,
+    path = "/api/workspaces",
+    request_body = CreateWorkspaceRequest,
+    responses(
+        (status = 201, description = "Workspace created successfully", body = Workspace),
+        (status = 400, description = "Invalid workspace name"),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Only accounts can create workspaces"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Workspaces"
+)]
+pub async fn create_workspace(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Json(request): Json<CreateWorkspaceRequest>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received create workspace request: {:?}", request);
+
+    match service.create_workspace(&principal, request).await {
+        Ok(workspace) => (StatusCode::CREATED, Json(workspace)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// List workspaces
+///
+/// Returns the workspaces of the calling account with its role in each. Admins see every
+/// workspace, and workspace API keys only their own.
+#[utoipa::path(
+    get,
+    path = "/api/workspaces",
+    responses(
+        (status = 200, description = "Workspaces retrieved successfully", body = Vec<Workspace>),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Workspaces"
+)]
+pub async fn list_workspaces(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received list workspaces request");
+
+    match service.list_workspaces(&principal).await {
+        Ok(workspaces) => (StatusCode::OK, Json(workspaces)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// List workspace members
+///
+/// Returns the members of a workspace with their roles. Any member can see them.
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{id}/members",
+    params(
+        ("id" = Uuid, Path, description = "Workspace ID")
+    ),
+    responses(
+        (status = 200, description = "Members retrieved successfully", body = Vec<WorkspaceMember>),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 404, description = "Workspace not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Workspaces"
+)]
+pub async fn list_workspace_members(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received list members request for workspace {}", id);
+
+    match service.list_members(&principal, id).await {
+        Ok(members) => (StatusCode::OK, Json(members)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Change a member's role
+///
+/// Requires the admin role. Nobody can grant a role above their own or change the role of a
+/// member above them, and the last owner cannot be demoted. Returns all members.
+#[utoipa::path(
+    patch,
+    path = "/api/workspaces/{id}/members/{user_id}",
+    params(
+        ("id" = Uuid, Path, description = "Workspace ID"),
+        ("user_id" = Uuid, Path, description = "Account ID of the member")
+    ),
+    request_body = UpdateMemberRequest,
+    responses(
+        (status = 200, description = "Role changed successfully", body = Vec<WorkspaceMember>),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Role too low"),
+        (status = 404, description = "Workspace or member not found"),
+        (status = 409, description = "The workspace would have no owner left"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Workspaces"
+)]
+pub async fn update_workspace_member(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Path((id, user_id)): Path<(Uuid, Uuid)>,
+    Json(request): Json<UpdateMemberRequest>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!(
+        "Received role change for member {} of workspace {}: {:?}",
+        user_id,
+        id,
+        request
+    );
+
+    match service
+        .update_member(&principal, id, user_id, request)
+        .await
+    {
+        Ok(members) => (StatusCode::OK, Json(members)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Remove a member
+///
+/// Members can always leave a workspace. Removing someone else requires the admin role and a
+/// role at least as high as theirs. The last owner cannot leave.
+#[utoipa::path(
+    delete,
+    path = "/api/workspaces/{id}/members/{user_id}",
+    params(
+        ("id" = Uuid, Path, description = "Workspace ID"),
+        ("user_id" = Uuid, Path, description = "Account ID of the member")
+    ),
+    responses(
+        (status = 204, description = "Member removed successfully"),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Role too low"),
+        (status = 404, description = "Workspace or member not found"),
+        (status = 409, description = "The workspace would have no owner left"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Workspaces"
+)]
+pub async fn remove_workspace_member(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Path((id, user_id)): Path<(Uuid, Uuid)>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!(
+        "Received removal of member {} from workspace {}",
+        user_id,
+        id
+    );
+
+    match service.remove_member(&principal, id, user_id).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Invite to a workspace
+///
+/// Requires the admin role. Returns the invitation with a token that only the account with
+/// the invited email can accept; the token is not shown again.
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{id}/invitations",
+    params(
+        ("id" = Uuid, Path, description = "Workspace ID")
+    ),
+    request_body = CreateInvitationRequest,
+    responses(
+        (status = 201, description = "Invitation created successfully", body = Invitation),
+        (status = 400, description = "Invalid email address"),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Role too low"),
+        (status = 404, description = "Workspace not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Workspaces"
+)]
+pub async fn create_invitation(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<CreateInvitationRequest>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!(
+        "Received invitation request for workspace {}: {:?}",
+        id,
+        request
+    );
+
+    match service.create_invitation(&principal, id, request).await {
+        Ok(invitation) => (StatusCode::CREATED, Json(invitation)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// List pending invitations
+///
+/// Requires the admin role. Returns the invitations that were neither accepted nor expired.
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{id}/invitations",
+    params(
+        ("id" = Uuid, Path, description = "Workspace ID")
+    ),
+    responses(
+        (status = 200, description = "Invitations retrieved successfully", body = Vec<Invitation>),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Role too low"),
+        (status = 404, description = "Workspace not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Workspaces"
+)]
+pub async fn list_invitations(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received list invitations request for workspace {}", id);
+
+    match service.list_invitations(&principal, id).await {
+        Ok(invitations) => (StatusCode::OK, Json(invitations)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Revoke an invitation
+///
+/// Requires the admin role.
+#[utoipa::path(
+    delete,
+    path = "/api/workspaces/{id}/invitations/{invitation_id}",
+    params(
+        ("id" = Uuid, Path, description = "Workspace ID"),
+        ("invitation_id" = Uuid, Path, description = "Invitation ID")
+    ),
+    responses(
+        (status = 204, description = "Invitation revoked successfully"),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Role too low"),
+        (status = 404, description = "Workspace or pending invitation not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Workspaces"
+)]
+pub async fn revoke_invitation(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Path((id, invitation_id)): Path<(Uuid, Uuid)>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!(
+        "Received revocation of invitation {} to workspace {}",
+        invitation_id,
+        id
+    );
+
+    match service
+        .revoke_invitation(&principal, id, invitation_id)
+        .await
+    {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Accept an invitation
+///
+/// Joins the workspace of an invitation sent to the calling account's email address.
+/// Accepting an invitation never lowers an existing role.
+#[utoipa::path(
+    post,
+    path = "/api/invitations/accept",
+    request_body = AcceptInvitationRequest,
+    responses(
+        (status = 200, description = "Invitation accepted", body = Workspace),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Only accounts can accept invitations"),
+        (status = 404, description = "Invitation not found, expired or for another email"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Workspaces"
+)]
+pub async fn accept_invitation(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Json(request): Json<AcceptInvitationRequest>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received accept invitation request");
+
+    match service.accept_invitation(&principal, request).await {
+        Ok(workspace) => (StatusCode::OK, Json(workspace)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Create a workspace API key
+///
+/// Requires the admin role. The key acts on the workspace with the given role when sent in
+/// the `X-API-Key` header; it is only returned in this response.
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{id}/api-keys",
+    params(
+        ("id" = Uuid, Path, description = "Workspace ID")
+    ),
+    request_body = CreateApiKeyRequest,
+    responses(
+        (status = 201, description = "API key created successfully", body = WorkspaceApiKey),
+        (status = 400, description = "Invalid name or role"),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Role too low"),
+        (status = 404, description = "Workspace not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Workspaces"
+)]
+pub async fn create_workspace_api_key(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<CreateApiKeyRequest>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!(
+        "Received API key request for workspace {}: {:?}",
+        id,
+        request
+    );
+
+    match service
+        .create_workspace_api_key(&principal, id, request)
+        .await
+    {
+        Ok(key) => (StatusCode::CREATED, Json(key)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// List workspace API keys
+///
+/// Requires the admin role. Returns the keys that were not revoked, without the keys
+/// themselves.
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{id}/api-keys",
+    params(
+        ("id" = Uuid, Path, description = "Workspace ID")
+    ),
+    responses(
+        (status = 200, description = "API keys retrieved successfully", body = Vec<WorkspaceApiKey>),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Role too low"),
+        (status = 404, description = "Workspace not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Workspaces"
+)]
+pub async fn list_workspace_api_keys(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received list API keys request for workspace {}", id);
+
+    match service.list_workspace_api_keys(&principal, id).await {
+        Ok(keys) => (StatusCode::OK, Json(keys)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Revoke a workspace API key
+///
+/// Requires the admin role. The key stops working immediately.
+#[utoipa::path(
+    delete,
+    path = "/api/workspaces/{id}/api-keys/{key_id}",
+    params(
+        ("id" = Uuid, Path, description = "Workspace ID"),
+        ("key_id" = Uuid, Path, description = "API key ID")
+    ),
+    responses(
+        (status = 204, description = "API key revoked successfully"),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Role too low"),
+        (status = 404, description = "Workspace or active API key not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Workspaces"
+)]
+pub async fn revoke_workspace_api_key(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Path((id, key_id)): Path<(Uuid, Uuid)>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!(
+        "Received revocation of API key {} of workspace {}",
+        key_id,
+        id
+    );
+
+    match service
+        .revoke_workspace_api_key(&principal, id, key_id)
+        .await
+    {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Query the audit log
+///
+/// Lists recorded actions matching the filters, newest first. Pass `next_before` of a page
+/// as `before` to get the next one.
+#[utoipa::path(
+    get,
+    path = "/api/audit",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "Audit events retrieved successfully", body = AuditEventPage),
+        (status = 400, description = "Invalid filter"),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Audit"
+)]
+pub async fn list_audit_events(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Query(query): Query<AuditQuery>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received audit log request: {:?}", query);
+
+    match service.list_audit_events(&principal, &query).await {
+        Ok(page) => (StatusCode::OK, Json(page)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Export the audit log
+///
+/// Streams every recorded action matching the filters as JSON lines, newest first.
+#[utoipa::path(
+    get,
+    path = "/api/audit/export",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "Exported audit events", content_type = "application/x-ndjson", body = String),
+        (status = 400, description = "Invalid filter"),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Audit"
+)]
+pub async fn export_audit_events(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Query(query): Query<AuditQuery>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received audit log export request: {:?}", query);
+
+    let stream = match service.export_audit_events(&principal, query).await {
+        Ok(stream) => stream,
+        Err(err) => {
+            // Create error response with environment
+            return ErrorResponse::new(err, service.get_environment()).into_response();
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "application/x-ndjson".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"audit.jsonl\"".to_string(),
+            ),
+        ],
+        Body::from_stream(stream),
+    )
+        .into_response()
+}
+
+/// List link quotas
+#[utoipa::path(
+    get,
+    path = "/api/quotas",
+    params(PageQuery),
+    responses(
+        (status = 200, description = "Quotas retrieved successfully", body = Vec<Quota>),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Quotas"
+)]
+pub async fn list_quotas(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Query(page): Query<PageQuery>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received list quotas request: {:?}", page);
+
+    match service.list_quotas(&principal, &page).await {
+        Ok(quotas) => (StatusCode::OK, Json(quotas)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Set a link quota
+///
+/// Limits the links an account creates outside workspaces, the links of a workspace, or the
+/// links created with a workspace API key. Links created with a workspace API key count
+/// towards both the key and its workspace. Omitted limits are unlimited, and daily limits
+/// reset at midnight UTC.
+#[utoipa::path(
+    put,
+    path = "/api/quotas/{subject_type}/{subject_id}",
+    params(
+        ("subject_type" = QuotaSubject, Path, description = "What the quota limits"),
+        ("subject_id" = Uuid, Path, description = "Account, workspace or workspace API key ID")
+    ),
+    request_body = SetQuotaRequest,
+    responses(
+        (status = 200, description = "Quota set successfully", body = Quota),
+        (status = 400, description = "Invalid limits"),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 404, description = "Subject not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Quotas"
+)]
+pub async fn set_quota(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Path((subject_type, subject_id)): Path<(QuotaSubject, Uuid)>,
+    Json(request): Json<SetQuotaRequest>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!(
+        "Received set quota request for {} {}: {:?}",
+        subject_type.as_str(),
+        subject_id,
+        request
+    );
+
+    match service
+        .set_quota(&principal, subject_type, subject_id, request)
+        .await
+    {
+        Ok(quota) => (StatusCode::OK, Json(quota)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Remove a link quota
+///
+/// The subject can create links without limit again. Its usage keeps being counted.
+#[utoipa::path(
+    delete,
+    path = "/api/quotas/{subject_type}/{subject_id}",
+    params(
+        ("subject_type" = QuotaSubject, Path, description = "What the quota limits"),
+        ("subject_id" = Uuid, Path, description = "Account, workspace or workspace API key ID")
+    ),
+    responses(
+        (status = 204, description = "Quota removed successfully"),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Admin access required"),
+        (status = 404, description = "Quota not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Quotas"
+)]
+pub async fn delete_quota(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Path((subject_type, subject_id)): Path<(QuotaSubject, Uuid)>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!(
+        "Received delete quota request for {} {}",
+        subject_type.as_str(),
+        subject_id
+    );
+
+    match service
+        .delete_quota(&principal, subject_type, subject_id)
+        .await
+    {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Get quota usage
+///
+/// Shows how much of each quota that applies to the caller's new links is used and how
+/// much remains: the account's quota for personal links, the workspace's quota for links
+/// created in a workspace, and also the key's own quota for workspace API keys.
+#[utoipa::path(
+    get,
+    path = "/api/usage",
+    responses(
+        (status = 200, description = "Usage retrieved successfully", body = Vec<QuotaUsage>),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Quotas"
+)]
+pub async fn get_usage(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!("Received quota usage request");
+
+    match service.get_usage(&principal).await {
+        Ok(usage) => (StatusCode::OK, Json(usage)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
+
+/// Mint signed links
+///
+/// Creates signed variants of a link, one per recipient, each with its own expiry. Signed
+/// links carry their expiry, recipient and key ID next to an HMAC signature, so they stop
+/// working when they expire without anything being revoked. Combine with
+/// `require_signature` on the link so it cannot be opened without a signature.
+#[utoipa::path(
+    post,
+    path = "/api/urls/{short_code}/signed",
+    params(
+        ("short_code" = String, Path, description = "Short code for the URL")
+    ),
+    request_body = SignLinkRequest,
+    responses(
+        (status = 200, description = "Signed links minted successfully", body = Vec<SignedLink>),
+        (status = 400, description = "Invalid expiry or recipient, or link signing is not configured"),
+        (status = 401, description = "Unauthorized - Invalid or missing credentials"),
+        (status = 403, description = "Forbidden - Viewers cannot change workspace links"),
+        (status = 404, description = "Short URL not found"),
+        (status = 410, description = "Short URL has been deleted"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "URL Shortener API"
+)]
+pub async fn sign_url(
+    State(service): State<UrlService>,
+    Extension(principal): Extension<Principal>,
+    Path(short_code): Path<String>,
+    Json(request): Json<SignLinkRequest>,
+) -> impl IntoResponse {
+    // Debug log the request
+    tracing::debug!(
+        "Received sign request for short code {}: {} recipient(s)",
+        short_code,
+        request.recipients.len()
+    );
+
+    match service.sign_url(&principal, &short_code, request).await {
+        Ok(links) => (StatusCode::OK, Json(links)).into_response(),
+        Err(err) => {
+            // Create error response with environment
+            ErrorResponse::new(err, service.get_environment()).into_response()
+        }
+    }
+}
@@ -1,30 +1,624 @@
-use axum::{body::Body, http::Request, middleware::Next, response::IntoResponse};
+use std::net::SocketAddr;
+use std::time::Duration;
 
-use crate::{application::url_service::UrlService, error::AppError};
+use axum::{
+    body::{to_bytes, Body, HttpBody},
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap, HeaderValue, Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
 
-pub async fn api_key_auth(
-    req: Request<Body>,
+use crate::{
+    api::client_ip,
+    application::{accounts::WORKSPACE_KEY_PREFIX, audit::RequestContext, url_service::UrlService},
+    domain::user::Principal,
+    error::{AppError, ErrorResponse},
+};
+
+const WORKSPACE_HEADER: &str = "X-Workspace-Id";
+const REQUEST_ID_HEADER: &str = "X-Request-Id";
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+const IDEMPOTENT_REPLAYED_HEADER: &str = "Idempotent-Replayed";
+const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+// Request and response bodies are buffered to fingerprint and store them
+const MAX_IDEMPOTENT_BODY_BYTES: usize = 2 * 1024 * 1024;
+// How long a key stays locked after its first request stops refreshing it, for example
+// because the server went down while processing it
+const IN_PROGRESS_TTL_SECS: u64 = 60;
+// How often the lock is refreshed while the first request is being processed
+const IN_PROGRESS_REFRESH_SECS: u64 = 20;
+
+/// Gives each request an ID, taken from a well-formed `X-Request-Id` header or generated,
+/// and returns it in the response. The ID and the client's address, as forwarded by trusted
+/// proxies, are logged, recorded with the audit events of the request and made available to
+/// handlers as an `Extension<RequestContext>`.
+pub async fn request_context(
+    State(service): State<UrlService>,
+    mut req: Request<Body>,
     next: Next,
-) -> Result<impl IntoResponse, impl IntoResponse> {
-    // Extract the API key from request headers
-    let auth_header = req
+) -> Response {
+    let request_id = req
         .headers()
-        .get("X-API-KEY")
-        .and_then(|header| header.to_str().ok());
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LENGTH
+                && id.chars().all(|c| c.is_ascii_graphic())
+        })
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let context = RequestContext {
+        client_ip: req
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| {
+                client_ip::resolve(addr.ip(), req.headers(), service.trusted_proxies())
+            }),
+        request_id,
+    };
+    req.extensions_mut().insert(context.clone());
 
-    // Get the service instance from the request extensions
+    let header = HeaderValue::from_str(&context.request_id).ok();
+    let mut response = context.scope(next.run(req)).await;
+    if let Some(header) = header {
+        response.headers_mut().insert(REQUEST_ID_HEADER, header);
+    }
+    response
+}
+
+/// Rejects requests from clients outside the admin allowlist, when one is configured.
+/// Only wraps the instance-wide admin endpoints; users reach their own links from anywhere.
+pub async fn restrict_to_allowed_ips(
+    req: Request<Body>,
+    next: Next,
+) -> Result<impl IntoResponse, impl IntoResponse> {
     let service = req
         .extensions()
         .get::<UrlService>()
         .expect("UrlService not found in request extensions");
+    let allowed = service.admin_allowed_ips();
+    if allowed.is_empty() {
+        return Ok(next.run(req).await);
+    }
 
-    let api_key = service.get_api_key();
-
-    match auth_header {
-        Some(key) if key == api_key => Ok(next.run(req).await),
+    let client_ip = req
+        .extensions()
+        .get::<RequestContext>()
+        .and_then(|context| context.client_ip);
+    match client_ip {
+        Some(ip) if allowed.iter().any(|net| net.contains(&ip)) => Ok(next.run(req).await),
         _ => {
+            tracing::warn!(
+                "Admin API request from {:?} outside the allowed addresses",
+                client_ip
+            );
+            Err(
+                AppError::Forbidden("Access from this address is not allowed".to_string())
+                    .into_response(),
+            )
+        }
+    }
+}
+
+/// Requires the API key or a session token, and makes the caller available to handlers as
+/// an `Extension<Principal>`
+pub async fn authenticate(
+    mut req: Request<Body>,
+    next: Next,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    let service = req
+        .extensions()
+        .get::<UrlService>()
+        .expect("UrlService not found in request extensions")
+        .clone();
+
+    match resolve_principal(&service, req.headers()).await {
+        Ok(Some(principal)) => {
+            req.extensions_mut().insert(principal);
+            Ok(next.run(req).await)
+        }
+        Ok(None) => {
             tracing::warn!("Unauthorized API access attempt");
             Err(AppError::Unauthorized.into_response())
         }
+        Err(err) => Err(err.into_response()),
+    }
+}
+
+/// Like `authenticate`, but lets requests without credentials through as anonymous
+pub async fn identify(
+    mut req: Request<Body>,
+    next: Next,
+) -> Result<impl IntoResponse, impl IntoResponse> {
+    let service = req
+        .extensions()
+        .get::<UrlService>()
+        .expect("UrlService not found in request extensions")
+        .clone();
+
+    match resolve_principal(&service, req.headers()).await {
+        Ok(principal) => {
+            req.extensions_mut()
+                .insert(principal.unwrap_or(Principal::Anonymous));
+            Ok(next.run(req).await)
+        }
+        Err(err) => Err(err.into_response()),
+    }
+}
+
+/// Finds the caller from the `X-API-Key` header (the server's key or a workspace key) or an
+/// `Authorization: Bearer` session token, optionally acting in the workspace named by the
+/// `X-Workspace-Id` header. Credentials that are present but wrong are rejected rather than
+/// treated as missing.
+async fn resolve_principal(
+    service: &UrlService,
+    headers: &HeaderMap,
+) -> Result<Option<Principal>, AppError> {
+    if let Some(key) = headers.get("X-API-KEY") {
+        let principal = match key.to_str() {
+            Ok(key) if Some(key) == service.get_api_key() => Some(Principal::ApiKey),
+            Ok(key) if key.starts_with(WORKSPACE_KEY_PREFIX) => {
+                service.authenticate_workspace_key(key).await?
+            }
+            _ => None,
+        };
+        if principal.is_none() {
+            tracing::warn!("Invalid API key");
+            return Err(AppError::Unauthorized);
+        }
+        return Ok(principal);
+    }
+
+    let Some(token) = bearer_token(headers) else {
+        return Ok(None);
+    };
+    // Session tokens are hex strings, while JWTs are three dot separated parts
+    let user = if service.jwt_enabled() && token.split('.').count() == 3 {
+        service.authenticate_jwt(token).await?
+    } else {
+        service.authenticate_session(token).await?
+    };
+    let Some(user) = user else {
+        tracing::warn!("Invalid or expired bearer token");
+        return Err(AppError::Unauthorized);
+    };
+
+    let Some(workspace_id) = headers.get(WORKSPACE_HEADER) else {
+        return Ok(Some(Principal::User(user)));
+    };
+    let workspace_id = workspace_id
+        .to_str()
+        .ok()
+        .and_then(|id| Uuid::parse_str(id.trim()).ok())
+        .ok_or_else(|| AppError::BadRequest(format!("{} must be a UUID", WORKSPACE_HEADER)))?;
+    match service.get_workspace_access(&user, workspace_id).await? {
+        Some(access) => Ok(Some(Principal::Member(user, access))),
+        None => Err(AppError::Forbidden(
+            "You are not a member of this workspace".to_string(),
+        )),
+    }
+}
+
+/// The token of an `Authorization: Bearer` header
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+/// What is stored in Redis for an idempotency key
+#[derive(Serialize, Deserialize)]
+struct IdempotencyRecord {
+    /// Hash of the method, path, query and body of the first request using the key
+    fingerprint: String,
+    /// `None` while the first request is still being processed
+    response: Option<StoredResponse>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredResponse {
+    status: u16,
+    content_type: Option<String>,
+    location: Option<String>,
+    body: String,
+}
+
+/// Makes mutating requests carrying an `Idempotency-Key` header safe to retry.
+///
+/// The first request with a key runs normally and its response is stored; retries with the
+/// same key and payload get the stored response replayed, while a different payload is
+/// rejected with 422. Requests without the header, and safe methods, pass through untouched.
+/// Responses that cannot be stored, such as streamed or oversized ones, are passed on and
+/// their key is released. Routes whose responses carry secrets are not wrapped at all.
+pub async fn idempotency(req: Request<Body>, next: Next) -> Response {
+    if matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        return next.run(req).await;
+    }
+    let key = match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+        Some(key) => key.clone(),
+        None => return next.run(req).await,
+    };
+
+    let service = req
+        .extensions()
+        .get::<UrlService>()
+        .expect("UrlService not found in request extensions")
+        .clone();
+    // Each caller has their own keys, so one cannot replay another's responses
+    let scope = req
+        .extensions()
+        .get::<Principal>()
+        .map(Principal::scope)
+        .unwrap_or_else(|| Principal::Anonymous.scope());
+
+    match handle_idempotent(&service, &scope, key, req, next).await {
+        Ok(response) => response,
+        Err(err) => ErrorResponse::new(err, service.get_environment()).into_response(),
+    }
+}
+
+async fn handle_idempotent(
+    service: &UrlService,
+    scope: &str,
+    key: HeaderValue,
+    req: Request<Body>,
+    next: Next,
+) -> Result<Response, AppError> {
+    let key = key
+        .to_str()
+        .ok()
+        .filter(|key| !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LENGTH)
+        .ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "{} must be 1 to {} visible ASCII characters",
+                IDEMPOTENCY_KEY_HEADER, MAX_IDEMPOTENCY_KEY_LENGTH
+            ))
+        })?
+        .to_string();
+
+    // Buffer the body so it can be fingerprinted and still be passed on
+    let (parts, body) = req.into_parts();
+    let body = to_bytes(body, MAX_IDEMPOTENT_BODY_BYTES)
+        .await
+        .map_err(|_| AppError::BadRequest("Request body is too large".to_string()))?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(parts.method.as_str());
+    hasher.update(b" ");
+    hasher.update(
+        parts
+            .uri
+            .path_and_query()
+            .map(|path| path.as_str())
+            .unwrap_or_else(|| parts.uri.path()),
+    );
+    hasher.update(b"\n");
+    hasher.update(&body);
+    let fingerprint = format!("{:x}", hasher.finalize());
+
+    let cache = service.get_cache();
+    let cache_key = idempotency_cache_key(scope, &key);
+
+    // Claim the key; if another request already did, replay or reject based on its record
+    let pending = serde_json::to_string(&IdempotencyRecord {
+        fingerprint: fingerprint.clone(),
+        response: None,
+    })
+    .map_err(|e| AppError::Internal(format!("Failed to serialize idempotency record: {}", e)))?;
+    if !cache
+        .set_nx_with_expiry(&cache_key, &pending, IN_PROGRESS_TTL_SECS)
+        .await?
+    {
+        return match cache.get_json::<IdempotencyRecord>(&cache_key).await? {
+            Some(record) if record.fingerprint != fingerprint => {
+                Err(AppError::UnprocessableEntity(format!(
+                    "{} was already used with a different request",
+                    IDEMPOTENCY_KEY_HEADER
+                )))
+            }
+            Some(IdempotencyRecord {
+                response: Some(stored),
+                ..
+            }) => {
+                tracing::debug!("Replaying stored response for idempotency key {}", key);
+                Ok(replay(stored))
+            }
+            _ => Err(AppError::Conflict(
+                "A request with this idempotency key is still being processed".to_string(),
+            )),
+        };
+    }
+
+    // Keep the key locked for as long as the request runs, however long that is
+    let run = next.run(Request::from_parts(parts, Body::from(body)));
+    tokio::pin!(run);
+    let mut refresh = tokio::time::interval_at(
+        tokio::time::Instant::now() + Duration::from_secs(IN_PROGRESS_REFRESH_SECS),
+        Duration::from_secs(IN_PROGRESS_REFRESH_SECS),
+    );
+    let response = loop {
+        tokio::select! {
+            response = &mut run => break response,
+            _ = refresh.tick() => {
+                if let Err(err) = cache.expire(&cache_key, IN_PROGRESS_TTL_SECS).await {
+                    tracing::warn!("Failed to refresh idempotency key {}: {}", key, err);
+                }
+            }
+        }
+    };
+
+    // Server errors, auth failures and rate limiting say nothing about the request itself,
+    // so the key is released and the client can retry
+    let status = response.status();
+    if status.is_server_error()
+        || matches!(
+            status,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+        )
+    {
+        release_key(service, &cache_key).await;
+        return Ok(response);
+    }
+
+    // The request has been processed by now, so from here on nothing may turn the response
+    // into an error; when it cannot be stored the key is released instead
+    let fits = response
+        .body()
+        .size_hint()
+        .upper()
+        .is_some_and(|size| size <= MAX_IDEMPOTENT_BODY_BYTES as u64);
+    if !fits {
+        tracing::warn!(
+            "Not storing large or streamed response for idempotency key {}",
+            key
+        );
+        release_key(service, &cache_key).await;
+        return Ok(response);
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let body = match to_bytes(body, MAX_IDEMPOTENT_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(err) => {
+            // The body is lost, but the client still learns how the request went
+            tracing::error!(
+                "Failed to read response body for idempotency key {}: {}",
+                key,
+                err
+            );
+            release_key(service, &cache_key).await;
+            parts.headers.remove(header::CONTENT_LENGTH);
+            return Ok(Response::from_parts(parts, Body::empty()));
+        }
+    };
+
+    match String::from_utf8(body.to_vec()) {
+        Ok(text) => {
+            let header = |name: header::HeaderName| {
+                parts
+                    .headers
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string)
+            };
+            let record = IdempotencyRecord {
+                fingerprint,
+                response: Some(StoredResponse {
+                    status: parts.status.as_u16(),
+                    content_type: header(header::CONTENT_TYPE),
+                    location: header(header::LOCATION),
+                    body: text,
+                }),
+            };
+            if let Err(err) = cache
+                .set_json_with_expiry(&cache_key, &record, service.idempotency_ttl_secs())
+                .await
+            {
+                tracing::warn!(
+                    "Failed to store response for idempotency key {}: {}",
+                    key,
+                    err
+                );
+                release_key(service, &cache_key).await;
+            }
+        }
+        Err(_) => {
+            tracing::warn!("Not storing binary response for idempotency key {}", key);
+            release_key(service, &cache_key).await;
+        }
+    }
+
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+/// Drops the record of a key so it can be used again. Failures are only logged, since the
+/// record expires on its own and the response must still reach the client.
+async fn release_key(service: &UrlService, cache_key: &str) {
+    if let Err(err) = service.get_cache().delete(cache_key).await {
+        tracing::warn!("Failed to release idempotency key {}: {}", cache_key, err);
+    }
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let status = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+    let mut response = (status, stored.body).into_response();
+
+    let headers = response.headers_mut();
+    headers.remove(header::CONTENT_TYPE);
+    for (name, value) in [
+        (header::CONTENT_TYPE, stored.content_type),
+        (header::LOCATION, stored.location),
+    ] {
+        if let Some(value) = value.and_then(|value| HeaderValue::from_str(&value).ok()) {
+            headers.insert(name, value);
+        }
+    }
+    headers.insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+
+    response
+}
+
+fn idempotency_cache_key(scope: &str, key: &str) -> String {
+    format!("idempotency:{}:{}", scope, key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use axum::{routing::post, Extension, Json, Router};
+    use serde_json::{json, Value};
+    use sqlx::PgPool;
+
+    use crate::{config::AppConfig, domain::user::User, infrastructure::cache::Cache};
+
+    const USER_HEADER: &str = "X-Test-User";
+
+    /// Stands in for `authenticate`: the server's API key, or the account in `X-Test-User`
+    async fn fake_principal(mut req: Request<Body>, next: Next) -> Response {
+        let principal = match req
+            .headers()
+            .get(USER_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+        {
+            Some(id) => Principal::User(User {
+                id,
+                email: "user@example.com".to_string(),
+                is_admin: false,
+                created_at: time::OffsetDateTime::now_utc(),
+            }),
+            None => Principal::ApiKey,
+        };
+        req.extensions_mut().insert(principal);
+        next.run(req).await
+    }
+
+    /// Serves an endpoint that counts its calls and takes `delay_ms` from the body to run
+    async fn server(pool: &PgPool) -> (String, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let handler = move |Json(body): Json<Value>| async move {
+            let call = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            let delay = body["delay_ms"].as_u64().unwrap_or_default();
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+            (
+                StatusCode::CREATED,
+                [(header::LOCATION, "/created")],
+                Json(json!({ "call": call })),
+            )
+        };
+        let service = UrlService::new(
+            AppConfig::for_tests(),
+            pool.clone(),
+            Cache::connect_for_tests().await,
+            None,
+        );
+        let app = Router::new()
+            .route("/items", post(handler))
+            .route_layer(axum::middleware::from_fn(idempotency))
+            .route_layer(axum::middleware::from_fn(fake_principal))
+            .layer(Extension(service));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/items", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (url, calls)
+    }
+
+    async fn send(url: &str, key: &str, user: Option<Uuid>, body: Value) -> reqwest::Response {
+        let mut request = reqwest::Client::new()
+            .post(url)
+            .header(IDEMPOTENCY_KEY_HEADER, key)
+            .json(&body);
+        if let Some(user) = user {
+            request = request.header(USER_HEADER, user.to_string());
+        }
+        request.send().await.unwrap()
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn replays_the_response_of_an_identical_request(pool: PgPool) {
+        let (url, calls) = server(&pool).await;
+        let key = Uuid::new_v4().to_string();
+
+        let first = send(&url, &key, None, json!({ "name": "a" })).await;
+        assert_eq!(first.status(), StatusCode::CREATED);
+        assert!(first.headers().get(IDEMPOTENT_REPLAYED_HEADER).is_none());
+        let first: Value = first.json().await.unwrap();
+
+        let retry = send(&url, &key, None, json!({ "name": "a" })).await;
+        assert_eq!(retry.status(), StatusCode::CREATED);
+        assert_eq!(retry.headers()[IDEMPOTENT_REPLAYED_HEADER], "true");
+        assert_eq!(retry.headers()[header::LOCATION], "/created");
+        assert_eq!(retry.json::<Value>().await.unwrap(), first);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn rejects_a_different_request_with_the_same_key(pool: PgPool) {
+        let (url, calls) = server(&pool).await;
+        let key = Uuid::new_v4().to_string();
+
+        send(&url, &key, None, json!({ "name": "a" })).await;
+        let other = send(&url, &key, None, json!({ "name": "b" })).await;
+        assert_eq!(other.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        // The query string is part of the request too
+        let other = reqwest::Client::new()
+            .post(format!("{}?dry_run=true", url))
+            .header(IDEMPOTENCY_KEY_HEADER, &key)
+            .json(&json!({ "name": "a" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(other.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn rejects_retries_while_the_first_request_runs(pool: PgPool) {
+        let (url, calls) = server(&pool).await;
+        let key = Uuid::new_v4().to_string();
+        let body = json!({ "name": "a", "delay_ms": 500 });
+
+        let first = tokio::spawn({
+            let (url, key, body) = (url.clone(), key.clone(), body.clone());
+            async move { send(&url, &key, None, body).await }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let retry = send(&url, &key, None, body.clone()).await;
+
+        assert_eq!(retry.status(), StatusCode::CONFLICT);
+        assert_eq!(first.await.unwrap().status(), StatusCode::CREATED);
+        assert_eq!(
+            send(&url, &key, None, body).await.headers()[IDEMPOTENT_REPLAYED_HEADER],
+            "true"
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn keys_belong_to_one_caller(pool: PgPool) {
+        let (url, calls) = server(&pool).await;
+        let key = Uuid::new_v4().to_string();
+        let body = json!({ "name": "a" });
+
+        for user in [None, Some(Uuid::new_v4()), Some(Uuid::new_v4())] {
+            let response = send(&url, &key, user, body.clone()).await;
+            assert!(response.headers().get(IDEMPOTENT_REPLAYED_HEADER).is_none());
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
     }
 }
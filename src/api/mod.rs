@@ -1,4 +1,6 @@
 pub mod api_docs;
+pub mod client_ip;
 pub mod handlers;
 pub mod middleware;
+pub mod pages;
 pub mod routes;
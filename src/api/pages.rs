@@ -0,0 +1,179 @@
+use time::{macros::format_description, OffsetDateTime};
+
+use crate::domain::url::{SocialCard, Url};
+
+/// Escapes text for safe use inside HTML element content and attribute values
+pub fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn format_date(date: OffsetDateTime) -> String {
+    date.format(format_description!(
+        "[year]-[month]-[day] [hour]:[minute] UTC"
+    ))
+    .unwrap_or_else(|_| date.to_string())
+}
+
+/// Wraps page content in the shared HTML layout
+fn layout(title: &str, head: &str, body: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta name="robots" content="noindex, nofollow">
+<title>{title}</title>
+{head}<style>
+body {{ font-family: system-ui, sans-serif; max-width: 40rem; margin: 3rem auto; padding: 0 1rem; color: #222; }}
+.destination {{ word-break: break-all; padding: 0.75rem; background: #f4f4f4; border-radius: 4px; font-family: monospace; }}
+dl {{ display: grid; grid-template-columns: max-content 1fr; gap: 0.25rem 1rem; }}
+dt {{ font-weight: bold; }}
+.button {{ display: inline-block; padding: 0.6rem 1.2rem; background: #2563eb; color: #fff; border-radius: 4px; text-decoration: none; }}
+</style>
+</head>
+<body>
+{body}
+</body>
+</html>
+"#,
+        title = escape_html(title),
+        head = head,
+        body = body,
+    )
+}
+
+/// Renders the page shown in preview mode, before the visitor decides to follow the link
+pub fn preview_page(url: &Url, short_url: &str) -> String {
+    let expires = url
+        .expires_at
+        .map(format_date)
+        .unwrap_or_else(|| "Never".to_string());
+
+    let body = format!(
+        r#"<h1>Link preview</h1>
+<p>The short link <strong>{short_url}</strong> leads to:</p>
+<p class="destination">{destination}</p>
+<dl>
+<dt>Created</dt><dd>{created}</dd>
+<dt>Visits</dt><dd>{visits}</dd>
+<dt>Expires</dt><dd>{expires}</dd>
+</dl>
+<p><a class="button" href="{continue_url}" rel="noreferrer">Continue to destination</a></p>
+"#,
+        short_url = escape_html(short_url),
+        destination = escape_html(&url.original_url),
+        created = format_date(url.created_at),
+        visits = url.visits,
+        expires = expires,
+        continue_url = escape_html(short_url),
+    );
+
+    layout("Link preview", "", &body)
+}
+
+/// Renders the warning page shown before redirecting to a flagged or external link
+pub fn interstitial_page(
+    destination: &str,
+    confirm_url: &str,
+    preview_url: &str,
+    countdown_secs: u32,
+) -> String {
+    let head = format!(
+        r#"<noscript><meta http-equiv="refresh" content="{countdown};url={confirm_url}"></noscript>
+"#,
+        countdown = countdown_secs,
+        confirm_url = escape_html(confirm_url),
+    );
+
+    let body = format!(
+        r#"<h1>Before you continue</h1>
+<p>This link has been flagged for review or leads to a site outside our trusted domains. Make sure you trust the destination before continuing:</p>
+<p class="destination">{destination}</p>
+<p><a id="continue" class="button" href="{confirm_url}" rel="noreferrer" aria-disabled="true" style="pointer-events: none; opacity: 0.5;">Continue in <span id="countdown">{countdown}</span>s</a></p>
+<p><a href="{preview_url}">Show link details</a></p>
+<script>
+(function () {{
+  var remaining = {countdown};
+  var link = document.getElementById("continue");
+  var enable = function () {{
+    link.textContent = "Continue to destination";
+    link.removeAttribute("aria-disabled");
+    link.style.pointerEvents = "";
+    link.style.opacity = "";
+  }};
+  if (remaining <= 0) {{ enable(); return; }}
+  var timer = setInterval(function () {{
+    remaining -= 1;
+    if (remaining <= 0) {{ clearInterval(timer); enable(); }}
+    else {{ document.getElementById("countdown").textContent = remaining; }}
+  }}, 1000);
+}})();
+</script>
+"#,
+        destination = escape_html(destination),
+        confirm_url = escape_html(confirm_url),
+        preview_url = escape_html(preview_url),
+        countdown = countdown_secs,
+    );
+
+    layout("Leaving this site", &head, &body)
+}
+
+/// Renders a page carrying Open Graph overrides for link preview crawlers
+pub fn social_card_page(card: &SocialCard, destination: &str, short_url: &str) -> String {
+    let title = card.title.as_deref().unwrap_or(short_url);
+
+    let mut head = format!(
+        r#"<meta property="og:type" content="website">
+<meta property="og:url" content="{short_url}">
+<meta property="og:title" content="{title}">
+<meta name="twitter:title" content="{title}">
+"#,
+        short_url = escape_html(short_url),
+        title = escape_html(title),
+    );
+    if let Some(ref description) = card.description {
+        head.push_str(&format!(
+            r#"<meta property="og:description" content="{description}">
+<meta name="description" content="{description}">
+<meta name="twitter:description" content="{description}">
+"#,
+            description = escape_html(description),
+        ));
+    }
+    match card.image {
+        Some(ref image) => head.push_str(&format!(
+            r#"<meta property="og:image" content="{image}">
+<meta name="twitter:image" content="{image}">
+<meta name="twitter:card" content="summary_large_image">
+"#,
+            image = escape_html(image),
+        )),
+        None => head.push_str(
+            r#"<meta name="twitter:card" content="summary">
+"#,
+        ),
+    }
+
+    let body = format!(
+        r#"<h1>{title}</h1>
+<p><a href="{destination}">{destination}</a></p>
+"#,
+        title = escape_html(title),
+        destination = escape_html(destination),
+    );
+
+    layout(title, &head, &body)
+}
@@ -1,27 +1,144 @@
 use axum::{
-    extract::State,
+    extract::{DefaultBodyLimit, State},
     http::StatusCode,
     middleware,
     response::IntoResponse,
-    routing::{get, post},
+    routing::{delete, get, patch, post, put},
     Router,
 };
 
 use crate::{
-    api::{handlers, middleware::api_key_auth},
+    api::{
+        handlers,
+        middleware::{authenticate, idempotency, identify, restrict_to_allowed_ips},
+    },
     application::url_service::UrlService,
 };
 
+// Import files are larger than regular JSON bodies
+const IMPORT_MAX_BYTES: usize = 50 * 1024 * 1024;
+const RESTORE_MAX_BYTES: usize = 512 * 1024 * 1024;
+
 pub fn url_routes() -> Router<UrlService> {
     Router::new()
         .route("/api/urls", post(handlers::create_short_url))
+        .route("/api/urls/batch", post(handlers::create_short_urls))
+        .route("/api/urls/{short_code}/qr", get(handlers::get_qr_code))
         .route("/{short_code}", get(handlers::redirect_to_url))
+        .route("/api/users", post(handlers::create_user))
+        .route_layer(middleware::from_fn(idempotency))
+        // Added after the idempotency layer so session tokens are never stored for replay
+        .route("/api/auth/login", post(handlers::login))
+        // Credentials are optional here, but links created with them belong to the account
+        .route_layer(middleware::from_fn(identify))
 }
 
-pub fn admin_routes() -> Router<UrlService> {
+/// Endpoints for any signed-in caller, acting on their own links, account and workspaces
+pub fn account_routes() -> Router<UrlService> {
     Router::new()
+        .route("/api/urls", get(handlers::list_urls))
+        .route("/api/urls/broken", get(handlers::get_broken_links))
+        .route("/api/urls/export", get(handlers::export_urls))
+        .route(
+            "/api/urls/{short_code}",
+            patch(handlers::update_url).delete(handlers::delete_url),
+        )
+        .route("/api/urls/{short_code}/signed", post(handlers::sign_url))
+        .route(
+            "/api/urls/{short_code}/restore",
+            post(handlers::restore_url),
+        )
+        .route(
+            "/api/urls/{short_code}/history",
+            get(handlers::get_url_history),
+        )
+        .route(
+            "/api/urls/{short_code}/rollback",
+            post(handlers::rollback_url),
+        )
+        .route("/api/collections", get(handlers::list_collections))
+        .route("/api/collections/{id}", get(handlers::get_collection))
+        .route("/api/auth/logout", post(handlers::logout))
+        .route("/api/auth/me", get(handlers::get_current_user))
+        .route(
+            "/api/workspaces",
+            get(handlers::list_workspaces).post(handlers::create_workspace),
+        )
+        .route(
+            "/api/workspaces/{id}/members",
+            get(handlers::list_workspace_members),
+        )
+        .route(
+            "/api/workspaces/{id}/members/{user_id}",
+            patch(handlers::update_workspace_member).delete(handlers::remove_workspace_member),
+        )
+        .route(
+            "/api/workspaces/{id}/invitations/{invitation_id}",
+            delete(handlers::revoke_invitation),
+        )
+        .route("/api/invitations/accept", post(handlers::accept_invitation))
+        .route(
+            "/api/workspaces/{id}/api-keys/{key_id}",
+            delete(handlers::revoke_workspace_api_key),
+        )
+        .route("/api/usage", get(handlers::get_usage))
         .route("/api/stats", get(handlers::get_stats))
-        .route_layer(middleware::from_fn(api_key_auth))
+        // Authentication runs first, so unauthenticated requests never claim a key
+        .route_layer(middleware::from_fn(idempotency))
+        // Added after the idempotency layer, as invitation tokens and API keys must never be
+        // stored for replay
+        .route(
+            "/api/workspaces/{id}/invitations",
+            get(handlers::list_invitations).post(handlers::create_invitation),
+        )
+        .route(
+            "/api/workspaces/{id}/api-keys",
+            get(handlers::list_workspace_api_keys).post(handlers::create_workspace_api_key),
+        )
+        .route_layer(middleware::from_fn(authenticate))
+}
+
+/// Operator endpoints covering the whole instance, only reachable from `ADMIN_ALLOWED_IPS`
+pub fn admin_routes() -> Router<UrlService> {
+    Router::new()
+        .route("/api/jobs", get(handlers::list_jobs))
+        .route(
+            "/api/stats/expired-cleanup",
+            get(handlers::get_expired_cleanup_stats),
+        )
+        .route("/api/backup", get(handlers::download_backup))
+        .route("/api/users", get(handlers::list_users))
+        .route("/api/audit", get(handlers::list_audit_events))
+        .route("/api/audit/export", get(handlers::export_audit_events))
+        .route("/api/quotas", get(handlers::list_quotas))
+        .route(
+            "/api/quotas/{subject_type}/{subject_id}",
+            put(handlers::set_quota).delete(handlers::delete_quota),
+        )
+        .route("/api/imports/{id}", get(handlers::get_import))
+        .route("/api/imports/{id}/errors", get(handlers::get_import_errors))
+        .route("/api/collections", post(handlers::create_collection))
+        .route(
+            "/api/collections/{id}",
+            patch(handlers::update_collection).delete(handlers::delete_collection),
+        )
+        // Authentication runs first, so unauthenticated requests never claim a key
+        .route_layer(middleware::from_fn(idempotency))
+        // Added after the idempotency layer, as imports and archives are too large to buffer
+        // for it
+        .route(
+            "/api/imports",
+            get(handlers::list_imports)
+                .post(handlers::import_links)
+                .layer(DefaultBodyLimit::max(IMPORT_MAX_BYTES)),
+        )
+        .route(
+            "/api/restore",
+            post(handlers::restore_backup).layer(DefaultBodyLimit::max(RESTORE_MAX_BYTES)),
+        )
+        .route_layer(middleware::from_fn(authenticate))
+        // Checked before credentials, so clients outside the allowlist learn nothing more
+        .route_layer(middleware::from_fn(restrict_to_allowed_ips))
 }
 
 pub fn health_routes() -> Router<UrlService> {
@@ -0,0 +1,74 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use sha2::{Digest, Sha256};
+
+use crate::error::{AppError, Result};
+
+const MIN_PASSWORD_LENGTH: usize = 8;
+const MAX_PASSWORD_LENGTH: usize = 256;
+const MAX_EMAIL_LENGTH: usize = 254;
+/// Tells workspace API keys apart from the server's API key
+pub const WORKSPACE_KEY_PREFIX: &str = "wsk_";
+// Enough of a workspace API key to recognize it, without making it guessable
+const KEY_PREFIX_LENGTH: usize = 12;
+
+/// Lowercases an email address after checking it looks like one
+pub fn normalize_email(email: &str) -> Result<String> {
+    let email = email.trim().to_lowercase();
+    let valid = match email.split_once('@') {
+        Some((local, domain)) => !local.is_empty() && domain.contains('.') && !domain.contains('@'),
+        None => false,
+    };
+    if !valid || email.len() > MAX_EMAIL_LENGTH || email.contains(char::is_whitespace) {
+        return Err(AppError::BadRequest("Invalid email address".to_string()));
+    }
+
+    Ok(email)
+}
+
+pub fn hash_password(password: &str) -> Result<String> {
+    if !(MIN_PASSWORD_LENGTH..=MAX_PASSWORD_LENGTH).contains(&password.chars().count()) {
+        return Err(AppError::BadRequest(format!(
+            "Passwords must be {} to {} characters",
+            MIN_PASSWORD_LENGTH, MAX_PASSWORD_LENGTH
+        )));
+    }
+
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| AppError::Internal(format!("Failed to hash password: {}", e)))
+}
+
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+/// A random token for a new session or invitation
+pub fn new_secret_token() -> String {
+    rand::random::<[u8; 32]>()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Tokens and keys are stored by hash, so a leaked table does not leak usable credentials
+pub fn hash_secret_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// A new workspace API key and the part of it shown in listings
+pub fn new_workspace_api_key() -> (String, String) {
+    let key = format!("{}{}", WORKSPACE_KEY_PREFIX, new_secret_token());
+    let prefix = key[..KEY_PREFIX_LENGTH].to_string();
+    (key, prefix)
+}
@@ -0,0 +1,113 @@
+use std::future::Future;
+use std::net::IpAddr;
+
+use axum::body::Bytes;
+use futures::{stream, Stream};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use sqlx::PgPool;
+
+use crate::{
+    domain::{
+        audit::{AuditQuery, NewAuditEvent},
+        user::Principal,
+    },
+    error::{AppError, Result},
+    infrastructure::database,
+};
+
+const EXPORT_PAGE_SIZE: i64 = 1000;
+
+tokio::task_local! {
+    static REQUEST_CONTEXT: RequestContext;
+}
+
+/// The HTTP request being handled, as recorded in the audit log
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    pub request_id: String,
+    pub client_ip: Option<IpAddr>,
+}
+
+impl RequestContext {
+    /// Runs `future` with this context as the current one
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        REQUEST_CONTEXT.scope(self, future).await
+    }
+
+    /// The context of the request the current task handles, if any. Command line tasks
+    /// and background jobs have none.
+    pub fn current() -> Option<Self> {
+        REQUEST_CONTEXT.try_with(Clone::clone).ok()
+    }
+}
+
+/// An event for `action` taken by `principal` in the current request
+pub fn event(principal: &Principal, action: &'static str) -> NewAuditEvent {
+    let context = RequestContext::current();
+    NewAuditEvent {
+        actor: principal.changed_by(),
+        actor_id: principal.user_id(),
+        action,
+        target_id: None,
+        workspace_id: principal.workspace().map(|access| access.workspace_id),
+        changes: None,
+        client_ip: context
+            .as_ref()
+            .and_then(|context| context.client_ip)
+            .map(|ip| ip.to_string()),
+        request_id: context.map(|context| context.request_id),
+    }
+}
+
+/// The fields that differ between two versions of a record, as
+/// `{"field": {"before": ..., "after": ...}}`. A missing version, as for created or deleted
+/// records, has no fields, and missing fields count as null.
+pub fn diff<T: Serialize>(before: Option<&T>, after: Option<&T>) -> Option<Value> {
+    let fields = |version: Option<&T>| match version.map(serde_json::to_value) {
+        Some(Ok(Value::Object(fields))) => fields,
+        _ => Map::new(),
+    };
+    let (before, after) = (fields(before), fields(after));
+
+    let mut changes = Map::new();
+    for name in before.keys().chain(after.keys()) {
+        let old = before.get(name).unwrap_or(&Value::Null);
+        let new = after.get(name).unwrap_or(&Value::Null);
+        if old != new && !changes.contains_key(name) {
+            changes.insert(name.clone(), json!({ "before": old, "after": new }));
+        }
+    }
+
+    (!changes.is_empty()).then_some(Value::Object(changes))
+}
+
+/// Streams the events matching the query as JSON lines, newest first, reading the table
+/// page by page
+pub fn export_events(
+    db: PgPool,
+    query: AuditQuery,
+) -> impl Stream<Item = Result<Bytes>> + Send + 'static {
+    stream::try_unfold(
+        (db, query, None::<i64>, false),
+        |(db, query, before, finished)| async move {
+            if finished {
+                return Ok(None);
+            }
+
+            let events = database::list_audit_events(&db, &query, before, EXPORT_PAGE_SIZE).await?;
+            let finished = (events.len() as i64) < EXPORT_PAGE_SIZE;
+            let before = events.last().map(|event| event.id);
+
+            let mut chunk = Vec::new();
+            for event in &events {
+                serde_json::to_writer(&mut chunk, event).map_err(|e| {
+                    AppError::Internal(format!("Failed to serialize audit event: {}", e))
+                })?;
+                chunk.push(b'\n');
+            }
+
+            Ok(Some((Bytes::from(chunk), (db, query, before, finished))))
+        },
+    )
+}
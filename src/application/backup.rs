@@ -0,0 +1,1082 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+
+use axum::body::Bytes;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use futures::{stream, Stream};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    application::url_service::target_hash,
+    domain::{
+        collection::Collection,
+        revision::{RevisionChange, CHANGED_BY_RESTORE},
+        transfer::{
+            AccountBackup, ApiKeyBackup, ConflictPolicy, MembershipBackup, RestoreSummary,
+            WorkspaceBackup,
+        },
+        url::Url,
+        workspace::LinkScope,
+    },
+    error::{AppError, Result},
+    infrastructure::database,
+};
+
+/// Identifies our archives in their header record
+const BACKUP_FORMAT: &str = "tommy-shortener-backup";
+
+/// Version of the archive layout. Restores accept this and every older version.
+/// Version 2 added accounts, workspaces, memberships and workspace API keys.
+pub const BACKUP_VERSION: u32 = 2;
+
+const BACKUP_PAGE_SIZE: i64 = 1000;
+
+/// One line of a backup archive. An archive is a gzip-compressed JSON-lines file that starts
+/// with a header, lists accounts, workspaces, memberships, workspace API keys, collections,
+/// tags and links, and ends with a footer holding the record counts, so truncated archives
+/// are detected on restore.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum BackupRecord {
+    Header {
+        format: String,
+        version: u32,
+        #[serde(with = "time::serde::rfc3339")]
+        created_at: OffsetDateTime,
+    },
+    User(AccountBackup),
+    Workspace(WorkspaceBackup),
+    Membership(MembershipBackup),
+    ApiKey(ApiKeyBackup),
+    Collection(BackupCollection),
+    Tag {
+        name: String,
+    },
+    Link(Box<BackupLink>),
+    Footer {
+        // Version 1 archives have no accounts or workspaces
+        #[serde(default)]
+        users: u64,
+        #[serde(default)]
+        workspaces: u64,
+        #[serde(default)]
+        memberships: u64,
+        #[serde(default)]
+        api_keys: u64,
+        collections: u64,
+        tags: u64,
+        links: u64,
+    },
+}
+
+/// Number of records of each kind written to or read from an archive
+#[derive(Default)]
+struct RecordCounts {
+    users: u64,
+    workspaces: u64,
+    memberships: u64,
+    api_keys: u64,
+    collections: u64,
+    tags: u64,
+    links: u64,
+}
+
+impl RecordCounts {
+    fn footer(&self) -> BackupRecord {
+        BackupRecord::Footer {
+            users: self.users,
+            workspaces: self.workspaces,
+            memberships: self.memberships,
+            api_keys: self.api_keys,
+            collections: self.collections,
+            tags: self.tags,
+            links: self.links,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct BackupCollection {
+    id: Uuid,
+    name: String,
+    description: Option<String>,
+    // Archives written before collections belonged to workspaces leave it out
+    #[serde(default)]
+    workspace_id: Option<Uuid>,
+    #[serde(with = "time::serde::rfc3339")]
+    created_at: OffsetDateTime,
+}
+
+impl From<Collection> for BackupCollection {
+    fn from(collection: Collection) -> Self {
+        Self {
+            id: collection.id,
+            name: collection.name,
+            description: collection.description,
+            workspace_id: collection.workspace_id,
+            created_at: collection.created_at,
+        }
+    }
+}
+
+impl From<BackupCollection> for Collection {
+    fn from(collection: BackupCollection) -> Self {
+        Self {
+            id: collection.id,
+            name: collection.name,
+            description: collection.description,
+            workspace_id: collection.workspace_id,
+            created_at: collection.created_at,
+            url_count: 0,
+        }
+    }
+}
+
+/// Every column of a link, including its statistics and check state
+#[derive(Serialize, Deserialize)]
+struct BackupLink {
+    id: Uuid,
+    short_code: String,
+    original_url: String,
+    visits: i64,
+    #[serde(with = "time::serde::rfc3339")]
+    created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    expires_at: Option<OffsetDateTime>,
+    interstitial: bool,
+    interstitial_views: i64,
+    interstitial_confirmations: i64,
+    #[serde(default)]
+    require_signature: bool,
+    og_title: Option<String>,
+    og_description: Option<String>,
+    og_image: Option<String>,
+    page_title: Option<String>,
+    favicon_url: Option<String>,
+    http_status: Option<i32>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    metadata_fetched_at: Option<OffsetDateTime>,
+    fallback_url: Option<String>,
+    last_check_status: Option<i32>,
+    last_check_latency_ms: Option<i32>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    last_checked_at: Option<OffsetDateTime>,
+    check_failures: i32,
+    collection_id: Option<Uuid>,
+    title: Option<String>,
+    description: Option<String>,
+    metadata: serde_json::Value,
+    tags: Vec<String>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    deleted_at: Option<OffsetDateTime>,
+    #[serde(default)]
+    owner_id: Option<Uuid>,
+    #[serde(default)]
+    workspace_id: Option<Uuid>,
+}
+
+impl From<Url> for BackupLink {
+    fn from(url: Url) -> Self {
+        Self {
+            id: url.id,
+            short_code: url.short_code,
+            original_url: url.original_url,
+            visits: url.visits,
+            created_at: url.created_at,
+            expires_at: url.expires_at,
+            interstitial: url.interstitial,
+            interstitial_views: url.interstitial_views,
+            interstitial_confirmations: url.interstitial_confirmations,
+            require_signature: url.require_signature,
+            og_title: url.og_title,
+            og_description: url.og_description,
+            og_image: url.og_image,
+            page_title: url.page_title,
+            favicon_url: url.favicon_url,
+            http_status: url.http_status,
+            metadata_fetched_at: url.metadata_fetched_at,
+            fallback_url: url.fallback_url,
+            last_check_status: url.last_check_status,
+            last_check_latency_ms: url.last_check_latency_ms,
+            last_checked_at: url.last_checked_at,
+            check_failures: url.check_failures,
+            collection_id: url.collection_id,
+            title: url.title,
+            description: url.description,
+            metadata: url.metadata,
+            tags: url.tags,
+            deleted_at: url.deleted_at,
+            owner_id: url.owner_id,
+            workspace_id: url.workspace_id,
+        }
+    }
+}
+
+impl From<BackupLink> for Url {
+    fn from(link: BackupLink) -> Self {
+        Self {
+            id: link.id,
+            short_code: link.short_code,
+            original_url: link.original_url,
+            visits: link.visits,
+            created_at: link.created_at,
+            expires_at: link.expires_at,
+            interstitial: link.interstitial,
+            interstitial_views: link.interstitial_views,
+            interstitial_confirmations: link.interstitial_confirmations,
+            require_signature: link.require_signature,
+            og_title: link.og_title,
+            og_description: link.og_description,
+            og_image: link.og_image,
+            page_title: link.page_title,
+            favicon_url: link.favicon_url,
+            http_status: link.http_status,
+            metadata_fetched_at: link.metadata_fetched_at,
+            fallback_url: link.fallback_url,
+            last_check_status: link.last_check_status,
+            last_check_latency_ms: link.last_check_latency_ms,
+            last_checked_at: link.last_checked_at,
+            check_failures: link.check_failures,
+            collection_id: link.collection_id,
+            title: link.title,
+            description: link.description,
+            metadata: link.metadata,
+            tags: link.tags,
+            deleted_at: link.deleted_at,
+            owner_id: link.owner_id,
+            workspace_id: link.workspace_id,
+        }
+    }
+}
+
+enum BackupStage {
+    Start,
+    Links,
+    Done,
+}
+
+struct BackupState {
+    /// Read-only snapshot, so links changed during the backup cannot be missed or doubled
+    tx: Option<Transaction<'static, Postgres>>,
+    db: PgPool,
+    encoder: GzEncoder<Vec<u8>>,
+    stage: BackupStage,
+    /// Position of the last written link
+    after: Option<(OffsetDateTime, Uuid)>,
+    counts: RecordCounts,
+}
+
+/// Streams a compressed archive of every account, workspace, collection, tag and link,
+/// including the trash
+pub fn backup(db: PgPool) -> impl Stream<Item = Result<Bytes>> + Send + 'static {
+    let state = BackupState {
+        tx: None,
+        db,
+        encoder: GzEncoder::new(Vec::new(), Compression::default()),
+        stage: BackupStage::Start,
+        after: None,
+        counts: RecordCounts::default(),
+    };
+
+    stream::try_unfold(state, |mut state| async move {
+        // Records are collected first and compressed in one go, as the encoder is slow with
+        // the many small writes the JSON serializer makes
+        let mut lines = Vec::new();
+        match state.stage {
+            BackupStage::Start => {
+                let mut tx = state.db.begin().await?;
+                sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+                    .execute(&mut *tx)
+                    .await?;
+
+                write_record(
+                    &mut lines,
+                    &BackupRecord::Header {
+                        format: BACKUP_FORMAT.to_string(),
+                        version: BACKUP_VERSION,
+                        created_at: OffsetDateTime::now_utc(),
+                    },
+                )?;
+                for account in database::list_backup_accounts(&mut *tx).await? {
+                    write_record(&mut lines, &BackupRecord::User(account))?;
+                    state.counts.users += 1;
+                }
+                for workspace in database::list_backup_workspaces(&mut *tx).await? {
+                    write_record(&mut lines, &BackupRecord::Workspace(workspace))?;
+                    state.counts.workspaces += 1;
+                }
+                for membership in database::list_backup_memberships(&mut *tx).await? {
+                    write_record(&mut lines, &BackupRecord::Membership(membership))?;
+                    state.counts.memberships += 1;
+                }
+                for key in database::list_backup_api_keys(&mut *tx).await? {
+                    write_record(&mut lines, &BackupRecord::ApiKey(key))?;
+                    state.counts.api_keys += 1;
+                }
+                for collection in database::list_collections(&mut *tx, LinkScope::default()).await?
+                {
+                    write_record(&mut lines, &BackupRecord::Collection(collection.into()))?;
+                    state.counts.collections += 1;
+                }
+                for name in database::list_tag_names(&mut *tx).await? {
+                    write_record(&mut lines, &BackupRecord::Tag { name })?;
+                    state.counts.tags += 1;
+                }
+
+                state.tx = Some(tx);
+                state.stage = BackupStage::Links;
+            }
+            BackupStage::Links => {
+                let Some(tx) = state.tx.as_mut() else {
+                    return Err(AppError::Internal("Backup snapshot is missing".to_string()));
+                };
+                let urls = database::list_urls_after(
+                    &mut **tx,
+                    state.after,
+                    BACKUP_PAGE_SIZE,
+                    true,
+                    LinkScope::default(),
+                )
+                .await?;
+                let finished = (urls.len() as i64) < BACKUP_PAGE_SIZE;
+                if let Some(last) = urls.last() {
+                    state.after = Some((last.created_at, last.id));
+                }
+                for url in urls {
+                    write_record(&mut lines, &BackupRecord::Link(Box::new(url.into())))?;
+                    state.counts.links += 1;
+                }
+
+                if finished {
+                    write_record(&mut lines, &state.counts.footer())?;
+                    if let Some(tx) = state.tx.take() {
+                        tx.rollback().await?;
+                    }
+
+                    state.encoder.write_all(&lines).map_err(archive_error)?;
+                    let encoder = std::mem::replace(
+                        &mut state.encoder,
+                        GzEncoder::new(Vec::new(), Compression::default()),
+                    );
+                    let rest = encoder.finish().map_err(archive_error)?;
+                    state.stage = BackupStage::Done;
+                    return Ok(Some((Bytes::from(rest), state)));
+                }
+            }
+            BackupStage::Done => return Ok(None),
+        }
+
+        state.encoder.write_all(&lines).map_err(archive_error)?;
+        let chunk = std::mem::take(state.encoder.get_mut());
+        Ok(Some((Bytes::from(chunk), state)))
+    })
+}
+
+fn write_record(lines: &mut Vec<u8>, record: &BackupRecord) -> Result<()> {
+    serde_json::to_writer(&mut *lines, record)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize backup record: {}", e)))?;
+    lines.push(b'\n');
+    Ok(())
+}
+
+fn archive_error(err: std::io::Error) -> AppError {
+    AppError::Internal(format!("Failed to write backup archive: {}", err))
+}
+
+/// Restores an archive in a single transaction, so a failed restore changes nothing.
+/// Returns what was restored and the short codes whose cached redirects are now stale.
+pub async fn restore(
+    db: &PgPool,
+    archive: &[u8],
+    policy: ConflictPolicy,
+) -> Result<(RestoreSummary, Vec<String>)> {
+    // Uncompressed archives are accepted too, for backups that were unpacked to be inspected
+    let reader: Box<dyn Read + Send + '_> = if archive.starts_with(&[0x1f, 0x8b]) {
+        Box::new(GzDecoder::new(archive))
+    } else {
+        Box::new(archive)
+    };
+    let mut lines = BufReader::new(reader).lines().enumerate();
+
+    let version = match lines.next() {
+        Some((_, line)) => match parse_record(1, &line.map_err(invalid_archive)?)? {
+            BackupRecord::Header {
+                format, version, ..
+            } if format == BACKUP_FORMAT => {
+                if version > BACKUP_VERSION {
+                    return Err(AppError::BadRequest(format!(
+                        "Backup version {} is newer than the supported version {}",
+                        version, BACKUP_VERSION
+                    )));
+                }
+                version
+            }
+            _ => {
+                return Err(AppError::BadRequest(
+                    "Not a backup archive: missing header".to_string(),
+                ))
+            }
+        },
+        None => {
+            return Err(AppError::BadRequest(
+                "The backup archive is empty".to_string(),
+            ))
+        }
+    };
+
+    let mut summary = RestoreSummary {
+        version,
+        ..Default::default()
+    };
+    let mut tx = db.begin().await?;
+    let mut stale_codes = Vec::new();
+    // Restored accounts and collections may be merged into existing ones with another ID
+    let mut user_ids: HashMap<Uuid, Uuid> = HashMap::new();
+    let mut collection_ids: HashMap<Uuid, Uuid> = HashMap::new();
+    let mut tags = Vec::new();
+    let mut counts = RecordCounts::default();
+    let mut complete = false;
+
+    for (index, line) in lines {
+        let line = line.map_err(invalid_archive)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if complete {
+            return Err(AppError::BadRequest(format!(
+                "Unexpected record after the footer on line {}",
+                index + 1
+            )));
+        }
+
+        let line_number = index + 1;
+        match parse_record(line_number, &line)? {
+            BackupRecord::Header { .. } => {
+                return Err(AppError::BadRequest(format!(
+                    "Unexpected header on line {}",
+                    line_number
+                )))
+            }
+            BackupRecord::User(account) => {
+                counts.users += 1;
+                let id = match database::find_existing_account(&mut tx, &account).await? {
+                    None => {
+                        database::restore_account(&mut tx, &account).await?;
+                        summary.users_created += 1;
+                        account.id
+                    }
+                    Some(existing) => match policy {
+                        ConflictPolicy::Fail => {
+                            return Err(AppError::Conflict(format!(
+                                "Account {} already exists",
+                                account.email
+                            )))
+                        }
+                        ConflictPolicy::Skip => {
+                            summary.users_skipped += 1;
+                            existing
+                        }
+                        ConflictPolicy::Overwrite => {
+                            database::overwrite_account(&mut tx, existing, &account).await?;
+                            summary.users_overwritten += 1;
+                            existing
+                        }
+                    },
+                };
+                user_ids.insert(account.id, id);
+            }
+            BackupRecord::Workspace(workspace) => {
+                counts.workspaces += 1;
+                let overwrite = policy == ConflictPolicy::Overwrite;
+                if database::restore_workspace(&mut tx, &workspace, overwrite).await? {
+                    summary.workspaces_created += 1;
+                } else {
+                    match policy {
+                        ConflictPolicy::Fail => {
+                            return Err(AppError::Conflict(format!(
+                                "Workspace {} already exists",
+                                workspace.name
+                            )))
+                        }
+                        ConflictPolicy::Skip => summary.workspaces_skipped += 1,
+                        ConflictPolicy::Overwrite => summary.workspaces_overwritten += 1,
+                    }
+                }
+            }
+            BackupRecord::Membership(membership) => {
+                counts.memberships += 1;
+                let workspace_id =
+                    require_workspace(&mut tx, membership.workspace_id, line_number).await?;
+                let user_id =
+                    require_account(&mut tx, &user_ids, membership.user_id, line_number).await?;
+                let overwrite = policy == ConflictPolicy::Overwrite;
+                if database::restore_membership(
+                    &mut tx,
+                    workspace_id,
+                    user_id,
+                    &membership,
+                    overwrite,
+                )
+                .await?
+                {
+                    summary.memberships_created += 1;
+                } else {
+                    match policy {
+                        ConflictPolicy::Fail => {
+                            return Err(AppError::Conflict(format!(
+                                "Account {} is already a member of workspace {}",
+                                user_id, workspace_id
+                            )))
+                        }
+                        ConflictPolicy::Skip => summary.memberships_skipped += 1,
+                        ConflictPolicy::Overwrite => summary.memberships_overwritten += 1,
+                    }
+                }
+            }
+            BackupRecord::ApiKey(key) => {
+                counts.api_keys += 1;
+                let workspace_id =
+                    require_workspace(&mut tx, key.workspace_id, line_number).await?;
+                // The creator is only informational and is cleared when their account is
+                // deleted, so a missing one is not an error
+                let created_by = match key.created_by {
+                    Some(id) => find_account(&mut tx, &user_ids, id).await?,
+                    None => None,
+                };
+                let id =
+                    match database::find_existing_api_key(&mut tx, key.id, &key.key_hash).await? {
+                        None => {
+                            summary.api_keys_created += 1;
+                            key.id
+                        }
+                        Some(existing) => match policy {
+                            ConflictPolicy::Fail => {
+                                return Err(AppError::Conflict(format!(
+                                    "API key {} already exists",
+                                    key.key_prefix
+                                )))
+                            }
+                            ConflictPolicy::Skip => {
+                                summary.api_keys_skipped += 1;
+                                continue;
+                            }
+                            ConflictPolicy::Overwrite => {
+                                summary.api_keys_overwritten += 1;
+                                existing
+                            }
+                        },
+                    };
+                database::restore_api_key(&mut tx, id, &key, workspace_id, created_by).await?;
+            }
+            BackupRecord::Collection(collection) => {
+                counts.collections += 1;
+                let mut collection = Collection::from(collection);
+                if let Some(workspace_id) = collection.workspace_id {
+                    collection.workspace_id =
+                        Some(require_workspace(&mut tx, workspace_id, line_number).await?);
+                }
+                let id = match database::find_existing_collection(&mut tx, &collection).await? {
+                    None => {
+                        database::restore_collection(&mut tx, &collection).await?;
+                        summary.collections_created += 1;
+                        collection.id
+                    }
+                    Some(existing) => match policy {
+                        ConflictPolicy::Fail => {
+                            return Err(AppError::Conflict(format!(
+                                "Collection {} already exists",
+                                collection.name
+                            )))
+                        }
+                        ConflictPolicy::Skip => {
+                            summary.collections_skipped += 1;
+                            existing
+                        }
+                        ConflictPolicy::Overwrite => {
+                            database::overwrite_collection(&mut tx, existing, &collection).await?;
+                            summary.collections_overwritten += 1;
+                            existing
+                        }
+                    },
+                };
+                collection_ids.insert(collection.id, id);
+            }
+            BackupRecord::Tag { name } => {
+                counts.tags += 1;
+                tags.push(name);
+            }
+            BackupRecord::Link(link) => {
+                counts.links += 1;
+                let mut url = Url::from(*link);
+                url.collection_id = url
+                    .collection_id
+                    .and_then(|id| collection_ids.get(&id).copied());
+                // Links never silently lose their owner; restoring them without it would
+                // hand them to whoever can see unowned links
+                if let Some(owner_id) = url.owner_id {
+                    url.owner_id =
+                        Some(require_account(&mut tx, &user_ids, owner_id, line_number).await?);
+                }
+                if let Some(workspace_id) = url.workspace_id {
+                    url.workspace_id =
+                        Some(require_workspace(&mut tx, workspace_id, line_number).await?);
+                }
+
+                if database::url_conflicts(&mut tx, url.id, &url.short_code).await? {
+                    match policy {
+                        ConflictPolicy::Fail => {
+                            return Err(AppError::Conflict(format!(
+                                "Short code {} already exists",
+                                url.short_code
+                            )))
+                        }
+                        ConflictPolicy::Skip => {
+                            summary.links_skipped += 1;
+                            continue;
+                        }
+                        ConflictPolicy::Overwrite => {
+                            stale_codes.extend(
+                                database::delete_conflicting_urls(&mut tx, url.id, &url.short_code)
+                                    .await?,
+                            );
+                            summary.links_overwritten += 1;
+                        }
+                    }
+                } else {
+                    summary.links_created += 1;
+                }
+
+                database::restore_url(&mut tx, &url, &target_hash(&url.original_url)).await?;
+                database::set_url_tags(&mut tx, url.id, &url.tags).await?;
+                database::record_revision(
+                    &mut tx,
+                    url.id,
+                    RevisionChange::Created,
+                    Some(CHANGED_BY_RESTORE),
+                )
+                .await?;
+            }
+            BackupRecord::Footer {
+                users,
+                workspaces,
+                memberships,
+                api_keys,
+                collections,
+                tags,
+                links,
+            } => {
+                if (users, workspaces, memberships, api_keys)
+                    != (
+                        counts.users,
+                        counts.workspaces,
+                        counts.memberships,
+                        counts.api_keys,
+                    )
+                    || (collections, tags, links) != (counts.collections, counts.tags, counts.links)
+                {
+                    return Err(AppError::BadRequest(
+                        "The backup archive does not match its footer counts".to_string(),
+                    ));
+                }
+                complete = true;
+            }
+        }
+    }
+
+    if !complete {
+        return Err(AppError::BadRequest(
+            "The backup archive is incomplete: missing footer".to_string(),
+        ));
+    }
+
+    // Tags used by links were created with them; this adds the unused ones
+    summary.tags = tags.len() as u64;
+    database::create_tags(&mut tx, &tags).await?;
+    tx.commit().await?;
+
+    Ok((summary, stale_codes))
+}
+
+/// The local ID of an account from the archive or the database, if there is one
+async fn find_account(
+    conn: &mut PgConnection,
+    user_ids: &HashMap<Uuid, Uuid>,
+    id: Uuid,
+) -> Result<Option<Uuid>> {
+    if let Some(id) = user_ids.get(&id) {
+        return Ok(Some(*id));
+    }
+    Ok(database::account_exists(&mut *conn, id)
+        .await?
+        .then_some(id))
+}
+
+/// Like `find_account`, failing the restore when the account is in neither place
+async fn require_account(
+    conn: &mut PgConnection,
+    user_ids: &HashMap<Uuid, Uuid>,
+    id: Uuid,
+    line_number: usize,
+) -> Result<Uuid> {
+    find_account(conn, user_ids, id)
+        .await?
+        .ok_or_else(|| missing_reference("Account", id, line_number))
+}
+
+/// Fails the restore when a workspace is neither in the archive nor in the database
+async fn require_workspace(conn: &mut PgConnection, id: Uuid, line_number: usize) -> Result<Uuid> {
+    if database::workspace_exists(&mut *conn, id).await? {
+        Ok(id)
+    } else {
+        Err(missing_reference("Workspace", id, line_number))
+    }
+}
+
+fn missing_reference(kind: &str, id: Uuid, line_number: usize) -> AppError {
+    AppError::BadRequest(format!(
+        "{} {} referenced on line {} is neither in the backup archive nor in the database",
+        kind, id, line_number
+    ))
+}
+
+fn parse_record(line_number: usize, line: &str) -> Result<BackupRecord> {
+    serde_json::from_str(line).map_err(|e| {
+        AppError::BadRequest(format!(
+            "Invalid backup record on line {}: {}",
+            line_number, e
+        ))
+    })
+}
+
+fn invalid_archive(err: std::io::Error) -> AppError {
+    AppError::BadRequest(format!("Invalid backup archive: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use futures::TryStreamExt;
+
+    async fn setup(pool: &PgPool) {
+        crate::infrastructure::migrations::run_migrations_if_needed(pool)
+            .await
+            .unwrap();
+
+        let user_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO users (email, password_hash) VALUES ('owner@example.com', 'hash') RETURNING id",
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let workspace_id: Uuid =
+            sqlx::query_scalar("INSERT INTO workspaces (name) VALUES ('team') RETURNING id")
+                .fetch_one(pool)
+                .await
+                .unwrap();
+        sqlx::query(
+            "INSERT INTO workspace_members (workspace_id, user_id, role) VALUES ($1, $2, 'owner')",
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO workspace_api_keys (workspace_id, name, role, key_hash, key_prefix, created_by) VALUES ($1, 'ci', 'editor', 'keyhash', 'sk_123', $2)",
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .unwrap();
+
+        let collection_id: Uuid = sqlx::query_scalar(
+            "INSERT INTO collections (name, description) VALUES ('docs', 'Documentation') RETURNING id",
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        for (short_code, original_url, tags) in [
+            ("first", "https://example.com/1", vec!["a".to_string()]),
+            (
+                "second",
+                "https://example.com/2",
+                vec!["a".to_string(), "b".to_string()],
+            ),
+            ("third", "https://example.com/3", vec![]),
+        ] {
+            let id: Uuid = sqlx::query_scalar(
+                "INSERT INTO urls (original_url, short_code, visits, collection_id) VALUES ($1, $2, 7, $3) RETURNING id",
+            )
+            .bind(original_url)
+            .bind(short_code)
+            .bind(collection_id)
+            .fetch_one(&mut *conn)
+            .await
+            .unwrap();
+            database::set_url_tags(&mut conn, id, &tags).await.unwrap();
+        }
+        database::create_tags(&mut conn, &["unused".to_string()])
+            .await
+            .unwrap();
+        sqlx::query("UPDATE urls SET owner_id = $1, workspace_id = $2 WHERE short_code = 'first'")
+            .bind(user_id)
+            .bind(workspace_id)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+    }
+
+    async fn archive(pool: &PgPool) -> Vec<u8> {
+        let chunks: Vec<Bytes> = backup(pool.clone()).try_collect().await.unwrap();
+        chunks.concat()
+    }
+
+    /// The records of an archive as uncompressed JSON lines
+    fn lines(archive: &[u8]) -> Vec<String> {
+        BufReader::new(GzDecoder::new(archive))
+            .lines()
+            .collect::<std::io::Result<_>>()
+            .unwrap()
+    }
+
+    type LinkSnapshot = (
+        String,
+        String,
+        i64,
+        Vec<String>,
+        Option<String>,
+        Option<String>,
+        Option<String>,
+    );
+
+    /// Short code, destination, visits, tags, collection, owner and workspace of every link
+    async fn snapshot(pool: &PgPool) -> Vec<LinkSnapshot> {
+        sqlx::query_as(
+            r#"
+            SELECT u.short_code, u.original_url, u.visits,
+                ARRAY(
+                    SELECT t.name FROM url_tags ut JOIN tags t ON t.id = ut.tag_id
+                    WHERE ut.url_id = u.id ORDER BY t.name
+                ),
+                c.name, o.email, w.name
+            FROM urls u
+            LEFT JOIN collections c ON c.id = u.collection_id
+            LEFT JOIN users o ON o.id = u.owner_id
+            LEFT JOIN workspaces w ON w.id = u.workspace_id
+            ORDER BY u.short_code
+            "#,
+        )
+        .fetch_all(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn clear(pool: &PgPool) {
+        for table in [
+            "url_revisions",
+            "url_tags",
+            "urls",
+            "tags",
+            "collections",
+            "workspace_api_keys",
+            "workspace_invitations",
+            "workspace_members",
+            "workspaces",
+            "user_sessions",
+            "users",
+        ] {
+            sqlx::query(&format!("DELETE FROM {}", table))
+                .execute(pool)
+                .await
+                .unwrap();
+        }
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn restores_an_archive_into_an_empty_database(pool: PgPool) {
+        setup(&pool).await;
+        let before = snapshot(&pool).await;
+        let archive = archive(&pool).await;
+        clear(&pool).await;
+
+        let (summary, stale) = restore(&pool, &archive, ConflictPolicy::Fail)
+            .await
+            .unwrap();
+
+        assert_eq!(snapshot(&pool).await, before);
+        assert_eq!(summary.version, BACKUP_VERSION);
+        assert_eq!(summary.users_created, 1);
+        assert_eq!(summary.workspaces_created, 1);
+        assert_eq!(summary.memberships_created, 1);
+        assert_eq!(summary.api_keys_created, 1);
+        assert_eq!(summary.collections_created, 1);
+        assert_eq!(summary.links_created, 3);
+        assert_eq!(summary.tags, 3);
+        assert!(stale.is_empty());
+        let unused: bool =
+            sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM tags WHERE name = 'unused')")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert!(unused);
+        let key: (String, String, Option<String>) = sqlx::query_as(
+            "SELECT k.key_hash, k.role, u.email FROM workspace_api_keys k LEFT JOIN users u ON u.id = k.created_by",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            key,
+            (
+                "keyhash".to_string(),
+                "editor".to_string(),
+                Some("owner@example.com".to_string())
+            )
+        );
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn fails_instead_of_dropping_missing_owners(pool: PgPool) {
+        setup(&pool).await;
+        let archive = archive(&pool).await;
+        clear(&pool).await;
+
+        // A link whose owner is in neither the archive nor the database
+        let records: Vec<String> = lines(&archive)
+            .into_iter()
+            .filter(|line| {
+                !line.contains(r#""type":"user""#) && !line.contains(r#""type":"membership""#)
+            })
+            .collect();
+        let result = restore(&pool, records.join("\n").as_bytes(), ConflictPolicy::Fail).await;
+
+        assert_matches!(result, Err(AppError::BadRequest(message)) if message.contains("neither in the backup archive"));
+        assert!(snapshot(&pool).await.is_empty());
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn fail_policy_aborts_without_changes(pool: PgPool) {
+        setup(&pool).await;
+        let archive = archive(&pool).await;
+        sqlx::query("DELETE FROM urls WHERE short_code = 'third'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let before = snapshot(&pool).await;
+
+        let result = restore(&pool, &archive, ConflictPolicy::Fail).await;
+
+        assert_matches!(result, Err(AppError::Conflict(_)));
+        assert_eq!(snapshot(&pool).await, before);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn skip_policy_keeps_existing_links(pool: PgPool) {
+        setup(&pool).await;
+        let archive = archive(&pool).await;
+        sqlx::query("UPDATE urls SET original_url = 'https://example.com/changed' WHERE short_code = 'first'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM urls WHERE short_code = 'third'")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let (summary, stale) = restore(&pool, &archive, ConflictPolicy::Skip)
+            .await
+            .unwrap();
+
+        let links = snapshot(&pool).await;
+        assert_eq!(links.len(), 3);
+        assert_eq!(links[0].1, "https://example.com/changed");
+        assert_eq!(links[2].0, "third");
+        assert_eq!(summary.users_skipped, 1);
+        assert_eq!(summary.api_keys_skipped, 1);
+        assert_eq!(summary.collections_skipped, 1);
+        assert_eq!(summary.links_skipped, 2);
+        assert_eq!(summary.links_created, 1);
+        assert!(stale.is_empty());
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn overwrite_policy_replaces_existing_links(pool: PgPool) {
+        setup(&pool).await;
+        let before = snapshot(&pool).await;
+        let archive = archive(&pool).await;
+        sqlx::query("UPDATE urls SET original_url = 'https://example.com/changed', visits = 0 WHERE short_code = 'first'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM urls WHERE short_code = 'third'")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let (summary, stale) = restore(&pool, &archive, ConflictPolicy::Overwrite)
+            .await
+            .unwrap();
+
+        assert_eq!(snapshot(&pool).await, before);
+        assert_eq!(summary.users_overwritten, 1);
+        assert_eq!(summary.workspaces_overwritten, 1);
+        assert_eq!(summary.memberships_overwritten, 1);
+        assert_eq!(summary.api_keys_overwritten, 1);
+        assert_eq!(summary.collections_overwritten, 1);
+        assert_eq!(summary.links_overwritten, 2);
+        assert_eq!(summary.links_created, 1);
+        let mut stale = stale;
+        stale.sort();
+        assert_eq!(stale, ["first", "second"]);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn rejects_truncated_archives(pool: PgPool) {
+        setup(&pool).await;
+        let archive = archive(&pool).await;
+        clear(&pool).await;
+
+        // Cut off inside the compressed stream
+        let result = restore(&pool, &archive[..archive.len() / 2], ConflictPolicy::Fail).await;
+        assert_matches!(result, Err(AppError::BadRequest(_)));
+
+        // Cleanly cut off before the footer
+        let mut records = lines(&archive);
+        records.pop();
+        let result = restore(&pool, records.join("\n").as_bytes(), ConflictPolicy::Fail).await;
+        assert_matches!(result, Err(AppError::BadRequest(message)) if message.contains("missing footer"));
+
+        assert!(snapshot(&pool).await.is_empty());
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn rejects_archives_that_do_not_match_their_footer(pool: PgPool) {
+        setup(&pool).await;
+        let archive = archive(&pool).await;
+        clear(&pool).await;
+
+        // A link record lost from the middle of the archive
+        let mut records = lines(&archive);
+        let link = records
+            .iter()
+            .position(|line| line.contains(r#""type":"link""#))
+            .unwrap();
+        records.remove(link);
+        let result = restore(&pool, records.join("\n").as_bytes(), ConflictPolicy::Fail).await;
+        assert_matches!(result, Err(AppError::BadRequest(message)) if message.contains("footer counts"));
+
+        // Records after the footer
+        let mut records = lines(&archive);
+        records.push(records[1].clone());
+        let result = restore(&pool, records.join("\n").as_bytes(), ConflictPolicy::Fail).await;
+        assert_matches!(result, Err(AppError::BadRequest(message)) if message.contains("after the footer"));
+
+        assert!(snapshot(&pool).await.is_empty());
+    }
+}
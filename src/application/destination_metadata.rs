@@ -0,0 +1,281 @@
+use std::sync::Arc;
+
+use reqwest::Method;
+use sqlx::PgPool;
+use tokio::sync::{mpsc, Semaphore};
+use url::Url;
+use uuid::Uuid;
+
+use crate::{
+    domain::url::DestinationMetadata,
+    infrastructure::{
+        database,
+        http_client::{FetchResponse, SafeHttpClient},
+    },
+};
+
+const MAX_TITLE_CHARS: usize = 300;
+
+/// A link whose destination metadata should be fetched
+#[derive(Debug)]
+pub struct MetadataJob {
+    pub url_id: Uuid,
+    pub original_url: String,
+}
+
+/// Extracts the title, favicon and status of a fetched destination
+pub fn metadata_from_response(response: &FetchResponse) -> DestinationMetadata {
+    let is_html = response
+        .content_type
+        .as_deref()
+        .map(|content_type| content_type.to_lowercase().contains("html"))
+        .unwrap_or(true);
+
+    let html = if is_html {
+        String::from_utf8_lossy(&response.body).into_owned()
+    } else {
+        String::new()
+    };
+
+    DestinationMetadata {
+        page_title: extract_title(&html),
+        favicon_url: extract_favicon(&html, &response.url),
+        http_status: Some(response.status.as_u16() as i32),
+    }
+}
+
+/// Consumes metadata jobs until every sender is dropped, fetching at most
+/// `concurrency` destinations at a time.
+pub async fn run_metadata_worker(
+    client: SafeHttpClient,
+    db: PgPool,
+    mut jobs: mpsc::Receiver<MetadataJob>,
+    concurrency: usize,
+) {
+    let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+
+    while let Some(job) = jobs.recv().await {
+        let permit = match permits.clone().acquire_owned().await {
+            Ok(permit) => permit,
+            Err(_) => break,
+        };
+        let client = client.clone();
+        let db = db.clone();
+
+        tokio::spawn(async move {
+            fetch_and_store(&client, &db, &job).await;
+            drop(permit);
+        });
+    }
+
+    tracing::info!("Metadata worker stopped");
+}
+
+async fn fetch_and_store(client: &SafeHttpClient, db: &PgPool, job: &MetadataJob) {
+    let metadata = match client.fetch(Method::GET, &job.original_url).await {
+        Ok(response) => metadata_from_response(&response),
+        Err(err) => {
+            tracing::debug!("Failed to fetch metadata for {}: {}", job.original_url, err);
+            DestinationMetadata::default()
+        }
+    };
+
+    if let Err(err) = database::store_destination_metadata(db, job.url_id, &metadata).await {
+        tracing::warn!("Failed to store metadata for link {}: {}", job.url_id, err);
+    }
+}
+
+fn extract_title(html: &str) -> Option<String> {
+    // ASCII lowercasing keeps byte offsets identical to the original
+    let lower = html.to_ascii_lowercase();
+    let open = lower.find("<title")?;
+    let start = open + lower[open..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+
+    let title = decode_entities(&html[start..end])
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if title.is_empty() {
+        None
+    } else {
+        Some(title.chars().take(MAX_TITLE_CHARS).collect())
+    }
+}
+
+/// Finds the first `<link rel="...icon...">`, falling back to `/favicon.ico`
+fn extract_favicon(html: &str, base: &Url) -> Option<String> {
+    let lower = html.to_ascii_lowercase();
+    let mut offset = 0;
+
+    while let Some(position) = lower[offset..].find("<link") {
+        let start = offset + position;
+        let end = match lower[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let tag = &html[start..end];
+
+        let is_icon = attribute(tag, "rel")
+            .map(|rel| {
+                rel.split_whitespace()
+                    .any(|rel| rel.eq_ignore_ascii_case("icon"))
+            })
+            .unwrap_or(false);
+        if is_icon {
+            if let Some(href) = attribute(tag, "href") {
+                if let Ok(url) = base.join(&decode_entities(&href)) {
+                    return Some(url.to_string());
+                }
+            }
+        }
+
+        offset = end;
+    }
+
+    base.join("/favicon.ico").ok().map(|url| url.to_string())
+}
+
+/// Reads an attribute value from the inside of a tag
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let mut offset = 0;
+
+    while let Some(position) = lower[offset..].find(name) {
+        let start = offset + position;
+        offset = start + name.len();
+
+        // Require a word boundary so `rel` does not match inside `data-rel`
+        let preceded_by_space = lower[..start]
+            .chars()
+            .last()
+            .map(char::is_whitespace)
+            .unwrap_or(false);
+        let rest = lower[offset..].trim_start();
+        if !preceded_by_space || !rest.starts_with('=') {
+            continue;
+        }
+
+        let value_start = tag.len() - rest.len() + 1;
+        let value = tag[value_start..].trim_start();
+        return match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..].split(quote).next().map(str::to_string),
+            Some(_) => value.split_whitespace().next().map(str::to_string),
+            None => None,
+        };
+    }
+
+    None
+}
+
+fn decode_entities(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use wiremock::matchers::path;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn fetch_metadata(server: &MockServer, path: &str) -> DestinationMetadata {
+        let client = SafeHttpClient::new(Duration::from_secs(5), 64 * 1024, true);
+        let response = client
+            .fetch(Method::GET, &format!("{}{}", server.uri(), path))
+            .await
+            .unwrap();
+        metadata_from_response(&response)
+    }
+
+    fn html(body: &str) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_raw(body.to_string(), "text/html; charset=utf-8")
+    }
+
+    #[tokio::test]
+    async fn extracts_title_favicon_and_status() {
+        let server = MockServer::start().await;
+        Mock::given(path("/page"))
+            .respond_with(html(concat!(
+                "<html><head><TITLE>\n  Fish &amp; Chips\n</TITLE>",
+                r#"<link data-rel="icon" href="/wrong.png">"#,
+                r#"<link rel="shortcut icon" href='/static/icon.png'>"#,
+                "</head></html>"
+            )))
+            .mount(&server)
+            .await;
+
+        let metadata = fetch_metadata(&server, "/page").await;
+
+        assert_eq!(metadata.page_title.as_deref(), Some("Fish & Chips"));
+        assert_eq!(
+            metadata.favicon_url,
+            Some(format!("{}/static/icon.png", server.uri()))
+        );
+        assert_eq!(metadata.http_status, Some(200));
+    }
+
+    #[tokio::test]
+    async fn reports_the_status_of_the_final_redirect_target() {
+        let server = MockServer::start().await;
+        Mock::given(path("/moved"))
+            .respond_with(ResponseTemplate::new(301).insert_header("Location", "/gone"))
+            .mount(&server)
+            .await;
+        Mock::given(path("/gone"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+
+        let metadata = fetch_metadata(&server, "/moved").await;
+
+        assert_eq!(metadata.http_status, Some(404));
+        assert_eq!(metadata.page_title, None);
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_the_default_favicon_for_non_html() {
+        let server = MockServer::start().await;
+        Mock::given(path("/file.pdf"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw("<title>Not a page</title>", "application/pdf"),
+            )
+            .mount(&server)
+            .await;
+
+        let metadata = fetch_metadata(&server, "/file.pdf").await;
+
+        assert_eq!(metadata.page_title, None);
+        assert_eq!(
+            metadata.favicon_url,
+            Some(format!("{}/favicon.ico", server.uri()))
+        );
+    }
+
+    #[tokio::test]
+    async fn ignores_a_title_past_the_body_limit() {
+        let server = MockServer::start().await;
+        let padding = "x".repeat(64 * 1024);
+        Mock::given(path("/long"))
+            .respond_with(html(&format!(
+                "<html>{}<title>Late</title></html>",
+                padding
+            )))
+            .mount(&server)
+            .await;
+
+        let metadata = fetch_metadata(&server, "/long").await;
+
+        assert_eq!(metadata.page_title, None);
+        assert_eq!(metadata.http_status, Some(200));
+    }
+}
@@ -0,0 +1,146 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::future::BoxFuture;
+use sqlx::PgPool;
+
+use crate::{
+    application::scheduler::Job,
+    config::AppConfig,
+    domain::maintenance::ExpiredCleanupStats,
+    error::Result,
+    infrastructure::{cache::Cache, database},
+};
+
+// Links are removed in batches so a large backlog does not hold long locks
+const CLEANUP_BATCH_SIZE: i64 = 1000;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Running totals of the expired-link cleanup, shared with the API
+#[derive(Clone)]
+pub struct CleanupMetrics(Arc<Mutex<ExpiredCleanupStats>>);
+
+impl CleanupMetrics {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self(Arc::new(Mutex::new(ExpiredCleanupStats {
+            enabled: config.expired_cleanup_enabled,
+            grace_days: config.expired_grace_days,
+            archive: config.expired_archive,
+            code_reuse_days: config.expired_code_reuse_days,
+            ..Default::default()
+        })))
+    }
+
+    pub fn snapshot(&self) -> ExpiredCleanupStats {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn record(&self, outcome: &Result<CleanupOutcome>, archive: bool, elapsed: Duration) {
+        let mut stats = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        stats.runs += 1;
+        stats.last_run_at = Some(time::OffsetDateTime::now_utc());
+        stats.last_run_duration_ms = Some(elapsed.as_millis() as u64);
+        match outcome {
+            Ok(outcome) => {
+                if archive {
+                    stats.links_archived += outcome.removed as u64;
+                } else {
+                    stats.links_deleted += outcome.removed as u64;
+                }
+                stats.codes_released += outcome.released;
+                stats.last_error = None;
+            }
+            Err(err) => stats.last_error = Some(err.to_string()),
+        }
+    }
+}
+
+/// What a single cleanup run did
+#[derive(Debug, Default)]
+pub struct CleanupOutcome {
+    /// Links archived or deleted
+    pub removed: usize,
+    /// Short codes freed for reuse
+    pub released: u64,
+}
+
+/// Removes links that expired longer than the grace period ago, and frees their short codes
+/// once their quarantine ends
+pub struct ExpiredLinkCleaner {
+    db: PgPool,
+    cache: Cache,
+    grace: Duration,
+    archive: bool,
+    code_reuse_after: Option<Duration>,
+    metrics: CleanupMetrics,
+}
+
+impl ExpiredLinkCleaner {
+    pub fn new(db: PgPool, cache: Cache, config: &AppConfig, metrics: CleanupMetrics) -> Self {
+        Self {
+            db,
+            cache,
+            grace: Duration::from_secs(config.expired_grace_days * SECS_PER_DAY),
+            archive: config.expired_archive,
+            code_reuse_after: config
+                .expired_code_reuse_days
+                .map(|days| Duration::from_secs(days * SECS_PER_DAY)),
+            metrics,
+        }
+    }
+
+    /// Removes every link past the grace period and releases quarantined short codes
+    pub async fn cleanup(&self) -> Result<CleanupOutcome> {
+        let mut outcome = CleanupOutcome {
+            released: database::release_retired_short_codes(&self.db).await?,
+            ..Default::default()
+        };
+
+        loop {
+            let short_codes = database::remove_expired_urls(
+                &self.db,
+                self.grace.as_secs() as i64,
+                CLEANUP_BATCH_SIZE,
+                self.archive,
+                self.code_reuse_after.map(|after| after.as_secs() as i64),
+            )
+            .await?;
+            outcome.removed += short_codes.len();
+
+            if !short_codes.is_empty() {
+                tracing::debug!("Removed expired links: {}", short_codes.join(", "));
+                for short_code in &short_codes {
+                    self.cache.delete(&Cache::url_cache_key(short_code)).await?;
+                }
+            }
+
+            if (short_codes.len() as i64) < CLEANUP_BATCH_SIZE {
+                return Ok(outcome);
+            }
+        }
+    }
+}
+
+impl Job for ExpiredLinkCleaner {
+    fn name(&self) -> &'static str {
+        "expired-cleanup"
+    }
+
+    fn run(&self) -> BoxFuture<'_, Result<String>> {
+        Box::pin(async move {
+            let started = Instant::now();
+            let outcome = self.cleanup().await;
+            self.metrics
+                .record(&outcome, self.archive, started.elapsed());
+
+            let outcome = outcome?;
+            Ok(format!(
+                "{} {} link(s) and released {} short code(s)",
+                if self.archive { "Archived" } else { "Deleted" },
+                outcome.removed,
+                outcome.released
+            ))
+        })
+    }
+}
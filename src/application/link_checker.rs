@@ -0,0 +1,388 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use reqwest::{Method, StatusCode};
+use sqlx::PgPool;
+use tokio::{sync::Semaphore, task::JoinSet};
+
+use crate::{
+    application::scheduler::Job,
+    config::AppConfig,
+    domain::{
+        revision::{RevisionChange, CHANGED_BY_LINK_CHECKER},
+        url::LinkToCheck,
+    },
+    error::Result,
+    infrastructure::{
+        cache::Cache,
+        database,
+        http_client::{FetchError, SafeHttpClient},
+    },
+};
+
+/// Settings for the periodic destination check
+#[derive(Debug, Clone)]
+pub struct LinkCheckSettings {
+    pub batch_size: i64,
+    /// Links checked more recently than this are skipped
+    pub recheck_after: Duration,
+    /// Number of hosts checked in parallel
+    pub concurrency: usize,
+    /// Pause between two requests to the same host
+    pub politeness_delay: Duration,
+    /// Consecutive failures before a link counts as broken for the fallback switch
+    pub failure_threshold: i32,
+    pub auto_fallback: bool,
+}
+
+impl LinkCheckSettings {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            batch_size: config.link_check_batch_size,
+            recheck_after: Duration::from_secs(config.link_check_recheck_secs),
+            concurrency: config.link_check_concurrency,
+            politeness_delay: Duration::from_millis(config.link_check_delay_ms),
+            failure_threshold: config.link_check_failure_threshold,
+            auto_fallback: config.link_check_auto_fallback,
+        }
+    }
+}
+
+/// Re-checks link destinations and records their status and latency
+#[derive(Clone)]
+pub struct LinkChecker {
+    client: SafeHttpClient,
+    db: PgPool,
+    cache: Cache,
+    settings: LinkCheckSettings,
+}
+
+impl LinkChecker {
+    pub fn new(
+        client: SafeHttpClient,
+        db: PgPool,
+        cache: Cache,
+        settings: LinkCheckSettings,
+    ) -> Self {
+        Self {
+            client,
+            db,
+            cache,
+            settings,
+        }
+    }
+
+    /// Checks one batch of links that are due, returning how many were checked
+    pub async fn check_due_links(&self) -> Result<usize> {
+        let links = database::get_links_due_for_check(
+            &self.db,
+            self.settings.recheck_after.as_secs() as i64,
+            self.settings.batch_size,
+        )
+        .await?;
+        let checked = links.len();
+
+        // Links to the same host are checked one after another with a pause in between,
+        // while different hosts are checked in parallel
+        let mut by_host: HashMap<String, Vec<LinkToCheck>> = HashMap::new();
+        for link in links {
+            let host = ::url::Url::parse(&link.original_url)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_lowercase))
+                .unwrap_or_default();
+            by_host.entry(host).or_default().push(link);
+        }
+
+        let permits = Arc::new(Semaphore::new(self.settings.concurrency.max(1)));
+        let mut tasks = JoinSet::new();
+        for (_, links) in by_host {
+            let checker = self.clone();
+            let permits = permits.clone();
+            tasks.spawn(async move {
+                let _permit = permits.acquire_owned().await;
+                for (index, link) in links.iter().enumerate() {
+                    if index > 0 {
+                        tokio::time::sleep(checker.settings.politeness_delay).await;
+                    }
+                    if let Err(err) = checker.check_link(link).await {
+                        tracing::warn!("Failed to check link {}: {}", link.short_code, err);
+                    }
+                }
+            });
+        }
+        while tasks.join_next().await.is_some() {}
+
+        Ok(checked)
+    }
+
+    async fn check_link(&self, link: &LinkToCheck) -> Result<()> {
+        let (status, latency) = self.probe(&link.original_url).await;
+        let broken = is_broken(status);
+
+        let failures = database::record_link_check(
+            &self.db,
+            link.id,
+            status.map(|status| status.as_u16() as i32),
+            latency.map(|latency| latency.as_millis().min(i32::MAX as u128) as i32),
+            broken,
+        )
+        .await?;
+
+        if broken {
+            tracing::debug!(
+                "Destination of {} looks broken ({:?}, {} consecutive failures)",
+                link.short_code,
+                status,
+                failures
+            );
+        }
+
+        if broken && self.settings.auto_fallback && failures >= self.settings.failure_threshold {
+            let mut tx = self.db.begin().await?;
+            let switched = database::switch_to_fallback(&mut tx, link.id).await?;
+            if switched.is_some() {
+                database::record_revision(
+                    &mut tx,
+                    link.id,
+                    RevisionChange::FallbackSwitch,
+                    Some(CHANGED_BY_LINK_CHECKER),
+                )
+                .await?;
+            }
+            tx.commit().await?;
+
+            if let Some(short_code) = switched {
+                tracing::warn!(
+                    "Switched broken link {} from {} to its fallback",
+                    short_code,
+                    link.original_url
+                );
+                self.cache
+                    .delete(&Cache::url_cache_key(&short_code))
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sends a HEAD request, retrying with GET for servers that do not support HEAD
+    async fn probe(&self, url: &str) -> (Option<StatusCode>, Option<Duration>) {
+        let mut result = self.client.fetch(Method::HEAD, url).await;
+
+        let head_unsupported = match result {
+            Ok(ref response) => matches!(
+                response.status,
+                StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+            ),
+            // Blocked or invalid destinations fail the same way with GET
+            Err(FetchError::Request(_)) => true,
+            Err(_) => false,
+        };
+        if head_unsupported {
+            result = self.client.fetch(Method::GET, url).await;
+        }
+
+        match result {
+            Ok(response) => (Some(response.status), Some(response.elapsed)),
+            Err(err) => {
+                tracing::debug!("Destination check for {} failed: {}", url, err);
+                (None, None)
+            }
+        }
+    }
+}
+
+impl Job for LinkChecker {
+    fn name(&self) -> &'static str {
+        "link-check"
+    }
+
+    fn run(&self) -> BoxFuture<'_, Result<String>> {
+        Box::pin(async move {
+            let checked = self.check_due_links().await?;
+            Ok(format!("Checked {} link(s)", checked))
+        })
+    }
+}
+
+/// Failed requests and error statuses count as broken; rate limiting is not the link's fault
+fn is_broken(status: Option<StatusCode>) -> bool {
+    match status {
+        None => true,
+        Some(StatusCode::TOO_MANY_REQUESTS) => false,
+        Some(status) => status.is_client_error() || status.is_server_error(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn checker(pool: &PgPool, failure_threshold: i32, auto_fallback: bool) -> LinkChecker {
+        crate::infrastructure::migrations::run_migrations_if_needed(pool)
+            .await
+            .unwrap();
+        LinkChecker::new(
+            SafeHttpClient::new(Duration::from_secs(5), 1024, true),
+            pool.clone(),
+            Cache::connect_for_tests().await,
+            LinkCheckSettings {
+                batch_size: 10,
+                recheck_after: Duration::ZERO,
+                concurrency: 1,
+                politeness_delay: Duration::ZERO,
+                failure_threshold,
+                auto_fallback,
+            },
+        )
+    }
+
+    async fn insert_link(pool: &PgPool, original_url: &str, fallback_url: &str) -> LinkToCheck {
+        let short_code = format!("chk{}", &Uuid::new_v4().simple().to_string()[..8]);
+        let id: Uuid = sqlx::query_scalar(
+            "INSERT INTO urls (original_url, short_code, fallback_url) VALUES ($1, $2, $3) RETURNING id",
+        )
+        .bind(original_url)
+        .bind(&short_code)
+        .bind(fallback_url)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        LinkToCheck {
+            id,
+            short_code,
+            original_url: original_url.to_string(),
+        }
+    }
+
+    /// Destination, fallback and consecutive failures of a link
+    async fn link_state(pool: &PgPool, id: Uuid) -> (String, Option<String>, i32) {
+        sqlx::query_as("SELECT original_url, fallback_url, check_failures FROM urls WHERE id = $1")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn switches_to_the_fallback_at_the_failure_threshold(pool: PgPool) {
+        let server = MockServer::start().await;
+        Mock::given(path("/broken"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+        let checker = checker(&pool, 2, true).await;
+        let broken = format!("{}/broken", server.uri());
+        let fallback = format!("{}/fallback", server.uri());
+        let link = insert_link(&pool, &broken, &fallback).await;
+
+        checker.check_link(&link).await.unwrap();
+        assert_eq!(
+            link_state(&pool, link.id).await,
+            (broken.clone(), Some(fallback.clone()), 1)
+        );
+
+        checker.check_link(&link).await.unwrap();
+        assert_eq!(link_state(&pool, link.id).await, (fallback, None, 0));
+
+        let switches: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM url_revisions WHERE url_id = $1 AND change = 'fallback_switch'",
+        )
+        .bind(link.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(switches, 1);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn keeps_the_destination_when_auto_fallback_is_off(pool: PgPool) {
+        let server = MockServer::start().await;
+        Mock::given(path("/broken"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+        let checker = checker(&pool, 1, false).await;
+        let broken = format!("{}/broken", server.uri());
+        let fallback = format!("{}/fallback", server.uri());
+        let link = insert_link(&pool, &broken, &fallback).await;
+
+        for _ in 0..3 {
+            checker.check_link(&link).await.unwrap();
+        }
+
+        assert_eq!(
+            link_state(&pool, link.id).await,
+            (broken, Some(fallback), 3)
+        );
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn resets_failures_when_the_destination_recovers(pool: PgPool) {
+        let server = MockServer::start().await;
+        Mock::given(path("/flaky"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(path("/flaky"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let checker = checker(&pool, 2, true).await;
+        let flaky = format!("{}/flaky", server.uri());
+        let fallback = format!("{}/fallback", server.uri());
+        let link = insert_link(&pool, &flaky, &fallback).await;
+
+        checker.check_link(&link).await.unwrap();
+        assert_eq!(link_state(&pool, link.id).await.2, 1);
+
+        checker.check_link(&link).await.unwrap();
+        assert_eq!(link_state(&pool, link.id).await, (flaky, Some(fallback), 0));
+        let status: Option<i32> =
+            sqlx::query_scalar("SELECT last_check_status FROM urls WHERE id = $1")
+                .bind(link.id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(status, Some(200));
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn retries_with_get_when_head_is_not_supported(pool: PgPool) {
+        let server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .respond_with(ResponseTemplate::new(405))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let checker = checker(&pool, 1, true).await;
+        let url = format!("{}/page", server.uri());
+        let link = insert_link(&pool, &url, &format!("{}/fallback", server.uri())).await;
+
+        checker.check_link(&link).await.unwrap();
+
+        assert_eq!(link_state(&pool, link.id).await.2, 0);
+    }
+
+    #[test]
+    fn rate_limiting_does_not_count_as_broken() {
+        assert!(!is_broken(Some(StatusCode::TOO_MANY_REQUESTS)));
+        assert!(!is_broken(Some(StatusCode::OK)));
+        assert!(is_broken(Some(StatusCode::NOT_FOUND)));
+        assert!(is_broken(Some(StatusCode::BAD_GATEWAY)));
+        assert!(is_broken(None));
+    }
+}
@@ -0,0 +1,198 @@
+use axum::body::Bytes;
+use futures::{stream, Stream};
+use serde::Serialize;
+use sqlx::PgPool;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::{
+    domain::{transfer::ExportFormat, url::Url, workspace::LinkScope},
+    error::{AppError, Result},
+    infrastructure::database,
+};
+
+// Links are read and written one page at a time, so memory use does not grow with the table
+const EXPORT_PAGE_SIZE: i64 = 1000;
+
+const CSV_HEADER: &[&str] = &[
+    "short_code",
+    "short_url",
+    "original_url",
+    "title",
+    "description",
+    "tags",
+    "collection_id",
+    "visits",
+    "interstitial_views",
+    "interstitial_confirmations",
+    "created_at",
+    "expires_at",
+    "last_check_status",
+    "check_failures",
+    "metadata",
+];
+
+/// One exported link with its stats
+#[derive(Serialize)]
+struct ExportedUrl {
+    short_code: String,
+    short_url: String,
+    original_url: String,
+    title: Option<String>,
+    description: Option<String>,
+    tags: Vec<String>,
+    collection_id: Option<Uuid>,
+    visits: i64,
+    interstitial_views: i64,
+    interstitial_confirmations: i64,
+    #[serde(with = "time::serde::rfc3339")]
+    created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    expires_at: Option<OffsetDateTime>,
+    last_check_status: Option<i32>,
+    check_failures: i32,
+    metadata: serde_json::Value,
+}
+
+impl ExportedUrl {
+    fn new(url: Url, base_url: &str) -> Self {
+        Self {
+            short_url: format!("{}/{}", base_url, url.short_code),
+            short_code: url.short_code,
+            original_url: url.original_url,
+            title: url.title,
+            description: url.description,
+            tags: url.tags,
+            collection_id: url.collection_id,
+            visits: url.visits,
+            interstitial_views: url.interstitial_views,
+            interstitial_confirmations: url.interstitial_confirmations,
+            created_at: url.created_at,
+            expires_at: url.expires_at,
+            last_check_status: url.last_check_status,
+            check_failures: url.check_failures,
+            metadata: url.metadata,
+        }
+    }
+
+    fn csv_record(&self) -> Vec<String> {
+        let format_time = |time: OffsetDateTime| time.format(&Rfc3339).unwrap_or_default();
+        let optional = |value: Option<String>| value.unwrap_or_default();
+
+        vec![
+            self.short_code.clone(),
+            self.short_url.clone(),
+            self.original_url.clone(),
+            optional(self.title.clone()),
+            optional(self.description.clone()),
+            self.tags.join("|"),
+            optional(self.collection_id.map(|id| id.to_string())),
+            self.visits.to_string(),
+            self.interstitial_views.to_string(),
+            self.interstitial_confirmations.to_string(),
+            format_time(self.created_at),
+            optional(self.expires_at.map(format_time)),
+            optional(self.last_check_status.map(|status| status.to_string())),
+            self.check_failures.to_string(),
+            self.metadata.to_string(),
+        ]
+    }
+}
+
+struct ExportState {
+    db: PgPool,
+    base_url: String,
+    format: ExportFormat,
+    /// Only links of this account or workspace
+    scope: LinkScope,
+    /// Position of the last exported link
+    after: Option<(OffsetDateTime, Uuid)>,
+    started: bool,
+    finished: bool,
+}
+
+/// Streams every link with its stats, reading the table page by page
+pub fn export_urls(
+    db: PgPool,
+    base_url: String,
+    format: ExportFormat,
+    scope: LinkScope,
+) -> impl Stream<Item = Result<Bytes>> + Send + 'static {
+    let state = ExportState {
+        db,
+        base_url,
+        format,
+        scope,
+        after: None,
+        started: false,
+        finished: false,
+    };
+
+    stream::try_unfold(state, |mut state| async move {
+        if state.finished {
+            return Ok(None);
+        }
+
+        let urls =
+            database::list_urls_after(&state.db, state.after, EXPORT_PAGE_SIZE, false, state.scope)
+                .await?;
+        state.finished = (urls.len() as i64) < EXPORT_PAGE_SIZE;
+        if let Some(last) = urls.last() {
+            state.after = Some((last.created_at, last.id));
+        }
+
+        let mut chunk = Vec::new();
+        match state.format {
+            ExportFormat::Csv => {
+                let mut writer = csv::WriterBuilder::new()
+                    .has_headers(false)
+                    .from_writer(&mut chunk);
+                if !state.started {
+                    writer.write_record(CSV_HEADER).map_err(csv_error)?;
+                }
+                for url in urls {
+                    let record = ExportedUrl::new(url, &state.base_url).csv_record();
+                    writer.write_record(&record).map_err(csv_error)?;
+                }
+                writer.flush().map_err(|e| csv_error(e.into()))?;
+            }
+            ExportFormat::Jsonl => {
+                for url in urls {
+                    serde_json::to_writer(&mut chunk, &ExportedUrl::new(url, &state.base_url))
+                        .map_err(|e| {
+                            AppError::Internal(format!("Failed to serialize link: {}", e))
+                        })?;
+                    chunk.push(b'\n');
+                }
+            }
+        }
+        state.started = true;
+
+        Ok(Some((Bytes::from(chunk), state)))
+    })
+}
+
+/// Renders rows that failed to import as a CSV report
+pub fn import_error_report(errors: &[crate::domain::transfer::ImportError]) -> Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(["row", "url", "error"])
+        .map_err(csv_error)?;
+    for error in errors {
+        writer
+            .write_record([
+                error.row_number.to_string().as_str(),
+                error.original_url.as_deref().unwrap_or_default(),
+                error.error.as_str(),
+            ])
+            .map_err(csv_error)?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| AppError::Internal(format!("Failed to write CSV: {}", e)))
+}
+
+fn csv_error(err: csv::Error) -> AppError {
+    AppError::Internal(format!("Failed to write CSV: {}", err))
+}
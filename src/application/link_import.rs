@@ -0,0 +1,562 @@
+use serde::Deserialize;
+use time::{
+    format_description::well_known::Rfc3339, macros::format_description, Date, OffsetDateTime,
+    PrimitiveDateTime,
+};
+
+use crate::{
+    application::url_service::validate_alias,
+    domain::{transfer::ImportFormat, url::CreateUrlRequest},
+    error::{AppError, Result},
+};
+
+/// A row of an import file, parsed into a link or the reason it could not be
+#[derive(Debug)]
+pub struct ImportRow {
+    /// Line or record number in the source file, so errors can be matched to the original
+    pub row_number: i32,
+    pub original_url: Option<String>,
+    pub link: std::result::Result<ImportedLink, String>,
+}
+
+/// A link to create, with the history it had in the system it is imported from
+#[derive(Debug)]
+pub struct ImportedLink {
+    pub request: CreateUrlRequest,
+    pub created_at: Option<OffsetDateTime>,
+    pub visits: i64,
+}
+
+/// Accepted header names for each column of a CSV import
+struct CsvColumns {
+    url: &'static [&'static str],
+    alias: &'static [&'static str],
+    expiry: &'static [&'static str],
+    tags: &'static [&'static str],
+    title: &'static [&'static str],
+    description: &'static [&'static str],
+    created_at: &'static [&'static str],
+    visits: &'static [&'static str],
+    /// Short codes come from another shortener: keep them when they are valid here,
+    /// generate new ones otherwise, and accept full short links in place of codes
+    foreign_codes: bool,
+}
+
+// Also matches the columns of our own export, so exported files can be imported again
+const GENERIC_COLUMNS: CsvColumns = CsvColumns {
+    url: &["url", "original_url", "target", "long_url", "destination"],
+    alias: &["alias", "short_code", "slug"],
+    expiry: &["expires_at", "expiry", "expires"],
+    tags: &["tags"],
+    title: &["title"],
+    description: &["description"],
+    created_at: &["created_at", "created"],
+    visits: &["visits", "clicks"],
+    foreign_codes: false,
+};
+
+const YOURLS_COLUMNS: CsvColumns = CsvColumns {
+    url: &["url"],
+    alias: &["keyword"],
+    expiry: &[],
+    tags: &[],
+    title: &["title"],
+    description: &[],
+    created_at: &["timestamp"],
+    visits: &["clicks"],
+    foreign_codes: true,
+};
+
+const BITLY_COLUMNS: CsvColumns = CsvColumns {
+    url: &["long_url", "long url", "destination", "original url", "url"],
+    alias: &["bitlink", "link", "short_url", "short url", "short link"],
+    expiry: &[],
+    tags: &["tags"],
+    title: &["title"],
+    description: &[],
+    created_at: &["created_at", "created", "date created", "creation date"],
+    visits: &[
+        "total_clicks",
+        "total clicks",
+        "clicks",
+        "engagements",
+        "total engagements",
+    ],
+    foreign_codes: true,
+};
+
+// Column order of the `yourls_url` table, used when an INSERT does not name its columns
+const YOURLS_TABLE_COLUMNS: &[&str] = &["keyword", "url", "title", "timestamp", "ip", "clicks"];
+
+/// Parses an import file in the given format
+pub fn parse(format: ImportFormat, data: &[u8]) -> Result<Vec<ImportRow>> {
+    match format {
+        ImportFormat::Csv => parse_csv(data, &GENERIC_COLUMNS),
+        ImportFormat::Yourls => {
+            let text = String::from_utf8_lossy(data);
+            if text.to_ascii_lowercase().contains("insert into") {
+                parse_yourls_sql(&text)
+            } else {
+                parse_csv(data, &YOURLS_COLUMNS)
+            }
+        }
+        ImportFormat::Bitly => parse_csv(data, &BITLY_COLUMNS),
+        ImportFormat::Kutt => parse_kutt_json(data),
+    }
+}
+
+/// Parses a CSV file with a header row. Only the URL column is required.
+fn parse_csv(data: &[u8], columns: &CsvColumns) -> Result<Vec<ImportRow>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(data);
+
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| AppError::BadRequest(format!("Invalid CSV header: {}", e)))?
+        .iter()
+        .map(|header| header.trim_start_matches('\u{feff}').to_ascii_lowercase())
+        .collect();
+    let column = |names: &[&str]| headers.iter().position(|h| names.contains(&h.as_str()));
+
+    let url_column = column(columns.url).ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "CSV must have a URL column named one of: {}",
+            columns.url.join(", ")
+        ))
+    })?;
+    let alias_column = column(columns.alias);
+    let expiry_column = column(columns.expiry);
+    let tags_column = column(columns.tags);
+    let title_column = column(columns.title);
+    let description_column = column(columns.description);
+    let created_at_column = column(columns.created_at);
+    let visits_column = column(columns.visits);
+
+    let mut rows = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        // The header is line 1, so data rows start at line 2 unless the reader knows better
+        let fallback_line = index as i32 + 2;
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                let row_number = e
+                    .position()
+                    .map(|position| position.line() as i32)
+                    .unwrap_or(fallback_line);
+                rows.push(ImportRow {
+                    row_number,
+                    original_url: None,
+                    link: Err(format!("Invalid CSV row: {}", e)),
+                });
+                continue;
+            }
+        };
+        let row_number = record
+            .position()
+            .map(|position| position.line() as i32)
+            .unwrap_or(fallback_line);
+
+        let field = |column: Option<usize>| {
+            column
+                .and_then(|column| record.get(column))
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        let original_url = field(Some(url_column));
+
+        let link = match original_url {
+            None => Err("Missing URL".to_string()),
+            Some(ref url) => (|| {
+                let alias = match field(alias_column) {
+                    Some(code) if columns.foreign_codes => foreign_code(&code),
+                    alias => alias,
+                };
+                Ok(ImportedLink {
+                    request: CreateUrlRequest {
+                        url: url.clone(),
+                        alias,
+                        expires_at: parse_timestamp(field(expiry_column).as_deref())?,
+                        tags: field(tags_column).map(|tags| split_tags(&tags)),
+                        title: field(title_column),
+                        description: field(description_column),
+                        dedupe: columns.foreign_codes.then_some(false),
+                        ..Default::default()
+                    },
+                    created_at: parse_timestamp(field(created_at_column).as_deref())?,
+                    visits: parse_visits(field(visits_column).as_deref())?,
+                })
+            })(),
+        };
+
+        rows.push(ImportRow {
+            row_number,
+            original_url,
+            link,
+        });
+    }
+
+    Ok(rows)
+}
+
+/// Reads the `yourls_url` rows of a YOURLS (MySQL) SQL dump. Row numbers count the
+/// imported records, since a single INSERT often spans one very long line.
+fn parse_yourls_sql(text: &str) -> Result<Vec<ImportRow>> {
+    let lower = text.to_ascii_lowercase();
+    let mut rows = Vec::new();
+    let mut offset = 0;
+
+    while let Some(position) = lower[offset..].find("insert into") {
+        let start = offset + position + "insert into".len();
+        let mut scanner = SqlScanner::new(&text[start..]);
+
+        let table = scanner.identifier()?;
+        let columns: Vec<String> = if scanner.peek() == Some('(') {
+            scanner.identifier_list()?
+        } else {
+            YOURLS_TABLE_COLUMNS.iter().map(|c| c.to_string()).collect()
+        };
+        scanner.keyword("values")?;
+
+        // Only the links table matters; options and click logs are skipped
+        let is_url_table = table == "url" || table.ends_with("_url");
+        loop {
+            let values = scanner.tuple()?;
+            if is_url_table {
+                let row_number = rows.len() as i32 + 1;
+                rows.push(yourls_row(row_number, &columns, values));
+            }
+            match scanner.next_token_char() {
+                Some(',') => continue,
+                _ => break,
+            }
+        }
+
+        offset = start + scanner.position;
+    }
+
+    Ok(rows)
+}
+
+fn yourls_row(row_number: i32, columns: &[String], values: Vec<Option<String>>) -> ImportRow {
+    let field = |name: &str| {
+        columns
+            .iter()
+            .position(|column| column == name)
+            .and_then(|index| values.get(index).cloned().flatten())
+            .filter(|value| !value.is_empty())
+    };
+    let original_url = field("url");
+
+    let link = match original_url {
+        None => Err("Missing URL".to_string()),
+        Some(ref url) => (|| {
+            Ok(ImportedLink {
+                request: CreateUrlRequest {
+                    url: url.clone(),
+                    alias: field("keyword").and_then(|code| foreign_code(&code)),
+                    title: field("title"),
+                    dedupe: Some(false),
+                    ..Default::default()
+                },
+                created_at: parse_timestamp(field("timestamp").as_deref())?,
+                visits: parse_visits(field("clicks").as_deref())?,
+            })
+        })(),
+    };
+
+    ImportRow {
+        row_number,
+        original_url,
+        link,
+    }
+}
+
+/// A link as returned by Kutt's links API
+#[derive(Deserialize)]
+struct KuttLink {
+    address: Option<String>,
+    target: Option<String>,
+    description: Option<String>,
+    visit_count: Option<i64>,
+    created_at: Option<String>,
+    expire_in: Option<String>,
+}
+
+/// Kutt exports are either a bare array of links or an API page with a `data` array
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KuttExport {
+    Page { data: Vec<KuttLink> },
+    Links(Vec<KuttLink>),
+}
+
+fn parse_kutt_json(data: &[u8]) -> Result<Vec<ImportRow>> {
+    let links = match serde_json::from_slice(data) {
+        Ok(KuttExport::Page { data }) => data,
+        Ok(KuttExport::Links(links)) => links,
+        Err(e) => return Err(AppError::BadRequest(format!("Invalid Kutt JSON: {}", e))),
+    };
+
+    let rows = links
+        .into_iter()
+        .enumerate()
+        .map(|(index, link)| {
+            let original_url = link.target.filter(|target| !target.is_empty());
+            let imported = match original_url {
+                None => Err("Missing URL".to_string()),
+                Some(ref url) => (|| {
+                    Ok(ImportedLink {
+                        request: CreateUrlRequest {
+                            url: url.clone(),
+                            alias: link.address.as_deref().and_then(foreign_code),
+                            expires_at: parse_timestamp(link.expire_in.as_deref())?,
+                            description: link.description.clone(),
+                            dedupe: Some(false),
+                            ..Default::default()
+                        },
+                        created_at: parse_timestamp(link.created_at.as_deref())?,
+                        visits: link.visit_count.unwrap_or(0).max(0),
+                    })
+                })(),
+            };
+
+            ImportRow {
+                row_number: index as i32 + 1,
+                original_url,
+                link: imported,
+            }
+        })
+        .collect();
+
+    Ok(rows)
+}
+
+/// Keeps another shortener's code when it is a valid alias here, accepting full short links
+/// such as `bit.ly/abc`. Codes that do not fit get a generated one instead.
+fn foreign_code(code: &str) -> Option<String> {
+    let code = code
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default();
+    validate_alias(code).ok().map(|_| code.to_string())
+}
+
+/// Accepts RFC 3339, `YYYY-MM-DD HH:MM:SS` (UTC), ISO 8601 with a `+0000` style offset
+/// and plain `YYYY-MM-DD` dates (midnight UTC)
+pub fn parse_timestamp(value: Option<&str>) -> std::result::Result<Option<OffsetDateTime>, String> {
+    let Some(value) = value.map(str::trim).filter(|value| !value.is_empty()) else {
+        return Ok(None);
+    };
+
+    if let Ok(timestamp) = OffsetDateTime::parse(value, &Rfc3339) {
+        return Ok(Some(timestamp));
+    }
+    if let Ok(timestamp) = OffsetDateTime::parse(
+        value,
+        format_description!(
+            "[year]-[month]-[day]T[hour]:[minute]:[second][offset_hour sign:mandatory][offset_minute]"
+        ),
+    ) {
+        return Ok(Some(timestamp));
+    }
+    if let Ok(timestamp) = PrimitiveDateTime::parse(
+        value,
+        format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"),
+    ) {
+        return Ok(Some(timestamp.assume_utc()));
+    }
+    Date::parse(value, format_description!("[year]-[month]-[day]"))
+        .map(|date| Some(date.midnight().assume_utc()))
+        .map_err(|_| format!("Invalid date: {}", value))
+}
+
+fn parse_visits(value: Option<&str>) -> std::result::Result<i64, String> {
+    match value {
+        None => Ok(0),
+        Some(value) => value
+            .replace(',', "")
+            .parse::<i64>()
+            .map(|visits| visits.max(0))
+            .map_err(|_| format!("Invalid visit count: {}", value)),
+    }
+}
+
+/// Tags can be separated by `|`, `,` or `;`
+fn split_tags(tags: &str) -> Vec<String> {
+    tags.split(['|', ',', ';'])
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Minimal reader for the MySQL INSERT statements found in SQL dumps
+struct SqlScanner<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> SqlScanner<'a> {
+    fn new(text: &'a str) -> Self {
+        Self { text, position: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.rest().chars().next()
+    }
+
+    fn next_token_char(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        match self.next_token_char() {
+            Some(c) if c == expected => Ok(()),
+            other => Err(self.error(&format!("expected '{}', found {:?}", expected, other))),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<()> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        if rest.len() >= keyword.len() && rest[..keyword.len()].eq_ignore_ascii_case(keyword) {
+            self.position += keyword.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {}", keyword.to_uppercase())))
+        }
+    }
+
+    /// Reads a table or column name, with or without backticks
+    fn identifier(&mut self) -> Result<String> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let (name, consumed) = match rest.strip_prefix('`') {
+            Some(quoted) => {
+                let end = quoted
+                    .find('`')
+                    .ok_or_else(|| self.error("unterminated identifier"))?;
+                (&quoted[..end], end + 2)
+            }
+            None => {
+                let end = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+                    .unwrap_or(rest.len());
+                (&rest[..end], end)
+            }
+        };
+        if name.is_empty() {
+            return Err(self.error("expected a name"));
+        }
+        self.position += consumed;
+
+        // Keep only the table name of `database`.`table`
+        Ok(name.rsplit('.').next().unwrap_or(name).to_ascii_lowercase())
+    }
+
+    fn identifier_list(&mut self) -> Result<Vec<String>> {
+        self.expect('(')?;
+        let mut names = vec![self.identifier()?];
+        while self.peek() == Some(',') {
+            self.expect(',')?;
+            names.push(self.identifier()?);
+        }
+        self.expect(')')?;
+        Ok(names)
+    }
+
+    /// Reads a parenthesized list of values; `NULL` becomes `None`
+    fn tuple(&mut self) -> Result<Vec<Option<String>>> {
+        self.expect('(')?;
+        let mut values = Vec::new();
+        loop {
+            values.push(self.value()?);
+            match self.next_token_char() {
+                Some(',') => continue,
+                Some(')') => return Ok(values),
+                other => return Err(self.error(&format!("unexpected {:?} in values", other))),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Option<String>> {
+        match self.peek() {
+            Some(quote @ ('\'' | '"')) => {
+                self.position += 1;
+                self.quoted_string(quote).map(Some)
+            }
+            Some(_) => {
+                let rest = self.rest();
+                let end = rest
+                    .find([',', ')'])
+                    .ok_or_else(|| self.error("unterminated value"))?;
+                let value = rest[..end].trim();
+                self.position += end;
+                if value.eq_ignore_ascii_case("null") {
+                    Ok(None)
+                } else {
+                    Ok(Some(value.to_string()))
+                }
+            }
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn quoted_string(&mut self, quote: char) -> Result<String> {
+        let mut value = String::new();
+        let mut chars = self.rest().char_indices();
+
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '\\' => {
+                    let (_, escaped) = chars
+                        .next()
+                        .ok_or_else(|| self.error("unterminated string"))?;
+                    value.push(match escaped {
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        '0' => '\0',
+                        other => other,
+                    });
+                }
+                c if c == quote => {
+                    // A doubled quote stands for the quote itself
+                    if self.rest()[index + 1..].starts_with(quote) {
+                        chars.next();
+                        value.push(quote);
+                    } else {
+                        self.position += index + 1;
+                        return Ok(value);
+                    }
+                }
+                c => value.push(c),
+            }
+        }
+
+        Err(self.error("unterminated string"))
+    }
+
+    fn error(&self, message: &str) -> AppError {
+        let context: String = self.rest().chars().take(40).collect();
+        AppError::BadRequest(format!(
+            "Invalid SQL dump: {} near \"{}\"",
+            message, context
+        ))
+    }
+}
@@ -0,0 +1,295 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use time::{Duration, OffsetDateTime};
+
+use crate::{
+    config::AppConfig,
+    domain::signed_link::LinkSignature,
+    error::{AppError, Result},
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Signs links and checks signed links with the configured keys. A signed link carries its
+/// expiry, optional recipient and key ID next to an HMAC-SHA256 of them and the short code,
+/// so it can be rejected without looking anything up.
+pub struct LinkSigner {
+    keys: Vec<(String, Vec<u8>)>,
+    /// Key new links are signed with
+    current: Option<String>,
+    max_ttl: Duration,
+}
+
+impl LinkSigner {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            keys: config
+                .link_signing_keys
+                .iter()
+                .map(|(id, secret)| (id.clone(), secret.as_bytes().to_vec()))
+                .collect(),
+            current: config.link_signing_key_id.clone(),
+            max_ttl: Duration::seconds(config.signed_link_max_ttl_secs as i64),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.current.is_some()
+    }
+
+    pub fn max_ttl(&self) -> Duration {
+        self.max_ttl
+    }
+
+    /// The query string of a signed link, and the ID of the key it was signed with. An empty
+    /// recipient is refused, as it would sign the same as none.
+    pub fn sign(
+        &self,
+        short_code: &str,
+        expires_at: OffsetDateTime,
+        recipient: Option<&str>,
+    ) -> Result<(String, String)> {
+        let (key_id, secret) = self
+            .current
+            .as_ref()
+            .and_then(|current| self.keys.iter().find(|(id, _)| id == current))
+            .ok_or_else(|| {
+                AppError::BadRequest("Link signing is not configured on this server".to_string())
+            })?;
+
+        if recipient == Some("") {
+            return Err(AppError::BadRequest(
+                "The recipient of a signed link cannot be empty".to_string(),
+            ));
+        }
+
+        let expires = expires_at.unix_timestamp();
+        let mut mac = mac(secret)?;
+        mac.update(&message(key_id, short_code, expires, recipient));
+        let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+
+        Ok((
+            key_id.clone(),
+            query(&LinkSignature {
+                expires: Some(expires),
+                recipient: recipient.map(str::to_string),
+                key_id: Some(key_id.clone()),
+                signature: Some(signature),
+            }),
+        ))
+    }
+
+    /// Checks the signature of a link, then its expiry. Links signed with a key that has
+    /// been removed are rejected like forged ones.
+    pub fn verify(&self, short_code: &str, signature: &LinkSignature) -> Result<()> {
+        let invalid = || AppError::Forbidden("Invalid link signature".to_string());
+        let (Some(expires), Some(key_id), Some(encoded)) = (
+            signature.expires,
+            signature.key_id.as_deref(),
+            signature.signature.as_deref(),
+        ) else {
+            return Err(invalid());
+        };
+        let Some((_, secret)) = self.keys.iter().find(|(id, _)| id == key_id) else {
+            tracing::debug!("Signed link for {} uses unknown key {}", short_code, key_id);
+            return Err(invalid());
+        };
+        let decoded = URL_SAFE_NO_PAD.decode(encoded).map_err(|_| invalid())?;
+
+        let mut mac = mac(secret)?;
+        mac.update(&message(
+            key_id,
+            short_code,
+            expires,
+            signature.recipient.as_deref(),
+        ));
+        // Compares in constant time
+        mac.verify_slice(&decoded).map_err(|_| invalid())?;
+
+        if OffsetDateTime::now_utc().unix_timestamp() >= expires {
+            return Err(AppError::Gone("This link has expired".to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+/// The query string carrying a signature, to pass it on to further requests
+pub fn query(signature: &LinkSignature) -> String {
+    let mut query = url::form_urlencoded::Serializer::new(String::new());
+    if let Some(expires) = signature.expires {
+        query.append_pair("exp", &expires.to_string());
+    }
+    if let Some(recipient) = &signature.recipient {
+        query.append_pair("rcpt", recipient);
+    }
+    if let Some(key_id) = &signature.key_id {
+        query.append_pair("kid", key_id);
+    }
+    if let Some(signature) = &signature.signature {
+        query.append_pair("sig", signature);
+    }
+    query.finish()
+}
+
+fn mac(secret: &[u8]) -> Result<HmacSha256> {
+    HmacSha256::new_from_slice(secret)
+        .map_err(|e| AppError::Internal(format!("Invalid link signing key: {}", e)))
+}
+
+// The recipient comes last, as it is the only part that can contain the separator. No
+// recipient and an empty one give the same message, so `sign` refuses empty recipients.
+fn message(key_id: &str, short_code: &str, expires: i64, recipient: Option<&str>) -> Vec<u8> {
+    format!(
+        "{}\n{}\n{}\n{}",
+        key_id,
+        short_code,
+        expires,
+        recipient.unwrap_or_default()
+    )
+    .into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    fn signer(keys: &[&str], current: &str) -> LinkSigner {
+        LinkSigner {
+            keys: keys
+                .iter()
+                .map(|id| (id.to_string(), format!("secret-{}", id).into_bytes()))
+                .collect(),
+            current: Some(current.to_string()),
+            max_ttl: Duration::days(30),
+        }
+    }
+
+    /// The signature of a signed link's query string
+    fn parse(query: &str) -> LinkSignature {
+        let mut signature = LinkSignature::default();
+        for (name, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            let value = value.into_owned();
+            match name.as_ref() {
+                "exp" => signature.expires = value.parse().ok(),
+                "rcpt" => signature.recipient = Some(value),
+                "kid" => signature.key_id = Some(value),
+                "sig" => signature.signature = Some(value),
+                _ => {}
+            }
+        }
+        signature
+    }
+
+    fn in_an_hour() -> OffsetDateTime {
+        OffsetDateTime::now_utc() + Duration::hours(1)
+    }
+
+    #[test]
+    fn verifies_the_links_it_signs() {
+        let signer = signer(&["k1"], "k1");
+        for recipient in [None, Some("alice@example.com"), Some("a\nb")] {
+            let (key_id, query) = signer.sign("abc", in_an_hour(), recipient).unwrap();
+            assert_eq!(key_id, "k1");
+            let signature = parse(&query);
+            assert_eq!(signature.recipient.as_deref(), recipient);
+            assert!(signer.verify("abc", &signature).is_ok());
+        }
+    }
+
+    #[test]
+    fn rejects_tampered_links() {
+        let signer = signer(&["k1"], "k1");
+        let (_, query) = signer
+            .sign("abc", in_an_hour(), Some("alice@example.com"))
+            .unwrap();
+        let signature = parse(&query);
+
+        assert_matches!(
+            signer.verify("abd", &signature),
+            Err(AppError::Forbidden(_))
+        );
+        let tampered = [
+            LinkSignature {
+                expires: signature.expires.map(|exp| exp + 86400),
+                ..signature.clone()
+            },
+            LinkSignature {
+                recipient: Some("bob@example.com".to_string()),
+                ..signature.clone()
+            },
+            LinkSignature {
+                recipient: None,
+                ..signature.clone()
+            },
+            LinkSignature {
+                signature: Some("not base64!".to_string()),
+                ..signature.clone()
+            },
+        ];
+        for signature in tampered {
+            assert_matches!(
+                signer.verify("abc", &signature),
+                Err(AppError::Forbidden(_))
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_and_removed_keys() {
+        let (_, query) = signer(&["k1"], "k1")
+            .sign("abc", in_an_hour(), None)
+            .unwrap();
+        let signature = parse(&query);
+
+        let removed = signer(&["k2"], "k2");
+        assert_matches!(
+            removed.verify("abc", &signature),
+            Err(AppError::Forbidden(_))
+        );
+        // Relabelling the link with a configured key does not help either
+        let renamed = LinkSignature {
+            key_id: Some("k2".to_string()),
+            ..signature
+        };
+        assert_matches!(removed.verify("abc", &renamed), Err(AppError::Forbidden(_)));
+    }
+
+    #[test]
+    fn verifies_links_of_the_previous_key_while_it_is_configured() {
+        let (_, query) = signer(&["k1"], "k1")
+            .sign("abc", in_an_hour(), None)
+            .unwrap();
+        let signature = parse(&query);
+
+        let rotated = signer(&["k1", "k2"], "k2");
+        assert!(rotated.verify("abc", &signature).is_ok());
+        let (key_id, query) = rotated.sign("abc", in_an_hour(), None).unwrap();
+        assert_eq!(key_id, "k2");
+        assert!(rotated.verify("abc", &parse(&query)).is_ok());
+    }
+
+    #[test]
+    fn expired_links_are_gone() {
+        let signer = signer(&["k1"], "k1");
+        let (_, query) = signer
+            .sign(
+                "abc",
+                OffsetDateTime::now_utc() - Duration::seconds(1),
+                None,
+            )
+            .unwrap();
+        assert_matches!(signer.verify("abc", &parse(&query)), Err(AppError::Gone(_)));
+    }
+
+    #[test]
+    fn refuses_empty_recipients() {
+        let signer = signer(&["k1"], "k1");
+        assert_matches!(
+            signer.sign("abc", in_an_hour(), Some("")),
+            Err(AppError::BadRequest(_))
+        );
+    }
+}
@@ -1 +1,16 @@
+pub mod accounts;
+pub mod audit;
+pub mod backup;
+pub mod destination_metadata;
+pub mod expired_cleanup;
+pub mod jwt_auth;
+pub mod link_checker;
+pub mod link_export;
+pub mod link_import;
+pub mod link_signing;
+pub mod qr_code;
+pub mod quota;
+pub mod schedule;
+pub mod scheduler;
+pub mod trash_purge;
 pub mod url_service;
@@ -0,0 +1,202 @@
+use image::{codecs::png::PngEncoder, ImageEncoder, Rgba, RgbaImage};
+use qrcode::{Color, EcLevel, QrCode};
+
+use crate::{
+    domain::qr::{QrCodeQuery, QrErrorCorrection, QrFormat},
+    error::{AppError, Result},
+};
+
+const DEFAULT_SIZE: u32 = 256;
+const MIN_SIZE: u32 = 64;
+const MAX_SIZE: u32 = 2048;
+const DEFAULT_MARGIN: u32 = 4;
+const MAX_MARGIN: u32 = 16;
+
+/// Validated rendering options for a QR code image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QrOptions {
+    pub format: QrFormat,
+    pub size: u32,
+    pub margin: u32,
+    pub ec_level: QrErrorCorrection,
+    pub foreground: [u8; 4],
+    pub background: [u8; 4],
+}
+
+impl QrOptions {
+    pub fn from_query(query: QrCodeQuery, format: QrFormat) -> Result<Self> {
+        let size = query.size.unwrap_or(DEFAULT_SIZE);
+        if !(MIN_SIZE..=MAX_SIZE).contains(&size) {
+            return Err(AppError::BadRequest(format!(
+                "size must be between {} and {} pixels",
+                MIN_SIZE, MAX_SIZE
+            )));
+        }
+
+        let margin = query.margin.unwrap_or(DEFAULT_MARGIN);
+        if margin > MAX_MARGIN {
+            return Err(AppError::BadRequest(format!(
+                "margin must be at most {} modules",
+                MAX_MARGIN
+            )));
+        }
+
+        let foreground = match query.fg {
+            Some(ref fg) => parse_hex_color(fg)?,
+            None => [0, 0, 0, 255],
+        };
+        let background = match query.bg {
+            Some(ref bg) => parse_hex_color(bg)?,
+            None => [255, 255, 255, 255],
+        };
+
+        Ok(Self {
+            format,
+            size,
+            margin,
+            ec_level: query.ec_level.unwrap_or(QrErrorCorrection::M),
+            foreground,
+            background,
+        })
+    }
+
+    /// Cache key identifying the rendered image for a short code and these options
+    pub fn cache_key(&self, short_code: &str) -> String {
+        format!(
+            "qr:{}:{:?}:{}:{}:{:?}:{}:{}",
+            short_code,
+            self.format,
+            self.size,
+            self.margin,
+            self.ec_level,
+            hex_color(&self.foreground),
+            hex_color(&self.background)
+        )
+    }
+
+    pub fn render(&self, data: &str) -> Result<Vec<u8>> {
+        let ec_level = match self.ec_level {
+            QrErrorCorrection::L => EcLevel::L,
+            QrErrorCorrection::M => EcLevel::M,
+            QrErrorCorrection::Q => EcLevel::Q,
+            QrErrorCorrection::H => EcLevel::H,
+        };
+        let code = QrCode::with_error_correction_level(data, ec_level)
+            .map_err(|e| AppError::Internal(format!("Failed to encode QR code: {}", e)))?;
+
+        match self.format {
+            QrFormat::Png => self.render_png(&code),
+            QrFormat::Svg => Ok(self.render_svg(&code).into_bytes()),
+        }
+    }
+
+    /// Size of one PNG module in pixels. Modules are whole pixels so they stay sharp, which
+    /// means the code itself is rounded down to the nearest multiple of the module count;
+    /// it is never smaller than one pixel per module.
+    fn module_size(&self, code: &QrCode) -> u32 {
+        let modules = code.width() as u32 + 2 * self.margin;
+        (self.size / modules).max(1)
+    }
+
+    /// Renders a PNG of exactly `size` pixels, centering the code and filling the pixels
+    /// left over from rounding with background. Only a code with more modules than `size`
+    /// pixels comes out larger, at one pixel per module.
+    fn render_png(&self, code: &QrCode) -> Result<Vec<u8>> {
+        let width = code.width();
+        let colors = code.to_colors();
+        let module_size = self.module_size(code);
+        let code_dimension = (width as u32 + 2 * self.margin) * module_size;
+        let dimension = self.size.max(code_dimension);
+        let offset = (dimension - code_dimension) / 2;
+
+        let mut image = RgbaImage::from_pixel(dimension, dimension, Rgba(self.background));
+        for (index, color) in colors.iter().enumerate() {
+            if *color != Color::Dark {
+                continue;
+            }
+            let x0 = offset + ((index % width) as u32 + self.margin) * module_size;
+            let y0 = offset + ((index / width) as u32 + self.margin) * module_size;
+            for y in y0..y0 + module_size {
+                for x in x0..x0 + module_size {
+                    image.put_pixel(x, y, Rgba(self.foreground));
+                }
+            }
+        }
+
+        let mut buffer = Vec::new();
+        PngEncoder::new(&mut buffer)
+            .write_image(
+                image.as_raw(),
+                dimension,
+                dimension,
+                image::ExtendedColorType::Rgba8,
+            )
+            .map_err(|e| AppError::Internal(format!("Failed to encode PNG: {}", e)))?;
+
+        Ok(buffer)
+    }
+
+    fn render_svg(&self, code: &QrCode) -> String {
+        let width = code.width();
+        let colors = code.to_colors();
+        let modules = width as u32 + 2 * self.margin;
+
+        let mut path = String::new();
+        for (index, color) in colors.iter().enumerate() {
+            if *color == Color::Dark {
+                let x = (index % width) as u32 + self.margin;
+                let y = (index / width) as u32 + self.margin;
+                path.push_str(&format!("M{} {}h1v1h-1z", x, y));
+            }
+        }
+
+        format!(
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+                r#"<svg xmlns="http://www.w3.org/2000/svg" version="1.1" width="{dim}" height="{dim}" viewBox="0 0 {modules} {modules}" shape-rendering="crispEdges">"#,
+                r#"<rect width="{modules}" height="{modules}" fill="{bg}" fill-opacity="{bg_opacity}"/>"#,
+                r#"<path d="{path}" fill="{fg}" fill-opacity="{fg_opacity}"/>"#,
+                "</svg>"
+            ),
+            dim = self.size,
+            modules = modules,
+            bg = svg_color(&self.background),
+            bg_opacity = svg_opacity(&self.background),
+            path = path,
+            fg = svg_color(&self.foreground),
+            fg_opacity = svg_opacity(&self.foreground),
+        )
+    }
+}
+
+/// Parses `RRGGBB` or `RRGGBBAA`, with or without a leading `#`
+fn parse_hex_color(value: &str) -> Result<[u8; 4]> {
+    let hex = value.trim_start_matches('#');
+    let invalid = || AppError::BadRequest(format!("Invalid color: {}", value));
+
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+
+    let mut rgba = [0, 0, 0, 255];
+    for (i, channel) in rgba.iter_mut().enumerate().take(hex.len() / 2) {
+        *channel = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+    }
+
+    Ok(rgba)
+}
+
+fn hex_color(rgba: &[u8; 4]) -> String {
+    format!(
+        "{:02x}{:02x}{:02x}{:02x}",
+        rgba[0], rgba[1], rgba[2], rgba[3]
+    )
+}
+
+fn svg_color(rgba: &[u8; 4]) -> String {
+    format!("#{:02x}{:02x}{:02x}", rgba[0], rgba[1], rgba[2])
+}
+
+fn svg_opacity(rgba: &[u8; 4]) -> String {
+    format!("{:.3}", rgba[3] as f32 / 255.0)
+}
@@ -0,0 +1,367 @@
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime, Time};
+use uuid::Uuid;
+
+use crate::{
+    domain::{
+        quota::{LimitUsage, Quota, QuotaSubject, QuotaUsage},
+        user::Principal,
+        workspace::LinkScope,
+    },
+    error::{AppError, Result},
+    infrastructure::{cache::Cache, database},
+};
+
+// Daily counters outlive their day a little, so they can still be read around midnight
+const DAILY_COUNTER_TTL_SECS: u64 = 2 * 86400;
+
+/// The quotas that apply to links created by a principal. Members create links for their
+/// workspace, and workspace API keys count towards both the key and its workspace. The
+/// server's API key is not limited.
+pub fn subjects(principal: &Principal) -> Vec<(QuotaSubject, Uuid)> {
+    match principal {
+        Principal::User(user) => vec![(QuotaSubject::User, user.id)],
+        Principal::Member(_, access) => vec![(QuotaSubject::Workspace, access.workspace_id)],
+        Principal::WorkspaceKey { key_id, access } => vec![
+            (QuotaSubject::ApiKey, *key_id),
+            (QuotaSubject::Workspace, access.workspace_id),
+        ],
+        Principal::Anonymous | Principal::ApiKey => Vec::new(),
+    }
+}
+
+/// Links counted towards the daily and total quotas of the subjects, until released
+#[derive(Debug)]
+pub struct Reservation {
+    /// Daily counters in the cache
+    counters: Vec<(String, Option<u64>)>,
+    /// Subjects whose total in the database includes the links
+    totals: Vec<(QuotaSubject, Uuid)>,
+    count: i64,
+}
+
+impl Reservation {
+    /// Gives back `count` of the reserved links, such as those that were not created
+    pub async fn release(&mut self, db: &PgPool, cache: &Cache, count: i64) {
+        let count = count.min(self.count);
+        if count <= 0 {
+            return;
+        }
+        self.count -= count;
+        if !self.counters.is_empty() {
+            if let Err(err) = cache.incr_many(&self.counters, -count).await {
+                tracing::error!(
+                    "Failed to release {} link(s) of daily quota: {}",
+                    count,
+                    err
+                );
+            }
+        }
+        for (subject_type, subject_id) in &self.totals {
+            if let Err(err) =
+                database::add_quota_total(db, *subject_type, *subject_id, -count).await
+            {
+                tracing::error!(
+                    "Failed to release {} link(s) of total quota: {}",
+                    count,
+                    err
+                );
+            }
+        }
+    }
+
+    /// Gives back every reserved link
+    pub async fn cancel(mut self, db: &PgPool, cache: &Cache) {
+        let count = self.count;
+        self.release(db, cache, count).await;
+    }
+}
+
+/// Counts `count` new links towards the quotas of a principal, failing if that would exceed
+/// any of them. Daily limits fail with 429, as they clear by themselves; total and active
+/// limits fail with 403.
+pub async fn reserve(
+    db: &PgPool,
+    cache: &Cache,
+    principal: &Principal,
+    count: i64,
+) -> Result<Reservation> {
+    let subjects = subjects(principal);
+    let now = OffsetDateTime::now_utc();
+    let counters: Vec<(String, Option<u64>)> = subjects
+        .iter()
+        .map(|(subject_type, subject_id)| {
+            (
+                daily_key(*subject_type, *subject_id, now),
+                Some(DAILY_COUNTER_TTL_SECS),
+            )
+        })
+        .collect();
+    let mut reservation = Reservation {
+        counters,
+        totals: Vec::new(),
+        count: 0,
+    };
+    if subjects.is_empty() || count <= 0 {
+        return Ok(reservation);
+    }
+
+    // Counting first and checking after keeps concurrent requests from both taking the
+    // last links of a quota
+    let daily = cache.incr_many(&reservation.counters, count).await?;
+    reservation.count = count;
+    let checked = async {
+        for (index, (subject_type, subject_id)) in subjects.iter().enumerate() {
+            let total = database::add_quota_total(db, *subject_type, *subject_id, count).await?;
+            reservation.totals.push((*subject_type, *subject_id));
+            let Some(quota) = database::get_quota(db, *subject_type, *subject_id).await? else {
+                continue;
+            };
+            check(&quota, daily[index], total)?;
+            let scope = link_scope(*subject_type, *subject_id);
+            if let (Some(limit), Some(scope)) = (quota.active_links, scope) {
+                let active = database::count_active_links(db, scope).await?;
+                if active + count > limit {
+                    return Err(AppError::Forbidden(format!(
+                        "This {} has reached its quota of {} active links; delete links or \
+                         let them expire to create more",
+                        subject_name(*subject_type),
+                        limit
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+    .await;
+
+    match checked {
+        Ok(()) => Ok(reservation),
+        Err(err) => {
+            reservation.cancel(db, cache).await;
+            Err(err)
+        }
+    }
+}
+
+/// How much of their quotas the subjects of a principal have used
+pub async fn usage(db: &PgPool, cache: &Cache, principal: &Principal) -> Result<Vec<QuotaUsage>> {
+    let now = OffsetDateTime::now_utc();
+    let mut usage = Vec::new();
+    for (subject_type, subject_id) in subjects(principal) {
+        let quota = database::get_quota(db, subject_type, subject_id).await?;
+        let daily = cache
+            .get_counters(&[daily_key(subject_type, subject_id, now)])
+            .await?;
+        let total = database::get_quota_total(db, subject_type, subject_id).await?;
+        let active_links = match link_scope(subject_type, subject_id) {
+            Some(scope) => Some(LimitUsage::new(
+                quota.as_ref().and_then(|quota| quota.active_links),
+                database::count_active_links(db, scope).await?,
+            )),
+            None => None,
+        };
+
+        usage.push(QuotaUsage {
+            subject_type,
+            subject_id,
+            daily_links: LimitUsage::new(
+                quota.as_ref().and_then(|quota| quota.daily_links),
+                daily[0],
+            ),
+            daily_resets_at: next_midnight(now),
+            total_links: LimitUsage::new(quota.as_ref().and_then(|quota| quota.total_links), total),
+            active_links,
+        });
+    }
+
+    Ok(usage)
+}
+
+/// Checks the counts of a subject, including links just reserved, against its quota
+fn check(quota: &Quota, daily: i64, total: i64) -> Result<()> {
+    let name = subject_name(quota.subject_type);
+    if let Some(limit) = quota.daily_links.filter(|limit| daily > *limit) {
+        return Err(AppError::TooManyRequests(format!(
+            "This {} has reached its quota of {} links per day; it resets at {}",
+            name,
+            limit,
+            next_midnight(OffsetDateTime::now_utc())
+                .format(&time::format_description::well_known::Rfc3339)
+                .unwrap_or_default()
+        )));
+    }
+    if let Some(limit) = quota.total_links.filter(|limit| total > *limit) {
+        return Err(AppError::Forbidden(format!(
+            "This {} has reached its quota of {} links",
+            name, limit
+        )));
+    }
+
+    Ok(())
+}
+
+/// The links an active link quota counts; API keys hold no links of their own
+fn link_scope(subject_type: QuotaSubject, subject_id: Uuid) -> Option<LinkScope> {
+    match subject_type {
+        QuotaSubject::User => Some(LinkScope {
+            owner_id: Some(subject_id),
+            workspace_id: None,
+        }),
+        QuotaSubject::Workspace => Some(LinkScope {
+            owner_id: None,
+            workspace_id: Some(subject_id),
+        }),
+        QuotaSubject::ApiKey => None,
+    }
+}
+
+fn subject_name(subject_type: QuotaSubject) -> &'static str {
+    match subject_type {
+        QuotaSubject::User => "account",
+        QuotaSubject::Workspace => "workspace",
+        QuotaSubject::ApiKey => "API key",
+    }
+}
+
+fn daily_key(subject_type: QuotaSubject, subject_id: Uuid, now: OffsetDateTime) -> String {
+    format!(
+        "quota:{}:{}:day:{}",
+        subject_type.as_str(),
+        subject_id,
+        now.date()
+    )
+}
+
+/// The start of the next UTC day, when daily counts start over
+fn next_midnight(now: OffsetDateTime) -> OffsetDateTime {
+    now.replace_time(Time::MIDNIGHT) + Duration::days(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    async fn setup(pool: &PgPool) -> (Cache, Principal) {
+        crate::infrastructure::migrations::run_migrations_if_needed(pool)
+            .await
+            .unwrap();
+        // Daily counters live in the shared cache, so every test counts for its own account
+        let email = format!("{}@example.com", Uuid::new_v4());
+        let user = database::create_user(pool, &email, "hash", false)
+            .await
+            .unwrap();
+        (Cache::connect_for_tests().await, Principal::User(user))
+    }
+
+    async fn set_quota(
+        pool: &PgPool,
+        principal: &Principal,
+        daily: Option<i64>,
+        total: Option<i64>,
+        active: Option<i64>,
+    ) {
+        let (subject_type, subject_id) = subjects(principal)[0];
+        database::set_quota(pool, subject_type, subject_id, daily, total, active)
+            .await
+            .unwrap();
+    }
+
+    /// Links created today and in total
+    async fn used(pool: &PgPool, cache: &Cache, principal: &Principal) -> (i64, i64) {
+        let usage = &usage(pool, cache, principal).await.unwrap()[0];
+        (usage.daily_links.used, usage.total_links.used)
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn daily_limits_fail_with_429_and_give_the_links_back(pool: PgPool) {
+        let (cache, principal) = setup(&pool).await;
+        set_quota(&pool, &principal, Some(3), None, None).await;
+
+        reserve(&pool, &cache, &principal, 2).await.unwrap();
+        let result = reserve(&pool, &cache, &principal, 2).await;
+
+        assert_matches!(result, Err(AppError::TooManyRequests(_)));
+        assert_eq!(used(&pool, &cache, &principal).await, (2, 2));
+        reserve(&pool, &cache, &principal, 1).await.unwrap();
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn total_and_active_limits_fail_with_403(pool: PgPool) {
+        let (cache, principal) = setup(&pool).await;
+        set_quota(&pool, &principal, None, Some(2), None).await;
+        reserve(&pool, &cache, &principal, 2).await.unwrap();
+        assert_matches!(
+            reserve(&pool, &cache, &principal, 1).await,
+            Err(AppError::Forbidden(message)) if message.contains("quota of 2 links")
+        );
+
+        let (cache, principal) = setup(&pool).await;
+        set_quota(&pool, &principal, None, None, Some(1)).await;
+        sqlx::query("INSERT INTO urls (original_url, short_code, owner_id) VALUES ('https://example.com', 'held', $1)")
+            .bind(principal.user_id())
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_matches!(
+            reserve(&pool, &cache, &principal, 1).await,
+            Err(AppError::Forbidden(message)) if message.contains("active links")
+        );
+        assert_eq!(used(&pool, &cache, &principal).await, (0, 0));
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn released_links_count_again(pool: PgPool) {
+        let (cache, principal) = setup(&pool).await;
+        set_quota(&pool, &principal, Some(5), Some(5), None).await;
+
+        let mut reservation = reserve(&pool, &cache, &principal, 4).await.unwrap();
+        reservation.release(&pool, &cache, 1).await;
+        assert_eq!(used(&pool, &cache, &principal).await, (3, 3));
+        // Never gives back more than was reserved
+        reservation.release(&pool, &cache, 10).await;
+        assert_eq!(used(&pool, &cache, &principal).await, (0, 0));
+
+        let reservation = reserve(&pool, &cache, &principal, 5).await.unwrap();
+        reservation.cancel(&pool, &cache).await;
+        assert_eq!(used(&pool, &cache, &principal).await, (0, 0));
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn totals_do_not_depend_on_the_cache(pool: PgPool) {
+        let (cache, principal) = setup(&pool).await;
+        set_quota(&pool, &principal, None, Some(2), None).await;
+        reserve(&pool, &cache, &principal, 2).await.unwrap();
+
+        // As if the cache had been flushed
+        let (subject_type, subject_id) = subjects(&principal)[0];
+        cache
+            .delete(&daily_key(
+                subject_type,
+                subject_id,
+                OffsetDateTime::now_utc(),
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(used(&pool, &cache, &principal).await, (0, 2));
+        assert_matches!(
+            reserve(&pool, &cache, &principal, 1).await,
+            Err(AppError::Forbidden(_))
+        );
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn the_servers_api_key_is_not_limited(pool: PgPool) {
+        let (cache, _) = setup(&pool).await;
+        let mut reservation = reserve(&pool, &cache, &Principal::ApiKey, 1000)
+            .await
+            .unwrap();
+        reservation.release(&pool, &cache, 1000).await;
+        assert!(usage(&pool, &cache, &Principal::ApiKey)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,321 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use time::{Date, Month, OffsetDateTime};
+
+// A cron expression that matches nothing within this many days never matches
+const CRON_SEARCH_DAYS: i64 = 366 * 5;
+
+/// When a background job runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    /// Right after startup, then every interval
+    Interval(Duration),
+    Cron(CronSchedule),
+}
+
+impl Schedule {
+    /// Uses the cron expression if one is set, otherwise the interval
+    pub fn from_config(cron: Option<&str>, interval: Duration) -> Result<Self, String> {
+        match cron.map(str::trim).filter(|cron| !cron.is_empty()) {
+            Some(cron) => Ok(Schedule::Cron(cron.parse()?)),
+            None => Ok(Schedule::Interval(interval)),
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Schedule::Interval(interval) => write!(f, "every {}s", interval.as_secs()),
+            Schedule::Cron(cron) => write!(f, "cron {}", cron.expression),
+        }
+    }
+}
+
+/// A five-field cron expression (minute, hour, day of month, month, day of week) evaluated
+/// in UTC. Fields accept `*`, values, ranges, lists and `/` steps; `@hourly`, `@daily`,
+/// `@weekly`, `@monthly` and `@yearly` are also understood.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    expression: String,
+    // One bit per allowed value
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    // As in classic cron, a day matches either day field when both are restricted
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl CronSchedule {
+    /// The first matching minute after `after`
+    pub fn next_after(&self, after: OffsetDateTime) -> Option<OffsetDateTime> {
+        let after = after.to_offset(time::UtcOffset::UTC);
+        let mut next =
+            after.replace_second(0).ok()?.replace_nanosecond(0).ok()? + time::Duration::minutes(1);
+        let limit = after + time::Duration::days(CRON_SEARCH_DAYS);
+
+        while next < limit {
+            if !has_bit(self.months, next.month() as u8) {
+                let (year, month) = match next.month() {
+                    Month::December => (next.year() + 1, Month::January),
+                    month => (next.year(), month.next()),
+                };
+                next = Date::from_calendar_date(year, month, 1)
+                    .ok()?
+                    .midnight()
+                    .assume_utc();
+            } else if !self.day_matches(next.date()) {
+                next = next.date().next_day()?.midnight().assume_utc();
+            } else if !has_bit(self.hours, next.hour()) {
+                next = next.replace_minute(0).ok()? + time::Duration::hours(1);
+            } else if !has_bit(self.minutes, next.minute()) {
+                next += time::Duration::minutes(1);
+            } else {
+                return Some(next);
+            }
+        }
+
+        None
+    }
+
+    fn day_matches(&self, date: Date) -> bool {
+        let day_of_month = has_bit(self.days_of_month, date.day());
+        let day_of_week = has_bit(self.days_of_week, date.weekday().number_days_from_sunday());
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (true, true) => true,
+            (true, false) => day_of_week,
+            (false, true) => day_of_month,
+            (false, false) => day_of_month || day_of_week,
+        }
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expression = s.trim();
+        let expanded = match expression {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day_of_month, month, day_of_week] = fields.as_slice() else {
+            return Err(format!(
+                "Invalid cron expression: {} (expected 5 fields)",
+                expression
+            ));
+        };
+
+        let invalid = |err: String| format!("Invalid cron expression: {} ({})", expression, err);
+        // Both 0 and 7 mean Sunday
+        let days_of_week = parse_field(day_of_week, 0, 7).map_err(invalid)?;
+
+        Ok(Self {
+            expression: expression.to_string(),
+            minutes: parse_field(minute, 0, 59).map_err(invalid)?,
+            hours: parse_field(hour, 0, 23).map_err(invalid)?,
+            days_of_month: parse_field(day_of_month, 1, 31).map_err(invalid)?,
+            months: parse_field(month, 1, 12).map_err(invalid)?,
+            days_of_week: (days_of_week | days_of_week >> 7) & 0x7f,
+            any_day_of_month: *day_of_month == "*",
+            any_day_of_week: *day_of_week == "*",
+        })
+    }
+}
+
+/// Parses one field into a bit mask of the allowed values
+fn parse_field(field: &str, min: u8, max: u8) -> Result<u64, String> {
+    let parse_value = |value: &str| {
+        value
+            .parse::<u8>()
+            .map_err(|_| format!("{} is not a number", value))
+    };
+
+    let mut mask = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<usize>() {
+                Ok(step) if step > 0 => (range, Some(step)),
+                _ => return Err(format!("invalid step in {}", part)),
+            },
+            None => (part, None),
+        };
+
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (parse_value(start)?, parse_value(end)?),
+            // A single value with a step runs from that value to the end of the range
+            None if step.is_some() => (parse_value(range)?, max),
+            None => {
+                let value = parse_value(range)?;
+                (value, value)
+            }
+        };
+        if start < min || end > max || start > end {
+            return Err(format!("{} is outside {}-{}", part, min, max));
+        }
+
+        for value in (start..=end).step_by(step.unwrap_or(1)) {
+            mask |= 1 << value;
+        }
+    }
+
+    Ok(mask)
+}
+
+fn has_bit(mask: u64, value: u8) -> bool {
+    mask & (1 << value) != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    fn next(expression: &str, after: OffsetDateTime) -> Option<OffsetDateTime> {
+        expression
+            .parse::<CronSchedule>()
+            .unwrap()
+            .next_after(after)
+    }
+
+    #[test]
+    fn steps_ranges_and_lists_select_their_values() {
+        let cron: CronSchedule = "*/15 9-17/4 * * 1,3,5".parse().unwrap();
+        assert_eq!(cron.minutes, 1 << 0 | 1 << 15 | 1 << 30 | 1 << 45);
+        assert_eq!(cron.hours, 1 << 9 | 1 << 13 | 1 << 17);
+        assert_eq!(cron.days_of_week, 1 << 1 | 1 << 3 | 1 << 5);
+
+        // Monday evening runs last at 17:45, then on Wednesday morning
+        let monday = datetime!(2026-10-19 17:40 UTC);
+        assert_eq!(
+            cron.next_after(monday),
+            Some(datetime!(2026-10-19 17:45 UTC))
+        );
+        assert_eq!(
+            cron.next_after(datetime!(2026-10-19 17:45 UTC)),
+            Some(datetime!(2026-10-21 09:00 UTC))
+        );
+        // A single value with a step runs to the end of the range
+        assert_eq!(parse_field("50/5", 0, 59), Ok(1 << 50 | 1 << 55));
+    }
+
+    #[test]
+    fn seven_and_zero_both_mean_sunday() {
+        let sunday = Some(datetime!(2026-10-25 12:00 UTC));
+        assert_eq!(next("0 12 * * 7", datetime!(2026-10-19 00:00 UTC)), sunday);
+        assert_eq!(next("0 12 * * 0", datetime!(2026-10-19 00:00 UTC)), sunday);
+
+        let weekend: CronSchedule = "0 0 * * 6-7".parse().unwrap();
+        assert_eq!(weekend.days_of_week, 1 << 0 | 1 << 6);
+    }
+
+    #[test]
+    fn restricted_day_fields_match_either_day() {
+        // The 13th or any Monday
+        let after = datetime!(2026-11-09 00:00 UTC);
+        assert_eq!(
+            next("0 0 13 * 1", after),
+            Some(datetime!(2026-11-13 00:00 UTC))
+        );
+        assert_eq!(
+            next("0 0 13 * 1", datetime!(2026-11-13 00:00 UTC)),
+            Some(datetime!(2026-11-16 00:00 UTC))
+        );
+
+        // An unrestricted day field does not widen the other one
+        assert_eq!(
+            next("0 0 13 * *", after),
+            Some(datetime!(2026-11-13 00:00 UTC))
+        );
+        assert_eq!(
+            next("0 0 * * 1", after),
+            Some(datetime!(2026-11-16 00:00 UTC))
+        );
+    }
+
+    #[test]
+    fn aliases_expand_to_their_expressions() {
+        let after = datetime!(2026-10-19 10:30 UTC);
+        assert_eq!(
+            next("@hourly", after),
+            Some(datetime!(2026-10-19 11:00 UTC))
+        );
+        assert_eq!(next("@daily", after), Some(datetime!(2026-10-20 00:00 UTC)));
+        assert_eq!(next("@midnight", after), next("@daily", after));
+        assert_eq!(
+            next("@weekly", after),
+            Some(datetime!(2026-10-25 00:00 UTC))
+        );
+        assert_eq!(
+            next("@monthly", after),
+            Some(datetime!(2026-11-01 00:00 UTC))
+        );
+        assert_eq!(
+            next("@yearly", after),
+            Some(datetime!(2027-01-01 00:00 UTC))
+        );
+        assert_eq!(next("@annually", after), next("@yearly", after));
+    }
+
+    #[test]
+    fn searches_roll_over_months_and_years() {
+        assert_eq!(
+            next("0 0 1 * *", datetime!(2026-12-15 08:00 UTC)),
+            Some(datetime!(2027-01-01 00:00 UTC))
+        );
+        assert_eq!(
+            next("30 23 31 12 *", datetime!(2026-12-31 23:30 UTC)),
+            Some(datetime!(2027-12-31 23:30 UTC))
+        );
+        assert_eq!(
+            next("59 23 * * *", datetime!(2026-10-31 23:59 UTC)),
+            Some(datetime!(2026-11-01 23:59 UTC))
+        );
+        assert_eq!(
+            next("0 0 29 2 *", datetime!(2026-10-19 00:00 UTC)),
+            Some(datetime!(2028-02-29 00:00 UTC))
+        );
+    }
+
+    #[test]
+    fn impossible_dates_never_run() {
+        assert_eq!(next("0 0 31 2 *", datetime!(2026-10-19 00:00 UTC)), None);
+    }
+
+    #[test]
+    fn invalid_expressions_are_rejected() {
+        for expression in [
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * 32 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "0/x * * * *",
+            "30-10 * * * *",
+            "a * * * *",
+            "* * * *",
+            "* * * * * *",
+            "@reboot",
+        ] {
+            assert!(
+                expression.parse::<CronSchedule>().is_err(),
+                "{} was accepted",
+                expression
+            );
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::future::BoxFuture;
+use sha2::{Digest, Sha256};
+use sqlx::{Connection, PgConnection, PgPool};
+use time::OffsetDateTime;
+use tokio::{
+    sync::{watch, Mutex},
+    task::JoinSet,
+};
+
+use crate::{
+    application::schedule::Schedule, domain::job::JobOutcome, error::Result,
+    infrastructure::database,
+};
+
+// Runs started this close to the end of the previous interval still count as due, so a
+// timer firing slightly early does not skip a whole interval
+const INTERVAL_SLACK: Duration = Duration::from_secs(1);
+
+/// A unit of background work run on a schedule
+pub trait Job: Send + Sync + 'static {
+    /// Unique name, shared by all instances running the job
+    fn name(&self) -> &'static str;
+
+    /// Runs the job once, returning a short summary of what it did
+    fn run(&self) -> BoxFuture<'_, Result<String>>;
+}
+
+/// Runs jobs on their schedules. Each run takes a Postgres advisory lock named after the
+/// job and claims the run in `scheduled_jobs`, so only one instance runs each job at a time
+/// and each scheduled run happens once across all instances.
+pub struct Scheduler {
+    db: PgPool,
+    locks: Arc<JobLocks>,
+    instance: String,
+    jobs: Vec<(Arc<dyn Job>, Schedule)>,
+    shutdown: watch::Sender<bool>,
+    tasks: JoinSet<()>,
+}
+
+impl Scheduler {
+    pub fn new(db: PgPool, database_url: &str) -> Self {
+        let instance = format!(
+            "{}-{}",
+            std::env::var("HOSTNAME").unwrap_or_else(|_| "localhost".to_string()),
+            std::process::id()
+        );
+
+        Self {
+            db,
+            locks: Arc::new(JobLocks {
+                database_url: database_url.to_string(),
+                conn: Mutex::new(None),
+            }),
+            instance,
+            jobs: Vec::new(),
+            shutdown: watch::channel(false).0,
+            tasks: JoinSet::new(),
+        }
+    }
+
+    pub fn add(&mut self, job: impl Job, schedule: Schedule) {
+        self.jobs.push((Arc::new(job), schedule));
+    }
+
+    /// Registers the jobs in the shared job list and starts running them
+    pub async fn start(&mut self) -> Result<()> {
+        for (job, schedule) in std::mem::take(&mut self.jobs) {
+            database::register_job(&self.db, job.name(), &schedule.to_string()).await?;
+            tracing::info!("Scheduled job {} to run {}", job.name(), schedule);
+
+            let runner = JobRunner {
+                db: self.db.clone(),
+                locks: self.locks.clone(),
+                instance: self.instance.clone(),
+                lock_key: lock_key(job.name()),
+                job,
+                schedule,
+            };
+            self.tasks.spawn(runner.run(self.shutdown.subscribe()));
+        }
+
+        Ok(())
+    }
+
+    /// Cancels running jobs and waits for them to record their outcome, up to `timeout`
+    pub async fn shutdown(mut self, timeout: Duration) {
+        let _ = self.shutdown.send(true);
+
+        let stopped = tokio::time::timeout(timeout, async {
+            while self.tasks.join_next().await.is_some() {}
+        })
+        .await;
+        if stopped.is_err() {
+            tracing::warn!("Background jobs did not stop within {:?}", timeout);
+        }
+    }
+}
+
+struct JobRunner {
+    db: PgPool,
+    locks: Arc<JobLocks>,
+    instance: String,
+    lock_key: i64,
+    job: Arc<dyn Job>,
+    schedule: Schedule,
+}
+
+impl JobRunner {
+    async fn run(self, mut shutdown: watch::Receiver<bool>) {
+        let mut next_run = OffsetDateTime::now_utc();
+
+        loop {
+            let scheduled_at = match self.schedule {
+                Schedule::Interval(_) => next_run,
+                Schedule::Cron(ref cron) => match cron.next_after(OffsetDateTime::now_utc()) {
+                    Some(scheduled_at) => scheduled_at,
+                    None => {
+                        tracing::warn!("Job {} will never run again", self.job.name());
+                        return;
+                    }
+                },
+            };
+
+            let wait = Duration::try_from(scheduled_at - OffsetDateTime::now_utc())
+                .unwrap_or(Duration::ZERO);
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = shutdown.changed() => return,
+            }
+
+            // A run is due unless another instance already started it
+            let due_before = match self.schedule {
+                Schedule::Interval(interval) => {
+                    next_run = scheduled_at + interval;
+                    // Like a missed tick, a run that overran its interval delays the next one
+                    next_run = next_run.max(OffsetDateTime::now_utc());
+                    scheduled_at - interval + INTERVAL_SLACK
+                }
+                Schedule::Cron(_) => scheduled_at,
+            };
+
+            match self.run_once(due_before, &mut shutdown).await {
+                Ok(true) => return,
+                Ok(false) => {}
+                Err(err) => tracing::error!("Failed to run job {}: {}", self.job.name(), err),
+            }
+        }
+    }
+
+    /// Runs the job if this instance wins it, returning `true` if the job was cancelled
+    async fn run_once(
+        &self,
+        due_before: OffsetDateTime,
+        shutdown: &mut watch::Receiver<bool>,
+    ) -> Result<bool> {
+        let name = self.job.name();
+        if !self.locks.try_lock(self.lock_key).await? {
+            tracing::debug!("Job {} is running on another instance", name);
+            return Ok(false);
+        }
+
+        let result = self.run_claimed(due_before, shutdown).await;
+        self.locks.unlock(self.lock_key).await;
+        result
+    }
+
+    async fn run_claimed(
+        &self,
+        due_before: OffsetDateTime,
+        shutdown: &mut watch::Receiver<bool>,
+    ) -> Result<bool> {
+        let name = self.job.name();
+        if !database::claim_job_run(&self.db, name, due_before, &self.instance).await? {
+            tracing::debug!("Job {} already ran on another instance", name);
+            return Ok(false);
+        }
+
+        tracing::debug!("Starting job {}", name);
+        let started = Instant::now();
+        let (outcome, message) = tokio::select! {
+            result = self.job.run() => match result {
+                Ok(summary) => {
+                    tracing::info!("Job {} finished in {:?}: {}", name, started.elapsed(), summary);
+                    (JobOutcome::Succeeded, summary)
+                }
+                Err(err) => {
+                    tracing::error!("Job {} failed after {:?}: {}", name, started.elapsed(), err);
+                    (JobOutcome::Failed, err.to_string())
+                }
+            },
+            _ = shutdown.changed() => {
+                tracing::info!("Job {} cancelled by shutdown", name);
+                (JobOutcome::Cancelled, "Cancelled by shutdown".to_string())
+            }
+        };
+
+        database::finish_job_run(
+            &self.db,
+            name,
+            outcome,
+            &message,
+            started.elapsed().as_millis() as i64,
+        )
+        .await?;
+
+        Ok(outcome == JobOutcome::Cancelled)
+    }
+}
+
+/// Advisory locks held on one dedicated connection, outside the pool used by the jobs.
+/// If the instance dies, Postgres drops the connection and with it every lock it held.
+struct JobLocks {
+    database_url: String,
+    conn: Mutex<Option<PgConnection>>,
+}
+
+impl JobLocks {
+    async fn try_lock(&self, key: i64) -> Result<bool> {
+        let mut conn = self.conn.lock().await;
+        let conn_ref = match *conn {
+            Some(ref mut conn_ref) => conn_ref,
+            None => conn.insert(PgConnection::connect(&self.database_url).await?),
+        };
+
+        let result = database::try_advisory_lock(conn_ref, key).await;
+        if result.is_err() {
+            // Reconnect on the next attempt
+            *conn = None;
+        }
+        result
+    }
+
+    async fn unlock(&self, key: i64) {
+        let mut conn = self.conn.lock().await;
+        if let Some(ref mut conn_ref) = *conn {
+            if let Err(err) = database::advisory_unlock(conn_ref, key).await {
+                // Dropping the connection releases the lock anyway
+                tracing::warn!("Failed to release job lock: {}", err);
+                *conn = None;
+            }
+        }
+    }
+}
+
+/// Advisory lock keys are numbers, so job names are hashed into one
+fn lock_key(name: &str) -> i64 {
+    let digest = Sha256::digest(format!("scheduled-job:{}", name));
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    i64::from_be_bytes(bytes)
+}
@@ -0,0 +1,100 @@
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use sqlx::PgPool;
+
+use crate::{application::scheduler::Job, error::Result, infrastructure::database};
+
+// Links are deleted in batches so a large trash does not hold long locks
+const PURGE_BATCH_SIZE: i64 = 1000;
+
+/// Deletes links that have been in the trash longer than the retention period
+pub struct TrashPurger {
+    db: PgPool,
+    retention: Duration,
+    // Purged short codes are quarantined like those of expired links
+    code_reuse_after: Option<Duration>,
+}
+
+impl TrashPurger {
+    pub fn new(db: PgPool, retention: Duration, code_reuse_after: Option<Duration>) -> Self {
+        Self {
+            db,
+            retention,
+            code_reuse_after,
+        }
+    }
+
+    /// Deletes every link that was moved to the trash longer than the retention period ago,
+    /// returning how many were deleted
+    pub async fn purge(&self) -> Result<usize> {
+        let mut purged = 0;
+        loop {
+            let short_codes = database::purge_deleted_urls(
+                &self.db,
+                self.retention.as_secs() as i64,
+                PURGE_BATCH_SIZE,
+                self.code_reuse_after.map(|after| after.as_secs() as i64),
+            )
+            .await?;
+            purged += short_codes.len();
+            if !short_codes.is_empty() {
+                tracing::debug!("Purged deleted links: {}", short_codes.join(", "));
+            }
+
+            if (short_codes.len() as i64) < PURGE_BATCH_SIZE {
+                return Ok(purged);
+            }
+        }
+    }
+}
+
+impl Job for TrashPurger {
+    fn name(&self) -> &'static str {
+        "trash-purge"
+    }
+
+    fn run(&self) -> BoxFuture<'_, Result<String>> {
+        Box::pin(async move {
+            let purged = self.purge().await?;
+            Ok(format!("Purged {} link(s) from the trash", purged))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[sqlx::test(migrations = false)]
+    async fn purged_links_retire_their_short_codes(pool: PgPool) {
+        crate::infrastructure::migrations::run_migrations_if_needed(&pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            r#"
+            INSERT INTO urls (original_url, short_code, deleted_at) VALUES
+                ('https://example.com/old', 'old', CURRENT_TIMESTAMP - INTERVAL '40 days'),
+                ('https://example.com/recent', 'recent', CURRENT_TIMESTAMP - INTERVAL '1 day')
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let purger = TrashPurger::new(
+            pool.clone(),
+            Duration::from_secs(30 * 24 * 60 * 60),
+            Some(Duration::from_secs(24 * 60 * 60)),
+        );
+        assert_eq!(purger.purge().await.unwrap(), 1);
+
+        let retired: Vec<(String, bool)> = sqlx::query_as(
+            "SELECT short_code, reusable_at > CURRENT_TIMESTAMP FROM retired_short_codes",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(retired, [("old".to_string(), true)]);
+    }
+}
@@ -1,39 +1,1112 @@
+use ipnet::IpNet;
 use nanoid::nanoid;
 use time::Duration;
 
 use crate::{
-    config::AppConfig,
-    domain::url::{CreateUrlRequest, CreateUrlResponse, StatsResponse},
+    application::{
+        accounts,
+        audit::{self, diff},
+        backup,
+        destination_metadata::MetadataJob,
+        expired_cleanup::CleanupMetrics,
+        jwt_auth::JwtVerifier,
+        link_export,
+        link_import::{self, ImportRow},
+        link_signing::LinkSigner,
+        qr_code::QrOptions,
+        quota,
+    },
+    config::{AppConfig, AuthMode},
+    domain::{
+        audit::{AuditEventPage, AuditQuery, NewAuditEvent},
+        collection::{Collection, CreateCollectionRequest, UpdateCollectionRequest},
+        job::JobStatus,
+        maintenance::ExpiredCleanupStats,
+        quota::{Quota, QuotaSubject, QuotaUsage, SetQuotaRequest},
+        revision::{RevisionChange, UrlRevision},
+        signed_link::{LinkSignature, SignLinkRequest, SignedLink},
+        transfer::{
+            ConflictPolicy, ExportFormat, ImportError, ImportFormat, ImportJob, ImportStatus,
+            RestoreSummary,
+        },
+        url::{
+            BatchCreateUrlRequest, BatchCreateUrlResponse, BatchItemResult, BatchMode,
+            CreateUrlRequest, CreateUrlResponse, PageQuery, RedirectTarget, StatsQuery,
+            StatsResponse, UpdateUrlRequest, Url, UrlListQuery, UrlListResponse, VisitKind,
+        },
+        user::{CreateUserRequest, LoginRequest, LoginResponse, Principal, User},
+        workspace::{
+            AcceptInvitationRequest, CreateApiKeyRequest, CreateInvitationRequest,
+            CreateWorkspaceRequest, Invitation, UpdateMemberRequest, Workspace, WorkspaceAccess,
+            WorkspaceApiKey, WorkspaceMember, WorkspaceRole,
+        },
+    },
     error::{AppError, Result},
-    infrastructure::{cache::Cache, database},
+    infrastructure::{
+        cache::Cache,
+        database::{self, NewUrl, UrlChanges, UrlFilter, UrlOwner},
+    },
 };
-use sqlx::PgPool;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::{Connection, PgConnection, PgPool};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+const MAX_BATCH_SIZE: usize = 5000;
+// Imported rows are created in batches of this size, with progress recorded after each
+const IMPORT_CHUNK_SIZE: usize = 500;
+const MIN_ALIAS_LENGTH: usize = 3;
+const MAX_ALIAS_LENGTH: usize = 64;
+// Aliases share the path namespace with the rest of the API, so those prefixes are reserved
+const RESERVED_ALIASES: &[&str] = &["api", "api-docs", "health", "swagger-ui"];
+const MAX_TAGS_PER_URL: usize = 20;
+const MAX_TAG_LENGTH: usize = 50;
+const MAX_NAME_LENGTH: usize = 100;
+const MAX_TITLE_LENGTH: usize = 200;
+const MAX_DESCRIPTION_LENGTH: usize = 2000;
+const MAX_METADATA_BYTES: usize = 16 * 1024;
+const MAX_SIGNED_LINKS: usize = 1000;
+const MAX_RECIPIENT_LENGTH: usize = 200;
+
+// Rendered QR codes only depend on the short code and options, so they can live longer
+const QR_CACHE_TTL_SECS: u64 = 86400;
 
 #[derive(Clone)]
 pub struct UrlService {
     config: AppConfig,
     db: PgPool,
     cache: Cache,
+    metadata_jobs: Option<mpsc::Sender<MetadataJob>>,
+    cleanup_metrics: CleanupMetrics,
+    jwt_verifier: Option<Arc<JwtVerifier>>,
+    link_signer: Arc<LinkSigner>,
 }
 
 impl UrlService {
-    pub fn new(config: AppConfig, db: PgPool, cache: Cache) -> Self {
-        Self { config, db, cache }
+    pub fn new(
+        config: AppConfig,
+        db: PgPool,
+        cache: Cache,
+        metadata_jobs: Option<mpsc::Sender<MetadataJob>>,
+    ) -> Self {
+        Self {
+            cleanup_metrics: CleanupMetrics::from_config(&config),
+            jwt_verifier: JwtVerifier::from_config(&config).map(Arc::new),
+            link_signer: Arc::new(LinkSigner::from_config(&config)),
+            config,
+            db,
+            cache,
+            metadata_jobs,
+        }
+    }
+
+    /// The server's API key, unless an identity provider authenticates clients instead
+    pub fn get_api_key(&self) -> Option<&str> {
+        match self.config.auth_mode {
+            AuthMode::ApiKey => Some(&self.config.api_key),
+            AuthMode::Jwt => None,
+        }
+    }
+
+    /// Whether bearer tokens can be JWTs of the identity provider
+    pub fn jwt_enabled(&self) -> bool {
+        self.jwt_verifier.is_some()
     }
 
-    pub fn get_api_key(&self) -> &str {
-        &self.config.api_key
+    /// Loads the identity provider's signing keys ahead of the first request
+    pub async fn load_jwt_keys(&self) -> Result<()> {
+        match &self.jwt_verifier {
+            Some(verifier) => verifier.load_keys().await,
+            None => Ok(()),
+        }
+    }
+
+    /// Proxies whose forwarding headers give the client's address
+    pub fn trusted_proxies(&self) -> &[IpNet] {
+        &self.config.trusted_proxies
+    }
+
+    /// Client addresses the admin API accepts; empty allows any
+    pub fn admin_allowed_ips(&self) -> &[IpNet] {
+        &self.config.admin_allowed_ips
     }
 
     pub fn get_environment(&self) -> &crate::config::Environment {
         &self.config.environment
     }
 
-    pub async fn create_short_url(&self, request: CreateUrlRequest) -> Result<CreateUrlResponse> {
-        let short_code = nanoid!(8);
+    pub fn get_cache(&self) -> &Cache {
+        &self.cache
+    }
+
+    pub fn idempotency_ttl_secs(&self) -> u64 {
+        self.config.idempotency_ttl_secs
+    }
+
+    /// Totals the expired-link cleanup reports to
+    pub fn cleanup_metrics(&self) -> CleanupMetrics {
+        self.cleanup_metrics.clone()
+    }
+
+    pub async fn get_expired_cleanup_stats(
+        &self,
+        principal: &Principal,
+    ) -> Result<ExpiredCleanupStats> {
+        require_admin(principal)?;
+        self.audit(audit::event(principal, "expired_cleanup.read"))
+            .await?;
+        Ok(self.cleanup_metrics.snapshot())
+    }
+
+    /// Lists the background jobs of all instances with their latest run
+    pub async fn list_jobs(&self, principal: &Principal) -> Result<Vec<JobStatus>> {
+        require_admin(principal)?;
+        self.audit(audit::event(principal, "job.list")).await?;
+        database::list_scheduled_jobs(&self.db).await
+    }
+
+    /// Lists audit events matching the query, newest first
+    pub async fn list_audit_events(
+        &self,
+        principal: &Principal,
+        query: &AuditQuery,
+    ) -> Result<AuditEventPage> {
+        require_admin(principal)?;
+        self.audit(audit::event(principal, "audit.list")).await?;
+
+        let limit = query.limit();
+        let events = database::list_audit_events(&self.db, query, query.before, limit).await?;
+        let next_before = match events.last() {
+            Some(last) if events.len() as i64 == limit => Some(last.id),
+            _ => None,
+        };
+
+        Ok(AuditEventPage {
+            events,
+            next_before,
+        })
+    }
+
+    /// Streams every audit event matching the query as JSON lines, newest first
+    pub async fn export_audit_events(
+        &self,
+        principal: &Principal,
+        query: AuditQuery,
+    ) -> Result<impl futures::Stream<Item = Result<axum::body::Bytes>> + Send + 'static> {
+        require_admin(principal)?;
+        self.audit(audit::event(principal, "audit.export")).await?;
+        Ok(audit::export_events(self.db.clone(), query))
+    }
+
+    /// Sets the link quota of an account, workspace or workspace API key
+    pub async fn set_quota(
+        &self,
+        principal: &Principal,
+        subject_type: QuotaSubject,
+        subject_id: Uuid,
+        request: SetQuotaRequest,
+    ) -> Result<Quota> {
+        require_admin(principal)?;
+        let limits = [
+            request.daily_links,
+            request.total_links,
+            request.active_links,
+        ];
+        if limits.iter().flatten().any(|limit| *limit < 0) {
+            return Err(AppError::BadRequest(
+                "Quota limits cannot be negative".to_string(),
+            ));
+        }
+        if subject_type == QuotaSubject::ApiKey && request.active_links.is_some() {
+            return Err(AppError::BadRequest(
+                "API keys hold no links of their own; limit active links on their workspace"
+                    .to_string(),
+            ));
+        }
+        if !database::quota_subject_exists(&self.db, subject_type, subject_id).await? {
+            return Err(AppError::NotFound(format!(
+                "No {} with ID {}",
+                subject_type.as_str(),
+                subject_id
+            )));
+        }
+
+        let before = database::get_quota(&self.db, subject_type, subject_id).await?;
+        let quota = database::set_quota(
+            &self.db,
+            subject_type,
+            subject_id,
+            request.daily_links,
+            request.total_links,
+            request.active_links,
+        )
+        .await?;
+        self.audit(
+            audit::event(principal, "quota.set")
+                .target(format!("{}:{}", subject_type.as_str(), subject_id))
+                .changes(diff(before.as_ref(), Some(&quota))),
+        )
+        .await?;
+
+        Ok(quota)
+    }
+
+    /// Removes the link quota of a subject, leaving it unlimited
+    pub async fn delete_quota(
+        &self,
+        principal: &Principal,
+        subject_type: QuotaSubject,
+        subject_id: Uuid,
+    ) -> Result<()> {
+        require_admin(principal)?;
+        if !database::delete_quota(&self.db, subject_type, subject_id).await? {
+            return Err(AppError::NotFound(format!(
+                "No quota for {} {}",
+                subject_type.as_str(),
+                subject_id
+            )));
+        }
+        self.audit(audit::event(principal, "quota.delete").target(format!(
+            "{}:{}",
+            subject_type.as_str(),
+            subject_id
+        )))
+        .await
+    }
+
+    pub async fn list_quotas(&self, principal: &Principal, page: &PageQuery) -> Result<Vec<Quota>> {
+        require_admin(principal)?;
+        self.audit(audit::event(principal, "quota.list")).await?;
+        database::list_quotas(&self.db, page.limit(), page.offset()).await
+    }
+
+    /// How much of the quotas that apply to the caller's new links is used
+    pub async fn get_usage(&self, principal: &Principal) -> Result<Vec<QuotaUsage>> {
+        self.audit(audit::event(principal, "quota.usage")).await?;
+        quota::usage(&self.db, &self.cache, principal).await
+    }
+
+    /// Appends an event to the audit log, outside of any transaction
+    async fn audit(&self, event: NewAuditEvent) -> Result<()> {
+        database::insert_audit_event(&self.db, &event).await
+    }
+
+    /// Creates an account. Admins can create any account; anyone else only a regular one,
+    /// and only while sign-up is open.
+    pub async fn create_user(
+        &self,
+        principal: &Principal,
+        request: CreateUserRequest,
+    ) -> Result<User> {
+        if !principal.is_admin() {
+            if request.is_admin {
+                return Err(AppError::Forbidden(
+                    "Only admins can create admin accounts".to_string(),
+                ));
+            }
+            if !self.config.allow_signup {
+                return Err(AppError::Forbidden("Sign-up is disabled".to_string()));
+            }
+        }
+
+        let email = accounts::normalize_email(&request.email)?;
+        let password_hash = accounts::hash_password(&request.password)?;
+        let user = database::create_user(&self.db, &email, &password_hash, request.is_admin)
+            .await
+            .map_err(|err| {
+                conflict_on_unique_violation(err, "An account with this email already exists")
+            })?;
+        tracing::info!("Created account {} ({})", user.id, user.email);
+        self.audit(
+            audit::event(principal, "user.create")
+                .target(user.id)
+                .changes(diff(None, Some(&user))),
+        )
+        .await?;
+
+        Ok(user)
+    }
+
+    pub async fn list_users(&self, principal: &Principal, page: &PageQuery) -> Result<Vec<User>> {
+        require_admin(principal)?;
+        self.audit(audit::event(principal, "user.list")).await?;
+        database::list_users(&self.db, page.limit(), page.offset()).await
+    }
+
+    /// Checks an email and password and starts a session
+    pub async fn login(&self, request: LoginRequest) -> Result<LoginResponse> {
+        let user = match accounts::normalize_email(&request.email) {
+            Ok(email) => database::get_user_credentials(&self.db, &email).await?,
+            Err(_) => None,
+        };
+        let user = match user {
+            Some((user, Some(hash))) if accounts::verify_password(&request.password, &hash) => user,
+            _ => {
+                tracing::warn!("Failed login attempt");
+                return Err(AppError::Unauthorized);
+            }
+        };
+
+        let token = accounts::new_secret_token();
+        let expires_at =
+            time::OffsetDateTime::now_utc() + Duration::hours(self.config.session_ttl_hours as i64);
+        database::create_session(
+            &self.db,
+            &accounts::hash_secret_token(&token),
+            user.id,
+            expires_at,
+        )
+        .await?;
+        self.audit(audit::event(&Principal::User(user.clone()), "user.login").target(user.id))
+            .await?;
+
+        Ok(LoginResponse {
+            token,
+            expires_at,
+            user,
+        })
+    }
+
+    pub async fn logout(&self, principal: &Principal, token: &str) -> Result<()> {
+        database::delete_session(&self.db, &accounts::hash_secret_token(token)).await?;
+        let event = audit::event(principal, "user.logout");
+        self.audit(match principal.user_id() {
+            Some(user_id) => event.target(user_id),
+            None => event,
+        })
+        .await
+    }
+
+    /// The account a session token belongs to, if the session is still valid
+    pub async fn authenticate_session(&self, token: &str) -> Result<Option<User>> {
+        database::get_session_user(&self.db, &accounts::hash_secret_token(token)).await
+    }
+
+    /// The account of a valid JWT bearer token. Accounts are created on first sign-in, or
+    /// linked to the existing account with the token's email if the identity provider has
+    /// verified it, and follow later changes to the email and admin role in the token.
+    pub async fn authenticate_jwt(&self, token: &str) -> Result<Option<User>> {
+        let Some(verifier) = &self.jwt_verifier else {
+            return Ok(None);
+        };
+        let Some(identity) = verifier.verify(token).await? else {
+            return Ok(None);
+        };
+        let Ok(email) = accounts::normalize_email(&identity.email) else {
+            tracing::warn!("JWT of {} has an invalid email", identity.subject);
+            return Ok(None);
+        };
+
+        match database::get_external_user(&self.db, &identity.subject).await? {
+            Some(user) if user.email == email && user.is_admin == identity.is_admin => {
+                Ok(Some(user))
+            }
+            Some(user) => {
+                database::update_external_user(&self.db, user.id, &email, identity.is_admin)
+                    .await
+                    .map(Some)
+                    .map_err(|err| {
+                        conflict_on_unique_violation(
+                            err,
+                            "An account with this email already exists",
+                        )
+                    })
+            }
+            None => {
+                let user = database::link_external_user(
+                    &self.db,
+                    &identity.subject,
+                    &email,
+                    identity.email_verified,
+                    identity.is_admin,
+                )
+                .await?;
+                match &user {
+                    Some(user) => tracing::info!(
+                        "Account {} ({}) signed in through the identity provider",
+                        user.id,
+                        user.email
+                    ),
+                    // The account belongs to another subject, or the email is not verified
+                    None => tracing::warn!(
+                        "JWT of {} names the email of an account it cannot be linked to",
+                        identity.subject
+                    ),
+                }
+                Ok(user)
+            }
+        }
+    }
+
+    /// The workspace API key's principal, if the key is active
+    pub async fn authenticate_workspace_key(&self, key: &str) -> Result<Option<Principal>> {
+        let access =
+            database::use_workspace_api_key(&self.db, &accounts::hash_secret_token(key)).await?;
+        Ok(access.map(|(key_id, access)| Principal::WorkspaceKey { key_id, access }))
+    }
+
+    /// An account's access to a workspace it acts in, if it is a member
+    pub async fn get_workspace_access(
+        &self,
+        user: &User,
+        workspace_id: Uuid,
+    ) -> Result<Option<WorkspaceAccess>> {
+        let role = database::get_member_role(&self.db, workspace_id, user.id).await?;
+        Ok(role.map(|role| WorkspaceAccess { workspace_id, role }))
+    }
+
+    /// Creates a workspace owned by the account creating it
+    pub async fn create_workspace(
+        &self,
+        principal: &Principal,
+        request: CreateWorkspaceRequest,
+    ) -> Result<Workspace> {
+        let Some(user_id) = principal.user_id() else {
+            return Err(AppError::Forbidden(
+                "Workspaces can only be created by accounts".to_string(),
+            ));
+        };
+        let name = validate_name(&request.name, "Workspace")?;
+
+        let mut tx = self.db.begin().await?;
+        let workspace = database::create_workspace(&mut tx, name, user_id).await?;
+        database::insert_audit_event(
+            &mut *tx,
+            &audit::event(principal, "workspace.create")
+                .target(workspace.id)
+                .workspace(workspace.id)
+                .changes(diff(None, Some(&workspace))),
+        )
+        .await?;
+        tx.commit().await?;
+        tracing::info!("Created workspace {} ({})", workspace.id, workspace.name);
+
+        Ok(workspace)
+    }
+
+    /// Lists the workspaces the principal belongs to, or every workspace for admins
+    pub async fn list_workspaces(&self, principal: &Principal) -> Result<Vec<Workspace>> {
+        let all = principal.is_admin() || matches!(principal, Principal::WorkspaceKey { .. });
+        self.audit(audit::event(principal, "workspace.list"))
+            .await?;
+        let workspaces = database::list_workspaces(&self.db, principal.user_id(), all).await?;
+
+        // A workspace API key only sees its own workspace
+        Ok(match principal {
+            Principal::WorkspaceKey { access, .. } => workspaces
+                .into_iter()
+                .filter(|workspace| workspace.id == access.workspace_id)
+                .map(|workspace| Workspace {
+                    role: Some(access.role),
+                    ..workspace
+                })
+                .collect(),
+            _ => workspaces,
+        })
+    }
+
+    pub async fn list_members(
+        &self,
+        principal: &Principal,
+        workspace_id: Uuid,
+    ) -> Result<Vec<WorkspaceMember>> {
+        self.workspace_role(principal, workspace_id).await?;
+        self.audit(audit::event(principal, "member.list").workspace(workspace_id))
+            .await?;
+        database::list_members(&self.db, workspace_id).await
+    }
+
+    /// Changes the role of a member. Admins can manage members up to their own role, and a
+    /// workspace always keeps at least one owner.
+    pub async fn update_member(
+        &self,
+        principal: &Principal,
+        workspace_id: Uuid,
+        user_id: Uuid,
+        request: UpdateMemberRequest,
+    ) -> Result<Vec<WorkspaceMember>> {
+        let role = self.workspace_role(principal, workspace_id).await?;
+        check_role(role, WorkspaceRole::Admin)?;
+        if request.role > role {
+            return Err(AppError::Forbidden(
+                "You cannot grant a role higher than your own".to_string(),
+            ));
+        }
+
+        let mut tx = self.db.begin().await?;
+        database::lock_workspace(&mut tx, workspace_id).await?;
+        let current = database::get_member_role(&mut *tx, workspace_id, user_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Member not found".to_string()))?;
+        if current > role {
+            return Err(AppError::Forbidden(
+                "You cannot change the role of a member above you".to_string(),
+            ));
+        }
+        if current == WorkspaceRole::Owner && request.role != WorkspaceRole::Owner {
+            ensure_other_owner(&mut tx, workspace_id).await?;
+        }
+        database::set_member_role(&mut tx, workspace_id, user_id, request.role).await?;
+        database::insert_audit_event(
+            &mut *tx,
+            &audit::event(principal, "member.update")
+                .target(user_id)
+                .workspace(workspace_id)
+                .changes(diff(
+                    Some(&json!({ "role": current })),
+                    Some(&json!({ "role": request.role })),
+                )),
+        )
+        .await?;
+        tx.commit().await?;
+
+        database::list_members(&self.db, workspace_id).await
+    }
+
+    /// Removes a member from a workspace. Anyone can leave; removing others takes the admin
+    /// role and a role at least as high as theirs.
+    pub async fn remove_member(
+        &self,
+        principal: &Principal,
+        workspace_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<()> {
+        let role = self.workspace_role(principal, workspace_id).await?;
+        let leaving = principal.user_id() == Some(user_id);
+        if !leaving {
+            check_role(role, WorkspaceRole::Admin)?;
+        }
+
+        let mut tx = self.db.begin().await?;
+        database::lock_workspace(&mut tx, workspace_id).await?;
+        let current = database::get_member_role(&mut *tx, workspace_id, user_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Member not found".to_string()))?;
+        if !leaving && current > role {
+            return Err(AppError::Forbidden(
+                "You cannot remove a member above you".to_string(),
+            ));
+        }
+        if current == WorkspaceRole::Owner {
+            ensure_other_owner(&mut tx, workspace_id).await?;
+        }
+        database::remove_member(&mut tx, workspace_id, user_id).await?;
+        database::insert_audit_event(
+            &mut *tx,
+            &audit::event(principal, "member.remove")
+                .target(user_id)
+                .workspace(workspace_id)
+                .changes(diff(Some(&json!({ "role": current })), None)),
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Invites an email address to a workspace. The returned token is only shown once.
+    pub async fn create_invitation(
+        &self,
+        principal: &Principal,
+        workspace_id: Uuid,
+        request: CreateInvitationRequest,
+    ) -> Result<Invitation> {
+        let role = self.workspace_role(principal, workspace_id).await?;
+        check_role(role, WorkspaceRole::Admin)?;
+        if request.role > role {
+            return Err(AppError::Forbidden(
+                "You cannot invite with a role higher than your own".to_string(),
+            ));
+        }
+        let email = accounts::normalize_email(&request.email)?;
+
+        let token = accounts::new_secret_token();
+        let expires_at = time::OffsetDateTime::now_utc()
+            + Duration::hours(self.config.invitation_ttl_hours as i64);
+        let mut invitation = database::create_invitation(
+            &self.db,
+            workspace_id,
+            &email,
+            request.role,
+            &accounts::hash_secret_token(&token),
+            principal.user_id(),
+            expires_at,
+        )
+        .await?;
+        self.audit(
+            audit::event(principal, "invitation.create")
+                .target(invitation.id)
+                .workspace(workspace_id)
+                .changes(diff(None, Some(&invitation))),
+        )
+        .await?;
+        invitation.token = Some(token);
+
+        Ok(invitation)
+    }
+
+    /// Lists the invitations that can still be accepted
+    pub async fn list_invitations(
+        &self,
+        principal: &Principal,
+        workspace_id: Uuid,
+    ) -> Result<Vec<Invitation>> {
+        check_role(
+            self.workspace_role(principal, workspace_id).await?,
+            WorkspaceRole::Admin,
+        )?;
+        self.audit(audit::event(principal, "invitation.list").workspace(workspace_id))
+            .await?;
+        database::list_invitations(&self.db, workspace_id).await
+    }
+
+    pub async fn revoke_invitation(
+        &self,
+        principal: &Principal,
+        workspace_id: Uuid,
+        id: Uuid,
+    ) -> Result<()> {
+        check_role(
+            self.workspace_role(principal, workspace_id).await?,
+            WorkspaceRole::Admin,
+        )?;
+        if !database::delete_invitation(&self.db, workspace_id, id).await? {
+            return Err(AppError::NotFound("Invitation not found".to_string()));
+        }
+        self.audit(
+            audit::event(principal, "invitation.revoke")
+                .target(id)
+                .workspace(workspace_id),
+        )
+        .await
+    }
+
+    /// Joins the workspace of an invitation sent to the principal's email address
+    pub async fn accept_invitation(
+        &self,
+        principal: &Principal,
+        request: AcceptInvitationRequest,
+    ) -> Result<Workspace> {
+        let user = match principal {
+            Principal::User(user) | Principal::Member(user, _) => user,
+            _ => {
+                return Err(AppError::Forbidden(
+                    "Invitations can only be accepted by accounts".to_string(),
+                ))
+            }
+        };
+
+        let mut tx = self.db.begin().await?;
+        let (workspace_id, role) = database::claim_invitation(
+            &mut tx,
+            &accounts::hash_secret_token(&request.token),
+            &user.email,
+        )
+        .await?
+        .ok_or_else(|| AppError::NotFound("Invitation not found or expired".to_string()))?;
+        database::add_member(&mut tx, workspace_id, user.id, role).await?;
+        database::insert_audit_event(
+            &mut *tx,
+            &audit::event(principal, "invitation.accept")
+                .target(user.id)
+                .workspace(workspace_id)
+                .changes(diff(None, Some(&json!({ "role": role })))),
+        )
+        .await?;
+        tx.commit().await?;
+
+        database::get_workspace(&self.db, workspace_id, user.id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Workspace not found".to_string()))
+    }
+
+    /// Creates an API key acting on the workspace with a fixed role. The key is only shown
+    /// once.
+    pub async fn create_workspace_api_key(
+        &self,
+        principal: &Principal,
+        workspace_id: Uuid,
+        request: CreateApiKeyRequest,
+    ) -> Result<WorkspaceApiKey> {
+        let role = self.workspace_role(principal, workspace_id).await?;
+        check_role(role, WorkspaceRole::Admin)?;
+        if request.role == WorkspaceRole::Owner {
+            return Err(AppError::BadRequest(
+                "API keys cannot have the owner role".to_string(),
+            ));
+        }
+        if request.role > role {
+            return Err(AppError::Forbidden(
+                "You cannot create a key with a role higher than your own".to_string(),
+            ));
+        }
+        let name = validate_name(&request.name, "API key")?;
+
+        let (key, key_prefix) = accounts::new_workspace_api_key();
+        let mut api_key = database::create_workspace_api_key(
+            &self.db,
+            workspace_id,
+            name,
+            request.role,
+            &accounts::hash_secret_token(&key),
+            &key_prefix,
+            principal.user_id(),
+        )
+        .await?;
+        self.audit(
+            audit::event(principal, "api_key.create")
+                .target(api_key.id)
+                .workspace(workspace_id)
+                .changes(diff(None, Some(&api_key))),
+        )
+        .await?;
+        api_key.key = Some(key);
+
+        Ok(api_key)
+    }
+
+    pub async fn list_workspace_api_keys(
+        &self,
+        principal: &Principal,
+        workspace_id: Uuid,
+    ) -> Result<Vec<WorkspaceApiKey>> {
+        check_role(
+            self.workspace_role(principal, workspace_id).await?,
+            WorkspaceRole::Admin,
+        )?;
+        self.audit(audit::event(principal, "api_key.list").workspace(workspace_id))
+            .await?;
+        database::list_workspace_api_keys(&self.db, workspace_id).await
+    }
+
+    pub async fn revoke_workspace_api_key(
+        &self,
+        principal: &Principal,
+        workspace_id: Uuid,
+        id: Uuid,
+    ) -> Result<()> {
+        check_role(
+            self.workspace_role(principal, workspace_id).await?,
+            WorkspaceRole::Admin,
+        )?;
+        if !database::revoke_workspace_api_key(&self.db, workspace_id, id).await? {
+            return Err(AppError::NotFound("API key not found".to_string()));
+        }
+        self.audit(
+            audit::event(principal, "api_key.revoke")
+                .target(id)
+                .workspace(workspace_id),
+        )
+        .await
+    }
+
+    /// The role a principal has in a workspace. Server admins have full control over every
+    /// workspace; without access, the workspace is reported as missing.
+    async fn workspace_role(
+        &self,
+        principal: &Principal,
+        workspace_id: Uuid,
+    ) -> Result<WorkspaceRole> {
+        let role = match principal {
+            Principal::WorkspaceKey { access, .. } => {
+                (access.workspace_id == workspace_id).then_some(access.role)
+            }
+            _ if principal.is_admin() => database::workspace_exists(&self.db, workspace_id)
+                .await?
+                .then_some(WorkspaceRole::Owner),
+            _ => match principal.user_id() {
+                Some(user_id) => database::get_member_role(&self.db, workspace_id, user_id).await?,
+                None => None,
+            },
+        };
+
+        role.ok_or_else(|| AppError::NotFound("Workspace not found".to_string()))
+    }
+
+    /// Builds the public short URL for a short code
+    pub fn short_url(&self, short_code: &str) -> String {
+        format!("{}/{}", self.config.base_url, short_code)
+    }
+
+    pub async fn create_short_url(
+        &self,
+        principal: &Principal,
+        request: CreateUrlRequest,
+    ) -> Result<CreateUrlResponse> {
+        require_workspace_role(principal, WorkspaceRole::Editor)?;
+        let prepared = self.prepare_url(principal, request)?;
+        if let Some(collection_id) = prepared.collection_id {
+            self.ensure_collection_usable(principal, collection_id, prepared.workspace_id)
+                .await?;
+        }
+
+        let reservation = quota::reserve(&self.db, &self.cache, principal, 1).await?;
+        let (url, created) = match self.insert_url(principal, &prepared).await {
+            Ok(inserted) => inserted,
+            Err(err) => {
+                reservation.cancel(&self.db, &self.cache).await;
+                return Err(err);
+            }
+        };
+        if !created {
+            // An existing link was returned, so nothing counts towards the quota
+            reservation.cancel(&self.db, &self.cache).await;
+        }
+
+        if created {
+            // Cache the URL
+            let cache_key = Cache::url_cache_key(&url.short_code);
+            self.cache
+                .set_json_with_expiry(&cache_key, &RedirectTarget::from(&url), 3600)
+                .await?;
+            if !self.queue_metadata_fetch(&url) {
+                tracing::warn!(
+                    "Skipping metadata fetch for {}: queue is full",
+                    url.short_code
+                );
+            }
+        }
+
+        // Create response
+        Ok(self.create_response(url, created))
+    }
+
+    /// Inserts a link and its tags together, returning whether it was created
+    async fn insert_url(
+        &self,
+        principal: &Principal,
+        prepared: &PreparedUrl,
+    ) -> Result<(Url, bool)> {
+        let mut tx = self.db.begin().await?;
+        let (url, created) = insert_prepared_url(&mut tx, prepared).await?;
+        if created {
+            database::insert_audit_event(
+                &mut *tx,
+                &audit::event(principal, "link.create")
+                    .target(&url.short_code)
+                    .changes(diff(None, Some(&url))),
+            )
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok((url, created))
+    }
+
+    /// Creates many links at once, returning one result per item in request order.
+    ///
+    /// All items share one transaction. In transactional mode any failing item rolls back the
+    /// whole batch; in partial mode each item runs in its own savepoint so failures are skipped.
+    pub async fn create_short_urls(
+        &self,
+        principal: &Principal,
+        request: BatchCreateUrlRequest,
+    ) -> Result<BatchCreateUrlResponse> {
+        require_workspace_role(principal, WorkspaceRole::Editor)?;
+        if request.urls.is_empty() || request.urls.len() > MAX_BATCH_SIZE {
+            return Err(AppError::BadRequest(format!(
+                "A batch must contain between 1 and {} links",
+                MAX_BATCH_SIZE
+            )));
+        }
+        let transactional = request.mode.unwrap_or_default() == BatchMode::Transactional;
+        let request_len = request.urls.len();
+
+        // Validate every item up front, checking each referenced collection only once
+        let mut prepared: Vec<Result<PreparedUrl>> = request
+            .urls
+            .into_iter()
+            .map(|item| self.prepare_url(principal, item))
+            .collect();
+        let mut known_collections = HashMap::new();
+        for item in prepared.iter_mut() {
+            let Some((collection_id, workspace_id)) = item.as_ref().ok().and_then(|item| {
+                item.collection_id
+                    .map(|collection_id| (collection_id, item.workspace_id))
+            }) else {
+                continue;
+            };
+            let usable = match known_collections.get(&(collection_id, workspace_id)) {
+                Some(usable) => *usable,
+                None => {
+                    let usable = self
+                        .collection_usable(principal, collection_id, workspace_id)
+                        .await?;
+                    known_collections.insert((collection_id, workspace_id), usable);
+                    usable
+                }
+            };
+            if !usable {
+                *item = Err(unusable_collection(collection_id));
+            }
+        }
+
+        // In transactional mode nothing is inserted when an item is invalid
+        if transactional && prepared.iter().any(|item| item.is_err()) {
+            let errors = prepared.into_iter().map(|item| item.err()).collect();
+            return Ok(self.rolled_back_batch(errors));
+        }
+
+        let valid = prepared.iter().filter(|item| item.is_ok()).count();
+        let mut reservation =
+            quota::reserve(&self.db, &self.cache, principal, valid as i64).await?;
+        let results = match self.insert_urls(principal, prepared, transactional).await {
+            Ok(results) => results,
+            Err(err) => {
+                reservation.cancel(&self.db, &self.cache).await;
+                return Err(err);
+            }
+        };
+
+        // The first failing insert rolled back the whole batch
+        if transactional {
+            if let Some(index) = results.iter().position(|result| result.is_err()) {
+                reservation.cancel(&self.db, &self.cache).await;
+                let mut errors: Vec<Option<AppError>> = (0..request_len).map(|_| None).collect();
+                errors[index] = results
+                    .into_iter()
+                    .nth(index)
+                    .and_then(|result| result.err());
+                return Ok(self.rolled_back_batch(errors));
+            }
+        }
+        // Existing links returned by deduplication and failed items do not count
+        let created = results
+            .iter()
+            .filter(|result| matches!(result, Ok((_, true))))
+            .count();
+        reservation
+            .release(&self.db, &self.cache, (valid - created) as i64)
+            .await;
+        let failed = results.iter().filter(|result| result.is_err()).count();
+        let reused = results.len() - created - failed;
+
+        // Cache all new links in one round trip
+        let new_urls: Vec<&Url> = results
+            .iter()
+            .filter_map(|result| match result {
+                Ok((url, true)) => Some(url),
+                _ => None,
+            })
+            .collect();
+        let entries: Vec<(String, RedirectTarget)> = new_urls
+            .iter()
+            .map(|url| {
+                (
+                    Cache::url_cache_key(&url.short_code),
+                    RedirectTarget::from(*url),
+                )
+            })
+            .collect();
+        self.cache.set_many_json_with_expiry(&entries, 3600).await?;
+        let skipped = new_urls
+            .into_iter()
+            .filter(|url| !self.queue_metadata_fetch(url))
+            .count();
+        if skipped > 0 {
+            tracing::warn!(
+                "Skipping metadata fetch for {} batch link(s): queue is full",
+                skipped
+            );
+        }
+
+        let results: Vec<BatchItemResult> = results
+            .into_iter()
+            .enumerate()
+            .map(|(index, result)| match result {
+                Ok((url, created)) => BatchItemResult {
+                    index,
+                    url: Some(self.create_response(url, created)),
+                    error: None,
+                },
+                Err(err) => self.batch_error(index, err),
+            })
+            .collect();
+
+        Ok(BatchCreateUrlResponse {
+            created,
+            reused,
+            failed,
+            results,
+        })
+    }
+
+    /// Inserts the valid items of a batch in one transaction. In transactional mode the first
+    /// failing insert rolls everything back and is the last result; otherwise each item runs
+    /// in its own savepoint so failures are skipped.
+    async fn insert_urls(
+        &self,
+        principal: &Principal,
+        prepared: Vec<Result<PreparedUrl>>,
+        transactional: bool,
+    ) -> Result<Vec<Result<(Url, bool)>>> {
+        let mut tx = self.db.begin().await?;
+        let results: Vec<Result<(Url, bool)>> = if transactional {
+            // The first failing insert aborts the transaction, so later items are not attempted
+            let mut inserted = Vec::with_capacity(prepared.len());
+            for item in prepared.into_iter().flatten() {
+                match insert_prepared_url(&mut tx, &item).await {
+                    Ok(url) => inserted.push(Ok(url)),
+                    Err(err) => {
+                        tx.rollback().await?;
+                        inserted.push(Err(err));
+                        return Ok(inserted);
+                    }
+                }
+            }
+            inserted
+        } else {
+            let mut results = Vec::with_capacity(prepared.len());
+            for item in prepared {
+                let result = match item {
+                    Ok(item) => {
+                        let mut savepoint = tx.begin().await?;
+                        match insert_prepared_url(&mut savepoint, &item).await {
+                            Ok(inserted) => {
+                                savepoint.commit().await?;
+                                Ok(inserted)
+                            }
+                            Err(err) => {
+                                savepoint.rollback().await?;
+                                Err(err)
+                            }
+                        }
+                    }
+                    Err(err) => Err(err),
+                };
+                results.push(result);
+            }
+            results
+        };
+        let created_codes: Vec<&str> = results
+            .iter()
+            .filter_map(|result| match result {
+                Ok((url, true)) => Some(url.short_code.as_str()),
+                _ => None,
+            })
+            .collect();
+        if !created_codes.is_empty() {
+            database::insert_audit_event(
+                &mut *tx,
+                &audit::event(principal, "link.batch_create")
+                    .changes(Some(json!({ "short_codes": created_codes }))),
+            )
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(results)
+    }
 
+    /// Validates a creation request and resolves its defaults, without touching the database
+    fn prepare_url(&self, principal: &Principal, request: CreateUrlRequest) -> Result<PreparedUrl> {
         // Calculate expiration date if provided, with safety limits
-        let expires_at = if let Some(days) = request.expires_in_days {
+        let expires_at = if request.expires_at.is_some() {
+            request.expires_at
+        } else if let Some(days) = request.expires_in_days {
             // Limit to reasonable range to avoid overflow
             let days = days.clamp(0, 365 * 10); // Max 10 years
 
@@ -53,25 +1126,118 @@ impl UrlService {
             None
         };
 
-        // Create URL in database
-        let url = database::create_url(&self.db, &request.url, &short_code, expires_at).await?;
+        if let Some(ref og_image) = request.og_image {
+            validate_image_url(og_image)?;
+        }
+        if let Some(ref fallback_url) = request.fallback_url {
+            validate_fallback_url(fallback_url)?;
+        }
+        if request.require_signature == Some(true) {
+            self.ensure_link_signing()?;
+        }
+        let tags = normalize_tags(request.tags.as_deref().unwrap_or_default())?;
+        validate_notes(request.title.as_deref(), request.description.as_deref())?;
+        let metadata = match request.metadata {
+            Some(metadata) => validate_metadata(metadata)?,
+            None => serde_json::Value::Object(Default::default()),
+        };
 
-        // Cache the URL
-        let cache_key = Cache::url_cache_key(&short_code);
-        self.cache
-            .set_with_expiry(&cache_key, &url.original_url, 3600)
-            .await?;
+        let non_empty = |value: Option<String>| value.filter(|s| !s.is_empty());
+        let alias = non_empty(request.alias);
+        if let Some(ref alias) = alias {
+            validate_alias(alias)?;
+        }
 
-        // Create response
-        Ok(CreateUrlResponse {
+        Ok(PreparedUrl {
+            target_hash: target_hash(&request.url),
+            // Links with an alias or an expiry are never shared, since the requested alias or
+            // expiry could not be honored
+            dedupe: request.dedupe.unwrap_or(self.config.dedupe_targets)
+                && alias.is_none()
+                && expires_at.is_none(),
+            alias,
+            original_url: request.url,
+            expires_at,
+            interstitial: request.interstitial.unwrap_or(false),
+            require_signature: request.require_signature.unwrap_or(false),
+            og_title: non_empty(request.og_title),
+            og_description: non_empty(request.og_description),
+            og_image: non_empty(request.og_image),
+            fallback_url: non_empty(request.fallback_url),
+            collection_id: request.collection_id,
+            title: non_empty(request.title),
+            description: non_empty(request.description),
+            metadata,
+            tags,
+            owner_id: principal.user_id(),
+            workspace_id: principal.workspace().map(|access| access.workspace_id),
+            changed_by: principal.changed_by(),
+        })
+    }
+
+    /// Fetches the destination's title and favicon in the background. A full queue only
+    /// means the link goes without metadata, so it must not fail the request.
+    /// Returns `false` when the fetch was skipped.
+    fn queue_metadata_fetch(&self, url: &Url) -> bool {
+        match self.metadata_jobs {
+            Some(ref jobs) => jobs
+                .try_send(MetadataJob {
+                    url_id: url.id,
+                    original_url: url.original_url.clone(),
+                })
+                .is_ok(),
+            None => true,
+        }
+    }
+
+    fn create_response(&self, url: Url, created: bool) -> CreateUrlResponse {
+        CreateUrlResponse {
             id: url.id,
+            short_url: self.short_url(&url.short_code),
             original_url: url.original_url,
-            short_url: format!("{}/{}", self.config.base_url, url.short_code),
             expires_at: url.expires_at,
-        })
+            title: url.title,
+            description: url.description,
+            metadata: url.metadata,
+            created,
+        }
+    }
+
+    /// Reports a transactional batch in which nothing was created
+    fn rolled_back_batch(&self, errors: Vec<Option<AppError>>) -> BatchCreateUrlResponse {
+        let failed = errors.iter().filter(|err| err.is_some()).count();
+        let results = errors
+            .into_iter()
+            .enumerate()
+            .map(|(index, err)| match err {
+                Some(err) => self.batch_error(index, err),
+                None => BatchItemResult {
+                    index,
+                    url: None,
+                    error: Some("Not created because another item in the batch failed".to_string()),
+                },
+            })
+            .collect();
+
+        BatchCreateUrlResponse {
+            created: 0,
+            reused: 0,
+            failed,
+            results,
+        }
+    }
+
+    fn batch_error(&self, index: usize, err: AppError) -> BatchItemResult {
+        BatchItemResult {
+            index,
+            url: None,
+            error: Some(err.user_message(self.get_environment())),
+        }
     }
 
-    pub async fn get_url(&self, short_code: &str) -> Result<String> {
+    /// Resolves a short code to its redirect target without counting a visit.
+    /// The returned `interstitial` flag also reflects the global domain policy.
+    pub async fn get_url(&self, short_code: &str) -> Result<RedirectTarget> {
         // Log request details
         tracing::debug!(
             "Processing URL retrieval request for short code: {}",
@@ -80,62 +1246,962 @@ impl UrlService {
 
         // Try to get URL from cache first
         let cache_key = Cache::url_cache_key(short_code);
-        if let Some(url) = self.cache.get(&cache_key).await? {
-            tracing::debug!("URL found in cache for short code: {}", short_code);
-            return Ok(url);
-        }
+        let target = match self.cache.get_json::<RedirectTarget>(&cache_key).await? {
+            Some(target) => {
+                tracing::debug!("URL found in cache for short code: {}", short_code);
+                target
+            }
+            None => {
+                tracing::debug!(
+                    "URL not in cache, checking database for short code: {}",
+                    short_code
+                );
 
-        tracing::debug!(
-            "URL not in cache, checking database for short code: {}",
-            short_code
-        );
+                // If not in cache, get from database
+                let url = self.find_url(short_code).await?;
 
-        // If not in cache, get from database
-        let url_result = database::get_url_by_code(&self.db, short_code).await?;
+                tracing::debug!("URL found in database for short code: {}", short_code);
+                let target = RedirectTarget::from(&url);
 
-        // Check if URL was found
-        if let Some(url) = url_result {
-            tracing::debug!("URL found in database for short code: {}", short_code);
+                // Cache the URL for future requests
+                self.cache
+                    .set_json_with_expiry(&cache_key, &target, 3600)
+                    .await?;
 
-            // Cache the URL for future requests
-            self.cache
-                .set_with_expiry(&cache_key, &url.original_url, 3600)
-                .await?;
+                target
+            }
+        };
+
+        let interstitial =
+            target.interstitial || self.outside_allowed_domains(&target.original_url);
+        Ok(RedirectTarget {
+            interstitial,
+            ..target
+        })
+    }
 
-            // Increment visit count
-            database::increment_visits(&self.db, url.id).await?;
+    /// Looks up an active link; links in the trash are reported as gone
+    async fn find_url(&self, short_code: &str) -> Result<Url> {
+        let url = database::get_url_by_code(&self.db, short_code)
+            .await?
+            .ok_or_else(|| {
+                // Use debug level for 404 errors as requested
+                tracing::debug!(
+                    "URL not found in database for short code: {}. This could be because the URL doesn't exist or has expired.",
+                    short_code
+                );
 
-            Ok(url.original_url)
-        } else {
-            // Use debug level for 404 errors as requested
-            tracing::debug!(
-                "URL not found in database for short code: {}. This could be because the URL doesn't exist or has expired.",
-                short_code
-            );
+                // Maintain the same user-facing error
+                AppError::NotFound("URL not found".to_string())
+            })?;
 
-            // Maintain the same user-facing error
-            Err(AppError::NotFound("URL not found".to_string()))
+        if url.deleted_at.is_some() {
+            return Err(AppError::Gone("URL has been deleted".to_string()));
         }
+
+        Ok(url)
     }
 
-    pub async fn get_stats(&self) -> Result<StatsResponse> {
-        let urls = database::get_url_stats(&self.db).await?;
-        let (total_urls, total_visits) = database::get_stats_summary(&self.db).await?;
+    /// Whether the global policy requires a warning page for this destination
+    fn outside_allowed_domains(&self, original_url: &str) -> bool {
+        let allowed = &self.config.interstitial_allowed_domains;
+        if allowed.is_empty() {
+            return false;
+        }
 
-        Ok(StatsResponse {
-            total_urls,
-            total_visits,
-            urls,
-        })
+        let host = match ::url::Url::parse(original_url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_lowercase))
+        {
+            Some(host) => host,
+            None => return true,
+        };
+
+        !allowed
+            .iter()
+            .any(|domain| host == *domain || host.ends_with(&format!(".{}", domain)))
     }
 
-    pub async fn check_database_connection(&self) -> anyhow::Result<()> {
-        // Simple query to check DB connectivity
-        sqlx::query("SELECT 1")
-            .execute(&self.db)
-            .await
-            .map(|_| ())
-            .map_err(|e| anyhow::anyhow!("Database connection check failed: {}", e))
+    pub fn interstitial_countdown_secs(&self) -> u32 {
+        self.config.interstitial_countdown_secs
+    }
+
+    /// Records how a request for a link was served
+    pub async fn record_visit(&self, url_id: Uuid, kind: VisitKind) -> Result<()> {
+        match kind {
+            VisitKind::Redirect => database::increment_visits(&self.db, url_id).await,
+            VisitKind::InterstitialShown => {
+                database::record_interstitial_view(&self.db, url_id).await
+            }
+            VisitKind::InterstitialConfirmed => {
+                database::record_interstitial_confirmation(&self.db, url_id).await
+            }
+        }
+    }
+
+    pub async fn update_url(
+        &self,
+        principal: &Principal,
+        short_code: &str,
+        request: UpdateUrlRequest,
+    ) -> Result<Url> {
+        tracing::debug!("Processing update request for short code: {}", short_code);
+
+        let owner = self
+            .authorize_url_owner(principal, short_code, WorkspaceRole::Editor)
+            .await?;
+        let url_id = owner.id;
+
+        if let Some(ref og_image) = request.og_image {
+            validate_image_url(og_image)?;
+        }
+        if let Some(ref fallback_url) = request.fallback_url {
+            validate_fallback_url(fallback_url)?;
+        }
+        if request.require_signature == Some(true) {
+            self.ensure_link_signing()?;
+        }
+        let tags = request.tags.as_deref().map(normalize_tags).transpose()?;
+        if let Some(Some(collection_id)) = request.collection_id {
+            self.ensure_collection_usable(principal, collection_id, owner.workspace_id)
+                .await?;
+        }
+        validate_notes(request.title.as_deref(), request.description.as_deref())?;
+        let metadata = request.metadata.map(validate_metadata).transpose()?;
+        if request
+            .url
+            .as_deref()
+            .is_some_and(|url| url.trim().is_empty())
+        {
+            return Err(AppError::BadRequest("url must not be empty".to_string()));
+        }
+        let new_target = request.url.as_deref().map(|url| (url, target_hash(url)));
+
+        let changes = UrlChanges {
+            original_url: new_target.as_ref().map(|(url, hash)| (*url, hash.as_str())),
+            expires_at: request.expires_at,
+            interstitial: request.interstitial,
+            require_signature: request.require_signature,
+            og_title: request.og_title.as_deref(),
+            og_description: request.og_description.as_deref(),
+            og_image: request.og_image.as_deref(),
+            fallback_url: request.fallback_url.as_deref(),
+            collection_id: request.collection_id,
+            title: request.title.as_deref(),
+            description: request.description.as_deref(),
+            metadata: metadata.as_ref(),
+        };
+
+        let mut tx = self.db.begin().await?;
+        let before = database::get_url_by_id(&mut *tx, url_id).await?;
+        database::update_url(&mut tx, short_code, &changes)
+            .await?
+            .ok_or_else(|| AppError::NotFound("URL not found".to_string()))?;
+        if let Some(ref tags) = tags {
+            database::set_url_tags(&mut tx, url_id, tags).await?;
+        }
+        database::record_revision(
+            &mut tx,
+            url_id,
+            RevisionChange::Updated,
+            Some(&principal.changed_by()),
+        )
+        .await?;
+        let url = database::get_url_by_id(&mut *tx, url_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("URL not found".to_string()))?;
+        database::insert_audit_event(
+            &mut *tx,
+            &audit::event(principal, "link.update")
+                .target(short_code)
+                .changes(diff(before.as_ref(), Some(&url))),
+        )
+        .await?;
+        tx.commit().await?;
+
+        // Drop the cached redirect target so the change applies immediately
+        self.cache.delete(&Cache::url_cache_key(short_code)).await?;
+        self.refresh_metadata(&url);
+
+        Ok(url)
+    }
+
+    /// Lists the revisions of a link, newest first
+    pub async fn get_url_history(
+        &self,
+        principal: &Principal,
+        short_code: &str,
+        page: &PageQuery,
+    ) -> Result<Vec<UrlRevision>> {
+        tracing::debug!("Processing history request for short code: {}", short_code);
+
+        let url_id = self
+            .authorize_url(principal, short_code, WorkspaceRole::Viewer)
+            .await?;
+        self.audit(audit::event(principal, "link.history").target(url_id))
+            .await?;
+
+        database::list_url_revisions(&self.db, url_id, page.limit(), page.offset()).await
+    }
+
+    /// Gives a link the redirect settings of one of its earlier revisions again, recording
+    /// the rollback as a new revision
+    pub async fn rollback_url(
+        &self,
+        principal: &Principal,
+        short_code: &str,
+        revision: i32,
+    ) -> Result<Url> {
+        tracing::debug!(
+            "Processing rollback request for short code: {} to revision {}",
+            short_code,
+            revision
+        );
+
+        let url_id = self
+            .authorize_url(principal, short_code, WorkspaceRole::Editor)
+            .await?;
+
+        let mut tx = self.db.begin().await?;
+        let before = database::get_url_by_id(&mut *tx, url_id).await?;
+        let snapshot = database::get_url_revision(&mut tx, url_id, revision)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Revision not found".to_string()))?;
+        if snapshot.require_signature {
+            self.ensure_link_signing()?;
+        }
+        let hash = target_hash(&snapshot.original_url);
+        let changes = UrlChanges {
+            original_url: Some((&snapshot.original_url, &hash)),
+            expires_at: Some(snapshot.expires_at),
+            interstitial: Some(snapshot.interstitial),
+            og_title: Some(snapshot.og_title.as_deref().unwrap_or_default()),
+            og_description: Some(snapshot.og_description.as_deref().unwrap_or_default()),
+            og_image: Some(snapshot.og_image.as_deref().unwrap_or_default()),
+            fallback_url: Some(snapshot.fallback_url.as_deref().unwrap_or_default()),
+            require_signature: Some(snapshot.require_signature),
+            ..Default::default()
+        };
+        database::update_url(&mut tx, short_code, &changes)
+            .await?
+            .ok_or_else(|| AppError::NotFound("URL not found".to_string()))?;
+        database::record_revision(
+            &mut tx,
+            url_id,
+            RevisionChange::Rollback,
+            Some(&principal.changed_by()),
+        )
+        .await?;
+        let url = database::get_url_by_id(&mut *tx, url_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("URL not found".to_string()))?;
+        database::insert_audit_event(
+            &mut *tx,
+            &audit::event(principal, "link.rollback")
+                .target(short_code)
+                .changes(diff(before.as_ref(), Some(&url))),
+        )
+        .await?;
+        tx.commit().await?;
+
+        self.cache.delete(&Cache::url_cache_key(short_code)).await?;
+        self.refresh_metadata(&url);
+
+        Ok(url)
+    }
+
+    /// Queues a metadata fetch for a link whose destination changed, which clears the
+    /// metadata of the old one
+    fn refresh_metadata(&self, url: &Url) {
+        if url.metadata_fetched_at.is_none() && !self.queue_metadata_fetch(url) {
+            tracing::warn!(
+                "Skipping metadata fetch for {}: queue is full",
+                url.short_code
+            );
+        }
+    }
+
+    /// Moves a link to the trash; it stops redirecting until restored or purged
+    pub async fn delete_url(&self, principal: &Principal, short_code: &str) -> Result<()> {
+        tracing::debug!("Processing delete request for short code: {}", short_code);
+
+        self.authorize_url(principal, short_code, WorkspaceRole::Editor)
+            .await?;
+        let mut tx = self.db.begin().await?;
+        if !database::soft_delete_url(&mut *tx, short_code).await? {
+            return Err(AppError::NotFound("URL not found".to_string()));
+        }
+        database::insert_audit_event(
+            &mut *tx,
+            &audit::event(principal, "link.delete").target(short_code),
+        )
+        .await?;
+        tx.commit().await?;
+        self.cache.delete(&Cache::url_cache_key(short_code)).await?;
+
+        Ok(())
+    }
+
+    /// Takes a link out of the trash and caches its redirect target again
+    pub async fn restore_deleted_url(
+        &self,
+        principal: &Principal,
+        short_code: &str,
+    ) -> Result<Url> {
+        tracing::debug!("Processing restore request for short code: {}", short_code);
+
+        self.authorize_url(principal, short_code, WorkspaceRole::Editor)
+            .await?;
+        let mut tx = self.db.begin().await?;
+        let url_id = database::restore_deleted_url(&mut *tx, short_code)
+            .await?
+            .ok_or_else(|| AppError::NotFound("URL not found in the trash".to_string()))?;
+        let url = database::get_url_by_id(&mut *tx, url_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("URL not found".to_string()))?;
+        database::insert_audit_event(
+            &mut *tx,
+            &audit::event(principal, "link.restore").target(short_code),
+        )
+        .await?;
+        tx.commit().await?;
+
+        self.cache
+            .set_json_with_expiry(
+                &Cache::url_cache_key(short_code),
+                &RedirectTarget::from(&url),
+                3600,
+            )
+            .await?;
+
+        Ok(url)
+    }
+
+    /// Looks up a link for preview without counting a visit
+    pub async fn get_url_preview(&self, short_code: &str) -> Result<Url> {
+        tracing::debug!("Processing preview request for short code: {}", short_code);
+
+        let url = self.find_url(short_code).await?;
+        // A preview would show where the link leads without a valid signature
+        if url.require_signature {
+            return Err(AppError::Forbidden(
+                "This link can only be opened through a signed link".to_string(),
+            ));
+        }
+
+        Ok(url)
+    }
+
+    /// Checks the signature of a signed link before the link is looked up. Returns whether
+    /// the request carries a valid signature.
+    pub fn verify_link_signature(
+        &self,
+        short_code: &str,
+        signature: Option<&LinkSignature>,
+    ) -> Result<bool> {
+        match signature {
+            Some(signature) => {
+                self.link_signer.verify(short_code, signature)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Mints a signed variant of a link for each recipient, each expiring on its own
+    pub async fn sign_url(
+        &self,
+        principal: &Principal,
+        short_code: &str,
+        request: SignLinkRequest,
+    ) -> Result<Vec<SignedLink>> {
+        self.ensure_link_signing()?;
+        self.authorize_url(principal, short_code, WorkspaceRole::Editor)
+            .await?;
+        let url = self.find_url(short_code).await?;
+        if request.recipients.is_empty() || request.recipients.len() > MAX_SIGNED_LINKS {
+            return Err(AppError::BadRequest(format!(
+                "Between 1 and {} signed links can be minted at once",
+                MAX_SIGNED_LINKS
+            )));
+        }
+
+        let now = time::OffsetDateTime::now_utc();
+        let latest = now + self.link_signer.max_ttl();
+        let mut links = Vec::with_capacity(request.recipients.len());
+        for item in request.recipients {
+            let recipient = item.recipient.filter(|recipient| !recipient.is_empty());
+            if let Some(ref recipient) = recipient {
+                if recipient.chars().count() > MAX_RECIPIENT_LENGTH
+                    || recipient.chars().any(char::is_control)
+                {
+                    return Err(AppError::BadRequest(format!(
+                        "Recipients must be at most {} characters without control characters",
+                        MAX_RECIPIENT_LENGTH
+                    )));
+                }
+            }
+            let expires_at = match (item.expires_at, item.expires_in_secs) {
+                (Some(expires_at), _) => expires_at,
+                (None, Some(secs)) => now
+                    .checked_add(Duration::seconds(secs))
+                    .unwrap_or(time::OffsetDateTime::UNIX_EPOCH),
+                (None, None) => {
+                    return Err(AppError::BadRequest(
+                        "Each signed link needs expires_at or expires_in_secs".to_string(),
+                    ))
+                }
+            };
+            // Signed links carry whole seconds
+            let expires_at = expires_at
+                .replace_nanosecond(0)
+                .map_err(|e| AppError::BadRequest(format!("Invalid expiry: {}", e)))?;
+            if expires_at <= now || expires_at > latest {
+                return Err(AppError::BadRequest(format!(
+                    "Signed links must expire within {} seconds from now",
+                    self.link_signer.max_ttl().whole_seconds()
+                )));
+            }
+
+            let (key_id, query) =
+                self.link_signer
+                    .sign(&url.short_code, expires_at, recipient.as_deref())?;
+            links.push(SignedLink {
+                recipient,
+                url: format!("{}?{}", self.short_url(&url.short_code), query),
+                expires_at,
+                key_id,
+            });
+        }
+
+        let minted: Vec<_> = links
+            .iter()
+            .map(|link| {
+                json!({
+                    "recipient": link.recipient,
+                    "expires_at": link.expires_at.unix_timestamp(),
+                    "key_id": link.key_id,
+                })
+            })
+            .collect();
+        self.audit(
+            audit::event(principal, "link.sign")
+                .target(&url.short_code)
+                .changes(Some(json!({ "signed_links": minted }))),
+        )
+        .await?;
+
+        Ok(links)
+    }
+
+    fn ensure_link_signing(&self) -> Result<()> {
+        if !self.link_signer.enabled() {
+            return Err(AppError::BadRequest(
+                "Link signing is not configured on this server".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub async fn get_qr_code(&self, short_code: &str, options: &QrOptions) -> Result<Vec<u8>> {
+        tracing::debug!("Processing QR code request for short code: {}", short_code);
+
+        // Make sure the link exists without counting a visit
+        let url_cache_key = Cache::url_cache_key(short_code);
+        if self.cache.get(&url_cache_key).await?.is_none() {
+            self.find_url(short_code).await?;
+        }
+
+        let cache_key = options.cache_key(short_code);
+        if let Some(image) = self.cache.get_bytes(&cache_key).await? {
+            tracing::debug!("QR code found in cache for short code: {}", short_code);
+            return Ok(image);
+        }
+
+        let image = options.render(&self.short_url(short_code))?;
+        self.cache
+            .set_bytes_with_expiry(&cache_key, &image, QR_CACHE_TTL_SECS)
+            .await?;
+
+        Ok(image)
+    }
+
+    pub async fn get_broken_links(
+        &self,
+        principal: &Principal,
+        page: &PageQuery,
+    ) -> Result<Vec<Url>> {
+        self.audit(audit::event(principal, "link.broken_list"))
+            .await?;
+        database::get_broken_links(
+            &self.db,
+            principal.link_scope(),
+            page.limit(),
+            page.offset(),
+        )
+        .await
+    }
+
+    pub async fn list_urls(
+        &self,
+        principal: &Principal,
+        query: &UrlListQuery,
+    ) -> Result<UrlListResponse> {
+        let filter = UrlFilter {
+            tag: query.tag.as_deref(),
+            collection_id: query.collection_id,
+            metadata_key: query.metadata_key.as_deref(),
+            metadata_value: query.metadata_value.as_deref(),
+            deleted: query.deleted.unwrap_or(false),
+            scope: principal.link_scope(),
+        };
+        if filter.metadata_value.is_some() && filter.metadata_key.is_none() {
+            return Err(AppError::BadRequest(
+                "metadata_value requires metadata_key".to_string(),
+            ));
+        }
+        let page = PageQuery {
+            limit: query.limit,
+            offset: query.offset,
+        };
+        self.audit(audit::event(principal, "link.list")).await?;
+
+        let urls = database::list_urls(
+            &self.db,
+            &filter,
+            query.sort.unwrap_or_default(),
+            page.limit(),
+            page.offset(),
+        )
+        .await?;
+        let (total, _) = database::get_stats_summary(&self.db, &filter).await?;
+
+        Ok(UrlListResponse { total, urls })
+    }
+
+    /// Stats of the links the principal owns, or of every link for admins
+    pub async fn get_stats(
+        &self,
+        principal: &Principal,
+        query: &StatsQuery,
+    ) -> Result<StatsResponse> {
+        let filter = UrlFilter {
+            tag: query.tag.as_deref(),
+            collection_id: query.collection_id,
+            scope: principal.link_scope(),
+            ..Default::default()
+        };
+        self.audit(audit::event(principal, "link.stats")).await?;
+
+        let urls = database::get_url_stats(&self.db, &filter).await?;
+        let (total_urls, total_visits) = database::get_stats_summary(&self.db, &filter).await?;
+        let tags = database::get_tag_stats(&self.db, &filter).await?;
+
+        Ok(StatsResponse {
+            total_urls,
+            total_visits,
+            urls,
+            tags,
+        })
+    }
+
+    pub async fn create_collection(
+        &self,
+        principal: &Principal,
+        request: CreateCollectionRequest,
+    ) -> Result<Collection> {
+        require_admin(principal)?;
+        let name = validate_collection_name(&request.name)?;
+        let description = request.description.as_deref().filter(|s| !s.is_empty());
+
+        let workspace_id = principal.workspace().map(|access| access.workspace_id);
+        let collection = database::create_collection(&self.db, name, description, workspace_id)
+            .await
+            .map_err(|err| {
+                conflict_on_unique_violation(err, "A collection with this name already exists")
+            })?;
+        self.audit(
+            audit::event(principal, "collection.create")
+                .target(collection.id)
+                .changes(diff(None, Some(&collection))),
+        )
+        .await?;
+
+        Ok(collection)
+    }
+
+    /// Collections of the principal's workspace, or those outside any workspace otherwise
+    pub async fn list_collections(&self, principal: &Principal) -> Result<Vec<Collection>> {
+        database::list_collections(&self.db, principal.link_scope()).await
+    }
+
+    pub async fn get_collection(&self, principal: &Principal, id: Uuid) -> Result<Collection> {
+        database::get_collection(&self.db, id, principal.link_scope())
+            .await?
+            .ok_or_else(|| AppError::NotFound("Collection not found".to_string()))
+    }
+
+    pub async fn update_collection(
+        &self,
+        principal: &Principal,
+        id: Uuid,
+        request: UpdateCollectionRequest,
+    ) -> Result<Collection> {
+        require_admin(principal)?;
+        let name = request
+            .name
+            .as_deref()
+            .map(validate_collection_name)
+            .transpose()?;
+
+        let scope = principal.link_scope();
+        let before = database::get_collection(&self.db, id, scope).await?;
+        let collection =
+            database::update_collection(&self.db, id, scope, name, request.description.as_deref())
+                .await
+                .map_err(|err| {
+                    conflict_on_unique_violation(err, "A collection with this name already exists")
+                })?
+                .ok_or_else(|| AppError::NotFound("Collection not found".to_string()))?;
+        self.audit(
+            audit::event(principal, "collection.update")
+                .target(id)
+                .changes(diff(before.as_ref(), Some(&collection))),
+        )
+        .await?;
+
+        Ok(collection)
+    }
+
+    pub async fn delete_collection(&self, principal: &Principal, id: Uuid) -> Result<()> {
+        require_admin(principal)?;
+        let scope = principal.link_scope();
+        let before = database::get_collection(&self.db, id, scope).await?;
+        if !database::delete_collection(&self.db, id, scope).await? {
+            return Err(AppError::NotFound("Collection not found".to_string()));
+        }
+        self.audit(
+            audit::event(principal, "collection.delete")
+                .target(id)
+                .changes(diff(before.as_ref(), None)),
+        )
+        .await
+    }
+
+    /// Queues an import file and returns the job tracking its progress
+    pub async fn start_import(
+        &self,
+        principal: &Principal,
+        format: ImportFormat,
+        data: &[u8],
+    ) -> Result<ImportJob> {
+        require_admin(principal)?;
+        let (job, rows) = self.create_import_job(format, data).await?;
+        self.audit(audit::event(principal, "import.start").target(job.id))
+            .await?;
+
+        let service = self.clone();
+        let principal = principal.clone();
+        tokio::spawn(async move {
+            service.execute_import(&principal, job.id, rows).await;
+        });
+
+        Ok(job)
+    }
+
+    /// Imports a file to completion and returns the finished job, for the command line
+    pub async fn import_file(
+        &self,
+        principal: &Principal,
+        format: ImportFormat,
+        data: &[u8],
+    ) -> Result<ImportJob> {
+        require_admin(principal)?;
+        let (job, rows) = self.create_import_job(format, data).await?;
+        self.audit(audit::event(principal, "import.start").target(job.id))
+            .await?;
+        self.execute_import(principal, job.id, rows).await;
+        self.get_import_job(principal, job.id).await
+    }
+
+    async fn create_import_job(
+        &self,
+        format: ImportFormat,
+        data: &[u8],
+    ) -> Result<(ImportJob, Vec<ImportRow>)> {
+        let rows = link_import::parse(format, data)?;
+        if rows.is_empty() {
+            return Err(AppError::BadRequest(
+                "The import contains no links".to_string(),
+            ));
+        }
+
+        let source = format.as_str();
+        let job = database::create_import_job(&self.db, source, rows.len() as i32).await?;
+        tracing::info!(
+            "Queued {} import {} with {} row(s)",
+            source,
+            job.id,
+            rows.len()
+        );
+
+        Ok((job, rows))
+    }
+
+    /// Runs an import and records whether it completed or failed
+    async fn execute_import(&self, principal: &Principal, job_id: Uuid, rows: Vec<ImportRow>) {
+        let (status, error) = match self.run_import(principal, job_id, rows).await {
+            Ok(()) => (ImportStatus::Completed, None),
+            Err(err) => {
+                tracing::error!("Import {} failed: {}", job_id, err);
+                (
+                    ImportStatus::Failed,
+                    Some(err.user_message(self.get_environment())),
+                )
+            }
+        };
+        if let Err(err) =
+            database::set_import_job_status(&self.db, job_id, status, error.as_deref()).await
+        {
+            tracing::error!("Failed to record the outcome of import {}: {}", job_id, err);
+        }
+    }
+
+    /// Creates the links of an import chunk by chunk, recording progress and failed rows
+    async fn run_import(
+        &self,
+        principal: &Principal,
+        job_id: Uuid,
+        rows: Vec<ImportRow>,
+    ) -> Result<()> {
+        database::set_import_job_status(&self.db, job_id, ImportStatus::Running, None).await?;
+
+        let mut rows = rows.into_iter();
+        loop {
+            let chunk: Vec<ImportRow> = rows.by_ref().take(IMPORT_CHUNK_SIZE).collect();
+            if chunk.is_empty() {
+                break;
+            }
+            let processed = chunk.len() as i32;
+
+            let mut errors = Vec::new();
+            let mut pending = Vec::new();
+            let mut requests = Vec::new();
+            for row in chunk {
+                match row.link {
+                    Ok(link) => {
+                        pending.push((
+                            row.row_number,
+                            row.original_url,
+                            link.request.alias.clone(),
+                            link.created_at,
+                            link.visits,
+                        ));
+                        requests.push(link.request);
+                    }
+                    Err(error) => errors.push(ImportError {
+                        row_number: row.row_number,
+                        original_url: row.original_url,
+                        error,
+                    }),
+                }
+            }
+
+            let (mut created, mut reused) = (0, 0);
+            let mut history = (Vec::new(), Vec::new(), Vec::new());
+            if !requests.is_empty() {
+                let response = self
+                    .create_short_urls(
+                        principal,
+                        BatchCreateUrlRequest {
+                            mode: Some(BatchMode::Partial),
+                            urls: requests,
+                        },
+                    )
+                    .await?;
+                for (result, (row_number, original_url, alias, created_at, visits)) in
+                    response.results.into_iter().zip(pending)
+                {
+                    match (result.url, result.error) {
+                        (_, Some(error)) => errors.push(ImportError {
+                            row_number,
+                            original_url,
+                            // Name the original code so conflicts can be resolved by hand
+                            error: match alias {
+                                Some(alias) => format!("{} ({})", error, alias),
+                                None => error,
+                            },
+                        }),
+                        (Some(url), None) if !url.created => reused += 1,
+                        (Some(url), None) => {
+                            created += 1;
+                            if created_at.is_some() || visits > 0 {
+                                history.0.push(url.id);
+                                history.1.push(created_at);
+                                history.2.push(visits);
+                            }
+                        }
+                        (None, None) => {}
+                    }
+                }
+            }
+
+            if !history.0.is_empty() {
+                database::restore_link_history(&self.db, &history.0, &history.1, &history.2)
+                    .await?;
+            }
+            database::record_import_progress(&self.db, job_id, processed, created, reused, &errors)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_import_job(&self, principal: &Principal, id: Uuid) -> Result<ImportJob> {
+        require_admin(principal)?;
+        self.audit(audit::event(principal, "import.read").target(id))
+            .await?;
+        database::get_import_job(&self.db, id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Import not found".to_string()))
+    }
+
+    pub async fn list_import_jobs(
+        &self,
+        principal: &Principal,
+        page: &PageQuery,
+    ) -> Result<Vec<ImportJob>> {
+        require_admin(principal)?;
+        self.audit(audit::event(principal, "import.list")).await?;
+        database::list_import_jobs(&self.db, page.limit(), page.offset()).await
+    }
+
+    /// Returns the rows of an import that could not be created, as CSV
+    pub async fn get_import_error_report(
+        &self,
+        principal: &Principal,
+        id: Uuid,
+    ) -> Result<Vec<u8>> {
+        // Make sure the job exists, so unknown jobs are not reported as having no errors
+        self.get_import_job(principal, id).await?;
+        let errors = database::get_import_errors(&self.db, id).await?;
+        link_export::import_error_report(&errors)
+    }
+
+    /// Streams the principal's links, or all links for admins, with their stats in the
+    /// given format
+    pub async fn export_urls(
+        &self,
+        principal: &Principal,
+        format: ExportFormat,
+    ) -> Result<impl futures::Stream<Item = Result<axum::body::Bytes>> + Send + 'static> {
+        self.audit(audit::event(principal, "link.export")).await?;
+        Ok(link_export::export_urls(
+            self.db.clone(),
+            self.config.base_url.clone(),
+            format,
+            principal.link_scope(),
+        ))
+    }
+
+    /// Streams a compressed archive of every collection, tag and link
+    pub async fn backup(
+        &self,
+        principal: &Principal,
+    ) -> Result<impl futures::Stream<Item = Result<axum::body::Bytes>> + Send + 'static> {
+        require_admin(principal)?;
+        self.audit(audit::event(principal, "backup.export")).await?;
+        Ok(backup::backup(self.db.clone()))
+    }
+
+    /// Restores a backup archive and drops cached redirects of the links it replaced
+    pub async fn restore(
+        &self,
+        principal: &Principal,
+        archive: &[u8],
+        policy: ConflictPolicy,
+    ) -> Result<RestoreSummary> {
+        require_admin(principal)?;
+        let (summary, stale_codes) = backup::restore(&self.db, archive, policy).await?;
+        tracing::info!("Restored backup: {:?}", summary);
+        self.audit(
+            audit::event(principal, "backup.restore").changes(serde_json::to_value(&summary).ok()),
+        )
+        .await?;
+
+        for short_code in stale_codes {
+            self.cache
+                .delete(&Cache::url_cache_key(&short_code))
+                .await?;
+        }
+
+        Ok(summary)
+    }
+
+    /// Looks up a link the principal may access with at least `role` in its workspace. Links
+    /// of other accounts and workspaces are reported as missing, so their short codes do not
+    /// reveal anything.
+    async fn authorize_url(
+        &self,
+        principal: &Principal,
+        short_code: &str,
+        role: WorkspaceRole,
+    ) -> Result<Uuid> {
+        self.authorize_url_owner(principal, short_code, role)
+            .await
+            .map(|owner| owner.id)
+    }
+
+    /// Like `authorize_url`, but also returns who the link belongs to
+    async fn authorize_url_owner(
+        &self,
+        principal: &Principal,
+        short_code: &str,
+        role: WorkspaceRole,
+    ) -> Result<UrlOwner> {
+        let owner = database::get_url_owner(&self.db, short_code)
+            .await?
+            .filter(|owner| {
+                principal.can_access(owner.owner_id, owner.workspace_id, WorkspaceRole::Viewer)
+            })
+            .ok_or_else(|| AppError::NotFound("URL not found".to_string()))?;
+        require_workspace_role(principal, role)?;
+
+        Ok(owner)
+    }
+
+    /// Whether a link in `workspace_id` may join the collection: the principal must see the
+    /// collection, and it must belong to the same workspace as the link
+    async fn collection_usable(
+        &self,
+        principal: &Principal,
+        id: Uuid,
+        workspace_id: Option<Uuid>,
+    ) -> Result<bool> {
+        let collection = database::get_collection(&self.db, id, principal.link_scope()).await?;
+        Ok(collection.is_some_and(|collection| collection.workspace_id == workspace_id))
+    }
+
+    async fn ensure_collection_usable(
+        &self,
+        principal: &Principal,
+        id: Uuid,
+        workspace_id: Option<Uuid>,
+    ) -> Result<()> {
+        if self.collection_usable(principal, id, workspace_id).await? {
+            Ok(())
+        } else {
+            Err(unusable_collection(id))
+        }
+    }
+
+    pub async fn check_database_connection(&self) -> anyhow::Result<()> {
+        // Simple query to check DB connectivity
+        sqlx::query("SELECT 1")
+            .execute(&self.db)
+            .await
+            .map(|_| ())
+            .map_err(|e| anyhow::anyhow!("Database connection check failed: {}", e))
     }
 
     pub async fn check_cache_connection(&self) -> anyhow::Result<()> {
@@ -146,3 +2212,486 @@ impl UrlService {
             .map_err(|e| anyhow::anyhow!("Redis connection check failed: {}", e))
     }
 }
+
+/// A validated link creation request
+struct PreparedUrl {
+    original_url: String,
+    alias: Option<String>,
+    expires_at: Option<time::OffsetDateTime>,
+    interstitial: bool,
+    require_signature: bool,
+    og_title: Option<String>,
+    og_description: Option<String>,
+    og_image: Option<String>,
+    fallback_url: Option<String>,
+    collection_id: Option<Uuid>,
+    title: Option<String>,
+    description: Option<String>,
+    metadata: serde_json::Value,
+    tags: Vec<String>,
+    target_hash: String,
+    dedupe: bool,
+    owner_id: Option<Uuid>,
+    workspace_id: Option<Uuid>,
+    /// Recorded as the author of the first revision
+    changed_by: String,
+}
+
+/// Inserts a link with its tags, or returns an existing one when deduplication applies.
+/// The flag is `true` when a new link was created.
+async fn insert_prepared_url(
+    conn: &mut PgConnection,
+    prepared: &PreparedUrl,
+) -> Result<(Url, bool)> {
+    let short_code = prepared.alias.clone().unwrap_or_else(|| nanoid!(8));
+    let new_url = NewUrl {
+        original_url: &prepared.original_url,
+        short_code: &short_code,
+        expires_at: prepared.expires_at,
+        interstitial: prepared.interstitial,
+        require_signature: prepared.require_signature,
+        og_title: prepared.og_title.as_deref(),
+        og_description: prepared.og_description.as_deref(),
+        og_image: prepared.og_image.as_deref(),
+        fallback_url: prepared.fallback_url.as_deref(),
+        collection_id: prepared.collection_id,
+        title: prepared.title.as_deref(),
+        description: prepared.description.as_deref(),
+        metadata: &prepared.metadata,
+        target_hash: &prepared.target_hash,
+        owner_id: prepared.owner_id,
+        workspace_id: prepared.workspace_id,
+    };
+
+    if prepared.dedupe {
+        database::lock_target_hash(conn, &prepared.target_hash).await?;
+        if let Some(url_id) = database::find_reusable_url(conn, &new_url, &prepared.tags).await? {
+            let url = database::get_url_by_id(&mut *conn, url_id)
+                .await?
+                .ok_or_else(|| AppError::Internal("Reused URL not found".to_string()))?;
+            tracing::debug!(
+                "Reusing short code {} for {}",
+                url.short_code,
+                url.original_url
+            );
+            return Ok((url, false));
+        }
+    }
+
+    // Codes of cleaned up links stay taken during their quarantine, so old links to them
+    // cannot silently lead somewhere else
+    if database::is_short_code_retired(conn, &short_code).await? {
+        return Err(AppError::Conflict(
+            "This short code is already taken".to_string(),
+        ));
+    }
+    let url_id = database::create_url(conn, &new_url)
+        .await
+        .map_err(|err| conflict_on_unique_violation(err, "This short code is already taken"))?;
+    database::set_url_tags(conn, url_id, &prepared.tags).await?;
+    database::record_revision(
+        conn,
+        url_id,
+        RevisionChange::Created,
+        Some(&prepared.changed_by),
+    )
+    .await?;
+    let url = database::get_url_by_id(&mut *conn, url_id)
+        .await?
+        .ok_or_else(|| AppError::Internal("Created URL not found".to_string()))?;
+
+    Ok((url, true))
+}
+
+/// Checks the role of a principal acting in a workspace; others are not limited by roles
+fn require_workspace_role(principal: &Principal, role: WorkspaceRole) -> Result<()> {
+    match principal.workspace() {
+        Some(access) => check_role(access.role, role),
+        None => Ok(()),
+    }
+}
+
+fn check_role(role: WorkspaceRole, required: WorkspaceRole) -> Result<()> {
+    if role >= required {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden(format!(
+            "This requires the {} role in the workspace",
+            required.as_str()
+        )))
+    }
+}
+
+/// Fails unless the workspace has an owner besides the one about to be demoted or removed
+async fn ensure_other_owner(conn: &mut PgConnection, workspace_id: Uuid) -> Result<()> {
+    if database::count_owners(conn, workspace_id).await? <= 1 {
+        return Err(AppError::Conflict(
+            "A workspace needs at least one owner".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn unusable_collection(id: Uuid) -> AppError {
+    AppError::BadRequest(format!(
+        "Collection {} does not exist or belongs to another workspace",
+        id
+    ))
+}
+
+fn require_admin(principal: &Principal) -> Result<()> {
+    if principal.is_admin() {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden("Admin access required".to_string()))
+    }
+}
+
+pub fn validate_alias(alias: &str) -> Result<()> {
+    let valid_chars = alias
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid_chars || !(MIN_ALIAS_LENGTH..=MAX_ALIAS_LENGTH).contains(&alias.len()) {
+        return Err(AppError::BadRequest(format!(
+            "Aliases must be {} to {} letters, digits, '-' or '_'",
+            MIN_ALIAS_LENGTH, MAX_ALIAS_LENGTH
+        )));
+    }
+    if RESERVED_ALIASES.contains(&alias.to_ascii_lowercase().as_str()) {
+        return Err(AppError::BadRequest(format!(
+            "The alias {} is reserved",
+            alias
+        )));
+    }
+    Ok(())
+}
+
+/// Open Graph images must be absolute http(s) URLs; an empty value clears the override
+fn validate_image_url(value: &str) -> Result<()> {
+    validate_http_url("og_image", value)
+}
+
+/// Fallback destinations must be absolute http(s) URLs; an empty value removes the fallback
+fn validate_fallback_url(value: &str) -> Result<()> {
+    validate_http_url("fallback_url", value)
+}
+
+fn validate_http_url(field: &str, value: &str) -> Result<()> {
+    if value.is_empty() {
+        return Ok(());
+    }
+
+    match ::url::Url::parse(value) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => Ok(()),
+        _ => Err(AppError::BadRequest(format!(
+            "{} must be an absolute http(s) URL",
+            field
+        ))),
+    }
+}
+
+/// Hashes a destination after normalizing its scheme, host, port and percent-encoding,
+/// so trivially different spellings of the same URL are treated as one target
+pub fn target_hash(original_url: &str) -> String {
+    let normalized = match ::url::Url::parse(original_url.trim()) {
+        Ok(url) => url.to_string(),
+        Err(_) => original_url.trim().to_string(),
+    };
+    format!("{:x}", Sha256::digest(normalized.as_bytes()))
+}
+
+/// Trims, lowercases and deduplicates tags, rejecting empty or overly long ones
+fn normalize_tags(tags: &[String]) -> Result<Vec<String>> {
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        if tag.is_empty() || tag.chars().count() > MAX_TAG_LENGTH {
+            return Err(AppError::BadRequest(format!(
+                "Tags must be between 1 and {} characters",
+                MAX_TAG_LENGTH
+            )));
+        }
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+
+    if normalized.len() > MAX_TAGS_PER_URL {
+        return Err(AppError::BadRequest(format!(
+            "A link can have at most {} tags",
+            MAX_TAGS_PER_URL
+        )));
+    }
+
+    Ok(normalized)
+}
+
+fn validate_notes(title: Option<&str>, description: Option<&str>) -> Result<()> {
+    if title.is_some_and(|title| title.chars().count() > MAX_TITLE_LENGTH) {
+        return Err(AppError::BadRequest(format!(
+            "title must be at most {} characters",
+            MAX_TITLE_LENGTH
+        )));
+    }
+    if description.is_some_and(|description| description.chars().count() > MAX_DESCRIPTION_LENGTH) {
+        return Err(AppError::BadRequest(format!(
+            "description must be at most {} characters",
+            MAX_DESCRIPTION_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+/// Metadata must be a JSON object of bounded size
+fn validate_metadata(metadata: serde_json::Value) -> Result<serde_json::Value> {
+    match metadata {
+        serde_json::Value::Object(_) if metadata.to_string().len() <= MAX_METADATA_BYTES => {
+            Ok(metadata)
+        }
+        serde_json::Value::Object(_) => Err(AppError::BadRequest(format!(
+            "metadata must be at most {} bytes",
+            MAX_METADATA_BYTES
+        ))),
+        _ => Err(AppError::BadRequest(
+            "metadata must be a JSON object".to_string(),
+        )),
+    }
+}
+
+fn validate_collection_name(name: &str) -> Result<&str> {
+    validate_name(name, "Collection")
+}
+
+fn validate_name<'a>(name: &'a str, kind: &str) -> Result<&'a str> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(AppError::BadRequest(format!(
+            "{} names must be between 1 and {} characters",
+            kind, MAX_NAME_LENGTH
+        )));
+    }
+    Ok(name)
+}
+
+/// Turns a unique constraint violation into a conflict error with a friendly message
+fn conflict_on_unique_violation(err: AppError, message: &str) -> AppError {
+    match err {
+        AppError::Database(sqlx::Error::Database(ref db_err)) if db_err.is_unique_violation() => {
+            AppError::Conflict(message.to_string())
+        }
+        err => err,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use serde_json::Value;
+
+    async fn service(pool: &PgPool) -> UrlService {
+        crate::infrastructure::migrations::run_migrations_if_needed(pool)
+            .await
+            .unwrap();
+        UrlService::new(
+            AppConfig::for_tests(),
+            pool.clone(),
+            Cache::connect_for_tests().await,
+            None,
+        )
+    }
+
+    /// A request parsed from JSON the way the API receives it
+    fn request<T: serde::de::DeserializeOwned>(body: Value) -> T {
+        serde_json::from_value(body).unwrap()
+    }
+
+    /// Creates a link as the server's API key and returns its short code
+    async fn create_link(service: &UrlService, url: &str) -> String {
+        let response = service
+            .create_short_url(&Principal::ApiKey, request(json!({ "url": url })))
+            .await
+            .unwrap();
+        response.short_url.rsplit('/').next().unwrap().to_string()
+    }
+
+    /// An admin account acting as owner of a new workspace
+    async fn workspace_admin(pool: &PgPool) -> Principal {
+        let email = format!("{}@example.com", Uuid::new_v4());
+        let user = database::create_user(pool, &email, "hash", true)
+            .await
+            .unwrap();
+        let workspace_id: Uuid =
+            sqlx::query_scalar("INSERT INTO workspaces (name) VALUES ('team') RETURNING id")
+                .fetch_one(pool)
+                .await
+                .unwrap();
+        Principal::Member(
+            user,
+            WorkspaceAccess {
+                workspace_id,
+                role: WorkspaceRole::Owner,
+            },
+        )
+    }
+
+    fn first_page() -> PageQuery {
+        PageQuery {
+            limit: None,
+            offset: None,
+        }
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn signature_requirement_is_revisioned_and_rolled_back(pool: PgPool) {
+        let service = service(&pool).await;
+        let code = create_link(&service, "https://example.com/signed").await;
+
+        let url = service
+            .update_url(
+                &Principal::ApiKey,
+                &code,
+                request(json!({ "require_signature": true })),
+            )
+            .await
+            .unwrap();
+        assert!(url.require_signature);
+        let history = service
+            .get_url_history(&Principal::ApiKey, &code, &first_page())
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 2);
+        assert!(history[0].require_signature);
+        assert!(!history[1].require_signature);
+
+        let url = service
+            .rollback_url(&Principal::ApiKey, &code, 1)
+            .await
+            .unwrap();
+        assert!(!url.require_signature);
+        let history = service
+            .get_url_history(&Principal::ApiKey, &code, &first_page())
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].change, RevisionChange::Rollback);
+        assert!(!history[0].require_signature);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn deleting_and_restoring_links_is_audited(pool: PgPool) {
+        let service = service(&pool).await;
+        let code = create_link(&service, "https://example.com/trash").await;
+
+        service.delete_url(&Principal::ApiKey, &code).await.unwrap();
+        service
+            .restore_deleted_url(&Principal::ApiKey, &code)
+            .await
+            .unwrap();
+
+        let actions: Vec<String> =
+            sqlx::query_scalar("SELECT action FROM audit_events WHERE target_id = $1 ORDER BY id")
+                .bind(&code)
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(actions, ["link.create", "link.delete", "link.restore"]);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn batches_count_reused_links_apart_from_new_ones(pool: PgPool) {
+        let service = service(&pool).await;
+        let existing = json!({ "url": "https://example.com/reused", "dedupe": true });
+        service
+            .create_short_url(&Principal::ApiKey, request(existing.clone()))
+            .await
+            .unwrap();
+
+        let response = service
+            .create_short_urls(
+                &Principal::ApiKey,
+                request(json!({
+                    "mode": "partial",
+                    "urls": [
+                        existing,
+                        { "url": "https://example.com/new" },
+                        { "url": "https://example.com/taken", "alias": "api" }
+                    ]
+                })),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            (response.created, response.reused, response.failed),
+            (1, 1, 1)
+        );
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn collections_stay_in_their_workspace(pool: PgPool) {
+        let service = service(&pool).await;
+        let (team_a, team_b) = (workspace_admin(&pool).await, workspace_admin(&pool).await);
+        let ours = service
+            .create_collection(&team_a, request(json!({ "name": "Campaign" })))
+            .await
+            .unwrap();
+        let theirs = service
+            .create_collection(&team_b, request(json!({ "name": "Campaign" })))
+            .await
+            .unwrap();
+
+        let listed = service.list_collections(&team_a).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, ours.id);
+        assert_matches!(
+            service.get_collection(&team_a, theirs.id).await,
+            Err(AppError::NotFound(_))
+        );
+        let personal = Principal::User(
+            database::create_user(&pool, "solo@example.com", "hash", false)
+                .await
+                .unwrap(),
+        );
+        assert!(service
+            .list_collections(&personal)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let result = service
+            .create_short_url(
+                &team_a,
+                request(json!({ "url": "https://example.com/a", "collection_id": theirs.id })),
+            )
+            .await;
+        assert_matches!(result, Err(AppError::BadRequest(_)));
+        let created = service
+            .create_short_url(
+                &team_a,
+                request(json!({ "url": "https://example.com/a", "collection_id": ours.id })),
+            )
+            .await
+            .unwrap();
+        let code = created.short_url.rsplit('/').next().unwrap();
+        let result = service
+            .update_url(
+                &team_a,
+                code,
+                request(json!({ "collection_id": theirs.id })),
+            )
+            .await;
+        assert_matches!(result, Err(AppError::BadRequest(_)));
+
+        // Server admins see every collection, but links outside a workspace cannot join one
+        let unscoped = create_link(&service, "https://example.com/unscoped").await;
+        let result = service
+            .update_url(
+                &Principal::ApiKey,
+                &unscoped,
+                request(json!({ "collection_id": ours.id })),
+            )
+            .await;
+        assert_matches!(result, Err(AppError::BadRequest(_)));
+    }
+}
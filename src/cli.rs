@@ -0,0 +1,136 @@
+use std::io::Write;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail};
+use futures::StreamExt;
+
+use crate::{
+    application::url_service::UrlService,
+    domain::{
+        transfer::{ConflictPolicy, ImportFormat},
+        user::Principal,
+    },
+};
+
+const USAGE: &str = "Usage:
+  tommy-shortener [--dev|--prod]                          Start the server
+  tommy-shortener import <csv|yourls|bitly|kutt> <file>   Import links from a file
+  tommy-shortener backup <file>                           Write a backup archive
+  tommy-shortener restore <file> [fail|skip|overwrite]    Restore a backup archive";
+
+/// What the binary was asked to do
+#[derive(Debug)]
+pub enum Command {
+    Serve,
+    Import {
+        format: ImportFormat,
+        path: String,
+    },
+    Backup {
+        path: String,
+    },
+    Restore {
+        path: String,
+        policy: ConflictPolicy,
+    },
+}
+
+impl Command {
+    /// Reads the subcommand from the command line. Flags such as `--prod` are handled by the
+    /// configuration and are skipped here.
+    pub fn from_args() -> anyhow::Result<Self> {
+        let args: Vec<String> = std::env::args()
+            .skip(1)
+            .filter(|arg| !arg.starts_with("--"))
+            .collect();
+
+        match args
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .as_slice()
+        {
+            [] => Ok(Command::Serve),
+            ["import", format, path] => Ok(Command::Import {
+                format: ImportFormat::from_str(format)
+                    .map_err(|e| anyhow!("{}\n\n{}", e, USAGE))?,
+                path: path.to_string(),
+            }),
+            ["backup", path] => Ok(Command::Backup {
+                path: path.to_string(),
+            }),
+            ["restore", path] => Ok(Command::Restore {
+                path: path.to_string(),
+                policy: ConflictPolicy::default(),
+            }),
+            ["restore", path, policy] => Ok(Command::Restore {
+                path: path.to_string(),
+                policy: ConflictPolicy::from_str(policy)
+                    .map_err(|e| anyhow!("{}\n\n{}", e, USAGE))?,
+            }),
+            _ => bail!("Unknown command: {}\n\n{}", args.join(" "), USAGE),
+        }
+    }
+}
+
+/// Imports a file to completion, printing a summary and the rows that failed
+pub async fn import(service: &UrlService, format: ImportFormat, path: &str) -> anyhow::Result<()> {
+    let data = std::fs::read(path).map_err(|e| anyhow!("Failed to read {}: {}", path, e))?;
+
+    let job = service
+        .import_file(&Principal::ApiKey, format, &data)
+        .await?;
+    println!(
+        "Import {} {:?}: {} of {} row(s) imported, {} matched existing links, {} failed",
+        job.id, job.status, job.created_count, job.total_rows, job.reused_count, job.failed_count
+    );
+    if let Some(error) = &job.error {
+        eprintln!("Import failed: {}", error);
+    }
+
+    if job.failed_count > 0 {
+        eprint!(
+            "{}",
+            String::from_utf8_lossy(
+                &service
+                    .get_import_error_report(&Principal::ApiKey, job.id)
+                    .await?
+            )
+        );
+    }
+
+    Ok(())
+}
+
+/// Writes a backup archive to a file
+pub async fn backup(service: &UrlService, path: &str) -> anyhow::Result<()> {
+    let mut file =
+        std::fs::File::create(path).map_err(|e| anyhow!("Failed to create {}: {}", path, e))?;
+
+    let mut archive = Box::pin(service.backup(&Principal::ApiKey).await?);
+    let mut bytes = 0;
+    while let Some(chunk) = archive.next().await {
+        let chunk = chunk?;
+        bytes += chunk.len();
+        file.write_all(&chunk)?;
+    }
+    file.sync_all()?;
+
+    println!("Wrote backup of {} byte(s) to {}", bytes, path);
+    Ok(())
+}
+
+/// Restores a backup archive from a file
+pub async fn restore(
+    service: &UrlService,
+    path: &str,
+    policy: ConflictPolicy,
+) -> anyhow::Result<()> {
+    let archive = std::fs::read(path).map_err(|e| anyhow!("Failed to read {}: {}", path, e))?;
+
+    let summary = service
+        .restore(&Principal::ApiKey, &archive, policy)
+        .await?;
+    println!("{}", serde_json::to_string_pretty(&summary)?);
+    Ok(())
+}
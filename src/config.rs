@@ -1,9 +1,14 @@
 use anyhow::Context;
+use ipnet::IpNet;
 use std::fmt;
 use std::fs;
+use std::net::IpAddr;
 use std::path::Path;
 use std::str::FromStr;
 
+// Secrets shorter than this could be brute forced from a signed link
+const MIN_SIGNING_SECRET_LENGTH: usize = 32;
+
 #[derive(Clone, Debug)]
 pub struct AppConfig {
     pub database_url: String,
@@ -11,6 +16,123 @@ pub struct AppConfig {
     pub api_key: String,
     pub base_url: String,
     pub environment: Environment,
+    /// Destination domains that redirect without a warning page; empty disables the policy
+    pub interstitial_allowed_domains: Vec<String>,
+    /// Seconds the warning page counts down before the visitor can continue
+    pub interstitial_countdown_secs: u32,
+    /// Fetch title, favicon and status of new destinations in the background
+    pub metadata_fetch_enabled: bool,
+    pub metadata_fetch_concurrency: usize,
+    /// Timeout for a whole outbound fetch, including redirects
+    pub fetch_timeout_secs: u64,
+    /// Maximum number of response body bytes read from a destination
+    pub fetch_max_body_bytes: usize,
+    /// Allow outbound fetches to private and loopback addresses (for local testing only)
+    pub fetch_allow_private_ips: bool,
+    /// Periodically re-check link destinations for broken links
+    pub link_check_enabled: bool,
+    pub link_check_interval_secs: u64,
+    /// Cron expression (UTC) replacing the interval, e.g. `0 3 * * *`
+    pub link_check_cron: Option<String>,
+    pub link_check_batch_size: i64,
+    /// Minimum time between two checks of the same link
+    pub link_check_recheck_secs: u64,
+    /// Number of destination hosts checked in parallel
+    pub link_check_concurrency: usize,
+    /// Pause between two requests to the same host
+    pub link_check_delay_ms: u64,
+    /// Consecutive failed checks before a broken link is switched to its fallback
+    pub link_check_failure_threshold: i32,
+    pub link_check_auto_fallback: bool,
+    /// Return an existing link for the same destination and options instead of creating
+    /// a new one, unless the request says otherwise
+    pub dedupe_targets: bool,
+    /// How long responses to requests with an `Idempotency-Key` are kept for replay
+    pub idempotency_ttl_secs: u64,
+    /// Days a deleted link stays in the trash before it is purged
+    pub trash_retention_days: u64,
+    pub trash_purge_interval_secs: u64,
+    pub trash_purge_cron: Option<String>,
+    pub expired_cleanup_enabled: bool,
+    pub expired_cleanup_interval_secs: u64,
+    pub expired_cleanup_cron: Option<String>,
+    /// Days past their expiry before links are cleaned up
+    pub expired_grace_days: u64,
+    /// Copy cleaned up links to `archived_urls` instead of only deleting them
+    pub expired_archive: bool,
+    /// Days before the short code of a cleaned up or purged link can be taken again; never
+    /// when unset
+    pub expired_code_reuse_days: Option<u64>,
+    /// Let anyone create an account; otherwise only admins can
+    pub allow_signup: bool,
+    /// How long a login session stays valid
+    pub session_ttl_hours: u64,
+    /// How long a workspace invitation can be accepted
+    pub invitation_ttl_hours: u64,
+    pub auth_mode: AuthMode,
+    /// Where the identity provider publishes its signing keys, when bearer tokens are JWTs
+    pub jwt_jwks_url: Option<String>,
+    /// A local JWKS document used instead of `jwt_jwks_url`
+    pub jwt_jwks_file: Option<String>,
+    /// Required `iss` claim, if set
+    pub jwt_issuer: Option<String>,
+    /// Required `aud` claim, if set
+    pub jwt_audience: Option<String>,
+    /// Claim holding the account's email address; dots separate nested claims
+    pub jwt_email_claim: String,
+    /// Claim holding the roles, as a list or a space separated string
+    pub jwt_roles_claim: String,
+    /// Role that makes the account an admin
+    pub jwt_admin_role: String,
+    /// Link tokens to existing accounts by email even without an `email_verified: true`
+    /// claim, for identity providers that only issue tokens for verified addresses
+    pub jwt_trust_email: bool,
+    /// How long fetched signing keys are used before they are fetched again
+    pub jwks_refresh_secs: u64,
+    /// Clock skew allowed when checking `exp` and `nbf`
+    pub jwt_leeway_secs: u64,
+    /// Proxies whose `Forwarded` and `X-Forwarded-For` headers are believed when working
+    /// out the client's address
+    pub trusted_proxies: Vec<IpNet>,
+    /// Client addresses the instance-wide admin endpoints accept requests from; empty allows any
+    pub admin_allowed_ips: Vec<IpNet>,
+    /// Secrets signed links are signed and checked with, by key ID
+    pub link_signing_keys: Vec<(String, String)>,
+    /// Key new signed links are signed with; the first key when unset
+    pub link_signing_key_id: Option<String>,
+    /// Longest validity of a signed link
+    pub signed_link_max_ttl_secs: u64,
+}
+
+/// How API clients authenticate
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AuthMode {
+    /// The server's `X-API-Key`, alongside sessions and workspace API keys
+    ApiKey,
+    /// JWT bearer tokens from an identity provider replace the server's API key.
+    /// Sessions and workspace API keys keep working.
+    Jwt,
+}
+
+impl fmt::Display for AuthMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuthMode::ApiKey => write!(f, "api_key"),
+            AuthMode::Jwt => write!(f, "jwt"),
+        }
+    }
+}
+
+impl FromStr for AuthMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "api_key" | "api-key" => Ok(AuthMode::ApiKey),
+            "jwt" | "oidc" => Ok(AuthMode::Jwt),
+            _ => Err(format!("Unknown authentication mode: {}", s)),
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -60,16 +182,221 @@ impl AppConfig {
         // Log the active environment
         tracing::info!("Application running in {} mode", environment);
 
+        let auth_mode = match std::env::var("AUTH_MODE") {
+            Ok(mode) => mode.trim().parse().map_err(anyhow::Error::msg)?,
+            Err(_) => AuthMode::ApiKey,
+        };
+        let jwt_jwks_url = std::env::var("JWT_JWKS_URL").ok();
+        let jwt_jwks_file = std::env::var("JWT_JWKS_FILE").ok();
+        if auth_mode == AuthMode::Jwt && jwt_jwks_url.is_none() == jwt_jwks_file.is_none() {
+            anyhow::bail!("AUTH_MODE=jwt needs exactly one of JWT_JWKS_URL and JWT_JWKS_FILE");
+        }
+        let link_signing_keys = Self::signing_keys()?;
+        let link_signing_key_id = match std::env::var("LINK_SIGNING_KEY_ID") {
+            Ok(id) if link_signing_keys.iter().any(|(key_id, _)| *key_id == id) => Some(id),
+            Ok(id) => anyhow::bail!("LINK_SIGNING_KEY_ID {} is not in LINK_SIGNING_KEYS", id),
+            Err(_) => link_signing_keys.first().map(|(id, _)| id.clone()),
+        };
+        // The server's API key is not used when an identity provider authenticates clients
+        let api_key = match auth_mode {
+            AuthMode::ApiKey => std::env::var("API_KEY").context("API_KEY must be set")?,
+            AuthMode::Jwt => String::new(),
+        };
+
         Ok(Self {
             database_url: std::env::var("DATABASE_URL").context("DATABASE_URL must be set")?,
             redis_url: std::env::var("REDIS_URL").context("REDIS_URL must be set")?,
-            api_key: std::env::var("API_KEY").context("API_KEY must be set")?,
+            api_key,
             base_url: std::env::var("BASE_URL")
                 .unwrap_or_else(|_| "http://localhost:3000".to_string()),
             environment,
+            interstitial_allowed_domains: std::env::var("INTERSTITIAL_ALLOWED_DOMAINS")
+                .map(|domains| Self::parse_list(&domains))
+                .unwrap_or_default(),
+            interstitial_countdown_secs: Self::env_or("INTERSTITIAL_COUNTDOWN_SECS", 5),
+            metadata_fetch_enabled: Self::env_or("METADATA_FETCH_ENABLED", true),
+            metadata_fetch_concurrency: Self::env_or("METADATA_FETCH_CONCURRENCY", 4),
+            fetch_timeout_secs: Self::env_or("FETCH_TIMEOUT_SECS", 5),
+            fetch_max_body_bytes: Self::env_or("FETCH_MAX_BODY_BYTES", 512 * 1024),
+            fetch_allow_private_ips: Self::env_or("FETCH_ALLOW_PRIVATE_IPS", false),
+            link_check_enabled: Self::env_or("LINK_CHECK_ENABLED", false),
+            link_check_interval_secs: Self::env_or("LINK_CHECK_INTERVAL_SECS", 3600),
+            link_check_cron: std::env::var("LINK_CHECK_CRON").ok(),
+            link_check_batch_size: Self::env_or("LINK_CHECK_BATCH_SIZE", 500),
+            link_check_recheck_secs: Self::env_or("LINK_CHECK_RECHECK_SECS", 86400),
+            link_check_concurrency: Self::env_or("LINK_CHECK_CONCURRENCY", 4),
+            link_check_delay_ms: Self::env_or("LINK_CHECK_DELAY_MS", 1000),
+            link_check_failure_threshold: Self::env_or("LINK_CHECK_FAILURE_THRESHOLD", 3),
+            link_check_auto_fallback: Self::env_or("LINK_CHECK_AUTO_FALLBACK", false),
+            dedupe_targets: Self::env_or("DEDUPE_TARGETS", false),
+            idempotency_ttl_secs: Self::env_or("IDEMPOTENCY_TTL_SECS", 86400),
+            trash_retention_days: Self::env_or("TRASH_RETENTION_DAYS", 30),
+            trash_purge_interval_secs: Self::env_or("TRASH_PURGE_INTERVAL_SECS", 3600),
+            trash_purge_cron: std::env::var("TRASH_PURGE_CRON").ok(),
+            expired_cleanup_enabled: Self::env_or("EXPIRED_CLEANUP_ENABLED", true),
+            expired_cleanup_interval_secs: Self::env_or("EXPIRED_CLEANUP_INTERVAL_SECS", 3600),
+            expired_cleanup_cron: std::env::var("EXPIRED_CLEANUP_CRON").ok(),
+            expired_grace_days: Self::env_or("EXPIRED_GRACE_DAYS", 7),
+            expired_archive: Self::env_or("EXPIRED_ARCHIVE", true),
+            expired_code_reuse_days: std::env::var("EXPIRED_CODE_REUSE_DAYS")
+                .ok()
+                .and_then(|value| value.trim().parse().ok()),
+            allow_signup: Self::env_or("ALLOW_SIGNUP", false),
+            session_ttl_hours: Self::env_or("SESSION_TTL_HOURS", 168),
+            invitation_ttl_hours: Self::env_or("INVITATION_TTL_HOURS", 168),
+            auth_mode,
+            jwt_jwks_url,
+            jwt_jwks_file,
+            jwt_issuer: std::env::var("JWT_ISSUER").ok(),
+            jwt_audience: std::env::var("JWT_AUDIENCE").ok(),
+            jwt_email_claim: Self::env_or("JWT_EMAIL_CLAIM", "email".to_string()),
+            jwt_roles_claim: Self::env_or("JWT_ROLES_CLAIM", "roles".to_string()),
+            jwt_admin_role: Self::env_or("JWT_ADMIN_ROLE", "admin".to_string()),
+            jwt_trust_email: Self::env_or("JWT_TRUST_EMAIL", false),
+            jwks_refresh_secs: Self::env_or("JWKS_REFRESH_SECS", 3600),
+            jwt_leeway_secs: Self::env_or("JWT_LEEWAY_SECS", 60),
+            trusted_proxies: Self::env_networks("TRUSTED_PROXIES")?,
+            admin_allowed_ips: Self::env_networks("ADMIN_ALLOWED_IPS")?,
+            link_signing_keys,
+            link_signing_key_id,
+            signed_link_max_ttl_secs: Self::env_or("SIGNED_LINK_MAX_TTL_SECS", 30 * 86400),
         })
     }
 
+    /// The defaults of every setting, with background work switched off and a signing key
+    #[cfg(test)]
+    pub fn for_tests() -> Self {
+        Self {
+            database_url: String::new(),
+            redis_url: String::new(),
+            api_key: "test-api-key".to_string(),
+            base_url: "http://localhost:3000".to_string(),
+            environment: Environment::Development,
+            interstitial_allowed_domains: Vec::new(),
+            interstitial_countdown_secs: 5,
+            metadata_fetch_enabled: false,
+            metadata_fetch_concurrency: 1,
+            fetch_timeout_secs: 5,
+            fetch_max_body_bytes: 512 * 1024,
+            fetch_allow_private_ips: false,
+            link_check_enabled: false,
+            link_check_interval_secs: 3600,
+            link_check_cron: None,
+            link_check_batch_size: 500,
+            link_check_recheck_secs: 86400,
+            link_check_concurrency: 1,
+            link_check_delay_ms: 0,
+            link_check_failure_threshold: 3,
+            link_check_auto_fallback: false,
+            dedupe_targets: false,
+            idempotency_ttl_secs: 86400,
+            trash_retention_days: 30,
+            trash_purge_interval_secs: 3600,
+            trash_purge_cron: None,
+            expired_cleanup_enabled: false,
+            expired_cleanup_interval_secs: 3600,
+            expired_cleanup_cron: None,
+            expired_grace_days: 7,
+            expired_archive: true,
+            expired_code_reuse_days: None,
+            allow_signup: false,
+            session_ttl_hours: 168,
+            invitation_ttl_hours: 168,
+            auth_mode: AuthMode::ApiKey,
+            jwt_jwks_url: None,
+            jwt_jwks_file: None,
+            jwt_issuer: None,
+            jwt_audience: None,
+            jwt_email_claim: "email".to_string(),
+            jwt_roles_claim: "roles".to_string(),
+            jwt_admin_role: "admin".to_string(),
+            jwt_trust_email: false,
+            jwks_refresh_secs: 3600,
+            jwt_leeway_secs: 60,
+            trusted_proxies: Vec::new(),
+            admin_allowed_ips: Vec::new(),
+            link_signing_keys: vec![(
+                "test".to_string(),
+                "a-test-secret-of-at-least-32-bytes".to_string(),
+            )],
+            link_signing_key_id: Some("test".to_string()),
+            signed_link_max_ttl_secs: 30 * 86400,
+        }
+    }
+
+    /// Parses `LINK_SIGNING_KEYS`, a comma separated list of `<key id>:<secret>` pairs.
+    /// Rotating keys means adding a new one, signing with it through `LINK_SIGNING_KEY_ID`
+    /// and removing the old one once the links signed with it have expired.
+    fn signing_keys() -> anyhow::Result<Vec<(String, String)>> {
+        let Ok(value) = std::env::var("LINK_SIGNING_KEYS") else {
+            return Ok(Vec::new());
+        };
+        let mut keys: Vec<(String, String)> = Vec::new();
+        for entry in value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let Some((id, secret)) = entry.split_once(':') else {
+                anyhow::bail!("LINK_SIGNING_KEYS entries must look like <key id>:<secret>");
+            };
+            let id = id.trim();
+            if id.is_empty()
+                || !id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                anyhow::bail!("Invalid link signing key ID: {}", id);
+            }
+            if secret.len() < MIN_SIGNING_SECRET_LENGTH {
+                anyhow::bail!(
+                    "The link signing key {} must be at least {} characters",
+                    id,
+                    MIN_SIGNING_SECRET_LENGTH
+                );
+            }
+            if keys.iter().any(|(other, _)| other == id) {
+                anyhow::bail!("Duplicate link signing key ID: {}", id);
+            }
+            keys.push((id.to_string(), secret.to_string()));
+        }
+
+        Ok(keys)
+    }
+
+    /// Parses an optional comma separated list of CIDRs or single addresses. Unlike other
+    /// settings, invalid entries fail startup, as ignoring them could open up access.
+    fn env_networks(name: &str) -> anyhow::Result<Vec<IpNet>> {
+        let Ok(value) = std::env::var(name) else {
+            return Ok(Vec::new());
+        };
+        Self::parse_list(&value)
+            .iter()
+            .map(|item| {
+                item.parse::<IpNet>()
+                    .or_else(|_| item.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| anyhow::anyhow!("{} contains an invalid network: {}", name, item))
+            })
+            .collect()
+    }
+
+    /// Reads and parses an optional environment variable, falling back to a default
+    fn env_or<T: FromStr>(name: &str, default: T) -> T {
+        std::env::var(name)
+            .ok()
+            .and_then(|value| value.trim().parse().ok())
+            .unwrap_or(default)
+    }
+
+    /// Parses a comma separated list, ignoring empty entries
+    fn parse_list(value: &str) -> Vec<String> {
+        value
+            .split(',')
+            .map(|item| item.trim().to_lowercase())
+            .filter(|item| !item.is_empty())
+            .collect()
+    }
+
     fn check_command_line_args() -> Option<Environment> {
         let args: Vec<String> = std::env::args().collect();
         if args.len() > 1 {
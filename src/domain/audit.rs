@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+/// A recorded action
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AuditEvent {
+    pub id: i64,
+    pub occurred_at: OffsetDateTime,
+    /// Who acted, e.g. an email address, `api` or `workspace-key:<id>`
+    pub actor: String,
+    /// The actor's account, if any
+    pub actor_id: Option<Uuid>,
+    /// `<target type>.<verb>`, e.g. `link.update`
+    pub action: String,
+    pub target_type: String,
+    pub target_id: Option<String>,
+    /// The workspace the actor acted in
+    pub workspace_id: Option<Uuid>,
+    /// Changed fields as `{"field": {"before": ..., "after": ...}}`, or other details
+    #[schema(value_type = Option<Object>)]
+    pub changes: Option<serde_json::Value>,
+    pub client_ip: Option<String>,
+    pub request_id: Option<String>,
+}
+
+/// An action about to be recorded
+#[derive(Debug)]
+pub struct NewAuditEvent {
+    pub actor: String,
+    pub actor_id: Option<Uuid>,
+    pub action: &'static str,
+    pub target_id: Option<String>,
+    pub workspace_id: Option<Uuid>,
+    pub changes: Option<serde_json::Value>,
+    pub client_ip: Option<String>,
+    pub request_id: Option<String>,
+}
+
+impl NewAuditEvent {
+    /// The part of the action before the verb
+    pub fn target_type(&self) -> &'static str {
+        self.action.split('.').next().unwrap_or(self.action)
+    }
+
+    pub fn target(mut self, target_id: impl ToString) -> Self {
+        self.target_id = Some(target_id.to_string());
+        self
+    }
+
+    /// The workspace acted on, when it is not the one the actor acts in
+    pub fn workspace(mut self, workspace_id: Uuid) -> Self {
+        self.workspace_id = Some(workspace_id);
+        self
+    }
+
+    pub fn changes(mut self, changes: Option<serde_json::Value>) -> Self {
+        self.changes = changes;
+        self
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditQuery {
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    pub workspace_id: Option<Uuid>,
+    /// Only include events at or after this time (RFC 3339)
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    #[param(value_type = Option<String>, format = DateTime)]
+    pub since: Option<OffsetDateTime>,
+    /// Only include events before this time (RFC 3339)
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    #[param(value_type = Option<String>, format = DateTime)]
+    pub until: Option<OffsetDateTime>,
+    /// Only include events older than this event ID, from `next_before` of the previous page
+    pub before: Option<i64>,
+    /// Maximum number of events to return (1-1000, default 100)
+    pub limit: Option<i64>,
+}
+
+impl AuditQuery {
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(100).clamp(1, 1000)
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AuditEventPage {
+    /// Newest first
+    pub events: Vec<AuditEvent>,
+    /// Pass as `before` to get the next page; absent on the last page
+    pub next_before: Option<i64>,
+}
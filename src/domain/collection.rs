@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// A named folder grouping links
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Collection {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    /// Workspace the collection belongs to; only links of that workspace can join it
+    pub workspace_id: Option<Uuid>,
+    pub created_at: OffsetDateTime,
+    /// Number of links in the collection
+    pub url_count: i64,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[schema(example = json!({
+    "name": "Spring campaign",
+    "description": "Links printed on the spring flyers"
+}))]
+pub struct CreateCollectionRequest {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[schema(example = json!({
+    "name": "Spring campaign 2026"
+}))]
+pub struct UpdateCollectionRequest {
+    pub name: Option<String>,
+    /// New description; an empty string removes it
+    pub description: Option<String>,
+}
@@ -0,0 +1,33 @@
+use serde::Serialize;
+use sqlx::types::time::OffsetDateTime;
+use utoipa::ToSchema;
+
+/// Result of the latest run of a background job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum JobOutcome {
+    Running,
+    Succeeded,
+    Failed,
+    /// Stopped because the instance running it shut down
+    Cancelled,
+}
+
+/// A background job and its latest run on any instance
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JobStatus {
+    pub name: String,
+    /// Interval or cron expression the job runs on
+    pub schedule: String,
+    pub last_started_at: Option<OffsetDateTime>,
+    pub last_finished_at: Option<OffsetDateTime>,
+    pub last_duration_ms: Option<i64>,
+    pub last_outcome: Option<JobOutcome>,
+    /// Summary of what the latest run did, or why it failed
+    pub last_message: Option<String>,
+    /// Instance that ran the job last
+    pub last_instance: Option<String>,
+    pub run_count: i64,
+    pub failure_count: i64,
+}
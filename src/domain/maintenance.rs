@@ -0,0 +1,24 @@
+use serde::Serialize;
+use sqlx::types::time::OffsetDateTime;
+use utoipa::ToSchema;
+
+/// What the expired-link cleanup has done since the server started
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct ExpiredCleanupStats {
+    pub enabled: bool,
+    /// Days past their expiry before links are cleaned up
+    pub grace_days: u64,
+    /// Whether cleaned up links are copied to the archive
+    pub archive: bool,
+    /// Days before a cleaned up short code can be taken again; `null` if never
+    pub code_reuse_days: Option<u64>,
+    pub runs: u64,
+    pub links_archived: u64,
+    pub links_deleted: u64,
+    /// Short codes that finished their quarantine and can be taken again
+    pub codes_released: u64,
+    pub last_run_at: Option<OffsetDateTime>,
+    pub last_run_duration_ms: Option<u64>,
+    /// Error of the latest run, if it failed
+    pub last_error: Option<String>,
+}
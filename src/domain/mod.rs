@@ -1 +1,12 @@
+pub mod audit;
+pub mod collection;
+pub mod job;
+pub mod maintenance;
+pub mod qr;
+pub mod quota;
+pub mod revision;
+pub mod signed_link;
+pub mod transfer;
 pub mod url;
+pub mod user;
+pub mod workspace;
@@ -0,0 +1,55 @@
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum QrFormat {
+    Png,
+    Svg,
+}
+
+impl QrFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            QrFormat::Png => "image/png",
+            QrFormat::Svg => "image/svg+xml",
+        }
+    }
+
+    /// Picks a format from an `Accept` header, defaulting to PNG.
+    pub fn from_accept(accept: Option<&str>) -> Self {
+        match accept {
+            Some(accept) if accept.contains("image/svg+xml") => QrFormat::Svg,
+            _ => QrFormat::Png,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+pub enum QrErrorCorrection {
+    #[serde(alias = "l")]
+    L,
+    #[serde(alias = "m")]
+    M,
+    #[serde(alias = "q")]
+    Q,
+    #[serde(alias = "h")]
+    H,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct QrCodeQuery {
+    /// Output format; when omitted it is negotiated from the `Accept` header
+    pub format: Option<QrFormat>,
+    /// Width and height of the image in pixels (64-2048, default 256)
+    pub size: Option<u32>,
+    /// Quiet zone around the code in modules (0-16, default 4)
+    pub margin: Option<u32>,
+    /// Error correction level (L, M, Q or H, default M)
+    pub ec_level: Option<QrErrorCorrection>,
+    /// Foreground color as hex `RRGGBB` or `RRGGBBAA` (default 000000)
+    pub fg: Option<String>,
+    /// Background color as hex `RRGGBB` or `RRGGBBAA` (default FFFFFF)
+    pub bg: Option<String>,
+}
@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Who a quota limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum QuotaSubject {
+    /// The personal links of an account
+    User,
+    /// The links of a workspace, whoever creates them
+    Workspace,
+    /// The links created with a workspace API key
+    ApiKey,
+}
+
+impl QuotaSubject {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            QuotaSubject::User => "user",
+            QuotaSubject::Workspace => "workspace",
+            QuotaSubject::ApiKey => "api_key",
+        }
+    }
+}
+
+/// Limits on the links a subject creates and holds. Missing limits are unlimited.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Quota {
+    pub subject_type: QuotaSubject,
+    pub subject_id: Uuid,
+    /// Links created per UTC day
+    pub daily_links: Option<i64>,
+    /// Links created in total
+    pub total_links: Option<i64>,
+    /// Links neither deleted nor expired
+    pub active_links: Option<i64>,
+    pub updated_at: OffsetDateTime,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[schema(example = json!({
+    "daily_links": 100,
+    "total_links": 10000,
+    "active_links": 1000
+}))]
+pub struct SetQuotaRequest {
+    pub daily_links: Option<i64>,
+    pub total_links: Option<i64>,
+    /// Not available for API keys, whose links belong to their workspace
+    pub active_links: Option<i64>,
+}
+
+/// How much of one limit is used
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LimitUsage {
+    /// Absent when unlimited
+    pub limit: Option<i64>,
+    pub used: i64,
+    /// Absent when unlimited
+    pub remaining: Option<i64>,
+}
+
+impl LimitUsage {
+    pub fn new(limit: Option<i64>, used: i64) -> Self {
+        Self {
+            limit,
+            used,
+            remaining: limit.map(|limit| (limit - used).max(0)),
+        }
+    }
+}
+
+/// A subject's usage of its quota
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QuotaUsage {
+    pub subject_type: QuotaSubject,
+    pub subject_id: Uuid,
+    pub daily_links: LimitUsage,
+    /// When the daily count starts over
+    pub daily_resets_at: OffsetDateTime,
+    pub total_links: LimitUsage,
+    /// Absent for API keys
+    pub active_links: Option<LimitUsage>,
+}
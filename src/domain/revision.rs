@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::time::OffsetDateTime;
+use utoipa::ToSchema;
+
+/// Who changed a link through the API
+pub const CHANGED_BY_API: &str = "api";
+/// The destination check switching a broken link to its fallback
+pub const CHANGED_BY_LINK_CHECKER: &str = "link-checker";
+/// A backup restore
+pub const CHANGED_BY_RESTORE: &str = "restore";
+
+/// What kind of change produced a revision
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum RevisionChange {
+    Created,
+    Updated,
+    /// Switched to the fallback destination after failed checks
+    FallbackSwitch,
+    Rollback,
+}
+
+/// The redirect settings of a link after a change
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UrlRevision {
+    /// Sequential number of the revision, starting at 1
+    pub revision: i32,
+    pub change: RevisionChange,
+    /// Who made the change, if known
+    pub changed_by: Option<String>,
+    pub original_url: String,
+    pub expires_at: Option<OffsetDateTime>,
+    pub interstitial: bool,
+    pub fallback_url: Option<String>,
+    pub og_title: Option<String>,
+    pub og_description: Option<String>,
+    pub og_image: Option<String>,
+    pub require_signature: bool,
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[schema(example = json!({ "revision": 2 }))]
+pub struct RollbackRequest {
+    /// Revision whose settings the link should get again
+    pub revision: i32,
+}
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::time::OffsetDateTime;
+use utoipa::ToSchema;
+
+/// The query parameters of a signed link, as received; any of them may be missing
+#[derive(Debug, Clone, Default)]
+pub struct LinkSignature {
+    pub expires: Option<i64>,
+    pub recipient: Option<String>,
+    pub key_id: Option<String>,
+    pub signature: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[schema(example = json!({
+    "recipients": [
+        { "recipient": "alice@example.com", "expires_in_secs": 86400 },
+        { "recipient": "bob@example.com", "expires_at": "2026-11-01T00:00:00Z" }
+    ]
+}))]
+pub struct SignLinkRequest {
+    /// One signed link is minted per entry
+    pub recipients: Vec<SignLinkRecipient>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SignLinkRecipient {
+    /// Who the link is for, e.g. an email address; it is part of the signed link
+    pub recipient: Option<String>,
+    /// When the signed link stops working (RFC 3339)
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub expires_at: Option<OffsetDateTime>,
+    /// How long the signed link works, used instead of `expires_at`
+    pub expires_in_secs: Option<i64>,
+}
+
+/// A signed variant of a link that redirects until it expires, without the server looking
+/// anything up to reject it afterwards
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SignedLink {
+    pub recipient: Option<String>,
+    pub url: String,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schema(value_type = String, format = DateTime)]
+    pub expires_at: OffsetDateTime,
+    /// ID of the key the link was signed with
+    pub key_id: String,
+}
@@ -0,0 +1,236 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::time::OffsetDateTime;
+use std::str::FromStr;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::domain::workspace::WorkspaceRole;
+
+/// File formats links can be imported from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    /// CSV with a `url` column and optional `alias`, `expires_at`, `tags`, `title`,
+    /// `description`, `created_at` and `visits` columns
+    #[default]
+    Csv,
+    /// YOURLS SQL dump of the `yourls_url` table, or a CSV export of it
+    Yourls,
+    /// Bitly CSV export
+    Bitly,
+    /// Kutt JSON, as returned by its links API
+    Kutt,
+}
+
+impl ImportFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ImportFormat::Csv => "csv",
+            ImportFormat::Yourls => "yourls",
+            ImportFormat::Bitly => "bitly",
+            ImportFormat::Kutt => "kutt",
+        }
+    }
+}
+
+impl FromStr for ImportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(ImportFormat::Csv),
+            "yourls" => Ok(ImportFormat::Yourls),
+            "bitly" => Ok(ImportFormat::Bitly),
+            "kutt" => Ok(ImportFormat::Kutt),
+            _ => Err(format!(
+                "Unknown import format: {} (expected csv, yourls, bitly or kutt)",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportQuery {
+    /// Format of the uploaded file, `csv` by default
+    pub format: Option<ImportFormat>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum ImportStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+}
+
+/// Progress of an asynchronous link import
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportJob {
+    pub id: Uuid,
+    /// Format the links were imported from, such as `csv` or `yourls`
+    pub source: String,
+    pub status: ImportStatus,
+    pub total_rows: i32,
+    pub processed_rows: i32,
+    pub created_count: i32,
+    /// Rows that matched an existing link, which was kept instead of creating a new one
+    pub reused_count: i32,
+    pub failed_count: i32,
+    /// Why the whole job failed, if it did
+    pub error: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub finished_at: Option<OffsetDateTime>,
+}
+
+/// A row that could not be imported
+#[derive(Debug, Serialize)]
+pub struct ImportError {
+    pub row_number: i32,
+    pub original_url: Option<String>,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Jsonl,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Jsonl => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportQuery {
+    /// `csv` (default) or `jsonl`
+    pub format: Option<ExportFormat>,
+}
+
+/// What a restore does with links and collections that already exist
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// Abort the whole restore without changing anything
+    #[default]
+    Fail,
+    /// Keep the existing entry and leave out the one from the archive
+    Skip,
+    /// Replace the existing entry with the one from the archive
+    Overwrite,
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fail" => Ok(ConflictPolicy::Fail),
+            "skip" => Ok(ConflictPolicy::Skip),
+            "overwrite" => Ok(ConflictPolicy::Overwrite),
+            _ => Err(format!(
+                "Unknown conflict policy: {} (expected fail, skip or overwrite)",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RestoreQuery {
+    /// How to handle accounts, workspaces, API keys, collections and links that already
+    /// exist, `fail` by default
+    pub on_conflict: Option<ConflictPolicy>,
+}
+
+/// What a restore changed
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct RestoreSummary {
+    /// Format version of the restored archive
+    pub version: u32,
+    pub users_created: u64,
+    pub users_overwritten: u64,
+    pub users_skipped: u64,
+    pub workspaces_created: u64,
+    pub workspaces_overwritten: u64,
+    pub workspaces_skipped: u64,
+    pub memberships_created: u64,
+    pub memberships_overwritten: u64,
+    pub memberships_skipped: u64,
+    pub api_keys_created: u64,
+    pub api_keys_overwritten: u64,
+    pub api_keys_skipped: u64,
+    pub collections_created: u64,
+    pub collections_overwritten: u64,
+    pub collections_skipped: u64,
+    pub tags: u64,
+    pub links_created: u64,
+    pub links_overwritten: u64,
+    pub links_skipped: u64,
+}
+
+/// An account as kept in backups, with its password hash so it can still sign in
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountBackup {
+    pub id: Uuid,
+    pub email: String,
+    pub password_hash: Option<String>,
+    pub is_admin: bool,
+    pub external_subject: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorkspaceBackup {
+    pub id: Uuid,
+    pub name: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MembershipBackup {
+    pub workspace_id: Uuid,
+    pub user_id: Uuid,
+    pub role: WorkspaceRole,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+/// A workspace API key as kept in backups; only the hash of the key is stored
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiKeyBackup {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub name: String,
+    pub role: WorkspaceRole,
+    pub key_hash: String,
+    pub key_prefix: String,
+    pub created_by: Option<Uuid>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_used_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub revoked_at: Option<OffsetDateTime>,
+}
@@ -1,8 +1,10 @@
 use serde::{Deserialize, Serialize};
 use sqlx::types::time::OffsetDateTime;
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
+use crate::domain::signed_link::LinkSignature;
+
 #[derive(Debug, Serialize, ToSchema)]
 pub struct Url {
     pub id: Uuid,
@@ -11,16 +13,262 @@ pub struct Url {
     pub visits: i64,
     pub created_at: OffsetDateTime,
     pub expires_at: Option<OffsetDateTime>,
+    pub interstitial: bool,
+    pub interstitial_views: i64,
+    pub interstitial_confirmations: i64,
+    /// Only redirects through signed links, which carry their own expiry
+    pub require_signature: bool,
+    pub og_title: Option<String>,
+    pub og_description: Option<String>,
+    pub og_image: Option<String>,
+    /// Title of the destination page, fetched in the background
+    pub page_title: Option<String>,
+    pub favicon_url: Option<String>,
+    /// HTTP status the destination answered with when its metadata was fetched
+    pub http_status: Option<i32>,
+    pub metadata_fetched_at: Option<OffsetDateTime>,
+    /// Destination the link is switched to when the original one is found broken
+    pub fallback_url: Option<String>,
+    /// HTTP status of the latest destination check, `None` if the request failed
+    pub last_check_status: Option<i32>,
+    pub last_check_latency_ms: Option<i32>,
+    pub last_checked_at: Option<OffsetDateTime>,
+    /// Number of consecutive failed destination checks
+    pub check_failures: i32,
+    pub collection_id: Option<Uuid>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// Free-form JSON object attached by the link's creator
+    #[schema(value_type = Object)]
+    pub metadata: serde_json::Value,
+    pub tags: Vec<String>,
+    /// When the link was moved to the trash
+    pub deleted_at: Option<OffsetDateTime>,
+    /// Account that created the link; `null` for anonymous links
+    pub owner_id: Option<Uuid>,
+    /// Workspace the link belongs to; `null` for personal links
+    pub workspace_id: Option<Uuid>,
 }
 
-#[derive(Debug, Deserialize, ToSchema)]
+/// The part of a link needed to serve a redirect, as stored in the cache
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedirectTarget {
+    pub id: Uuid,
+    pub original_url: String,
+    pub interstitial: bool,
+    #[serde(default)]
+    pub require_signature: bool,
+    #[serde(default)]
+    pub social_card: Option<SocialCard>,
+}
+
+impl From<&Url> for RedirectTarget {
+    fn from(url: &Url) -> Self {
+        Self {
+            id: url.id,
+            original_url: url.original_url.clone(),
+            interstitial: url.interstitial,
+            require_signature: url.require_signature,
+            social_card: SocialCard::from_url(url),
+        }
+    }
+}
+
+/// Open Graph overrides served to link preview crawlers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SocialCard {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+}
+
+impl SocialCard {
+    /// Returns the card for a link, or `None` when it has no overrides
+    pub fn from_url(url: &Url) -> Option<Self> {
+        if url.og_title.is_none() && url.og_description.is_none() && url.og_image.is_none() {
+            return None;
+        }
+
+        Some(Self {
+            title: url.og_title.clone(),
+            description: url.og_description.clone(),
+            image: url.og_image.clone(),
+        })
+    }
+}
+
+/// How a request for a short code was served, for click analytics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisitKind {
+    Redirect,
+    InterstitialShown,
+    InterstitialConfirmed,
+}
+
+/// Human-readable details about a destination
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DestinationMetadata {
+    pub page_title: Option<String>,
+    pub favicon_url: Option<String>,
+    pub http_status: Option<i32>,
+}
+
+/// A link whose destination is due for a check
+#[derive(Debug, Clone)]
+pub struct LinkToCheck {
+    pub id: Uuid,
+    pub short_code: String,
+    pub original_url: String,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
 #[schema(example = json!({
     "url": "https://example.com",
-    "expires_in_days": 1805226240
+    "expires_in_days": 1805226240,
+    "title": "Example homepage",
+    "metadata": { "ticket": "OPS-1234" }
 }))]
 pub struct CreateUrlRequest {
     pub url: String,
+    /// Custom short code (3-64 letters, digits, `-` or `_`) instead of a generated one
+    pub alias: Option<String>,
     pub expires_in_days: Option<i32>,
+    /// Absolute expiry (RFC 3339), used instead of `expires_in_days`
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub expires_at: Option<OffsetDateTime>,
+    /// Always show a warning page before redirecting
+    pub interstitial: Option<bool>,
+    /// Only redirect through signed links minted with `POST /api/urls/{short_code}/signed`
+    pub require_signature: Option<bool>,
+    /// Title shown when the link is unfurled by chat apps and social networks
+    pub og_title: Option<String>,
+    /// Description shown when the link is unfurled
+    pub og_description: Option<String>,
+    /// Absolute http(s) URL of the image shown when the link is unfurled
+    pub og_image: Option<String>,
+    /// Destination to switch to if the original one is found broken
+    pub fallback_url: Option<String>,
+    /// Tags to attach to the link
+    pub tags: Option<Vec<String>>,
+    /// Collection to put the link in
+    pub collection_id: Option<Uuid>,
+    /// Human-readable title
+    pub title: Option<String>,
+    /// Free-form notes about the link
+    pub description: Option<String>,
+    /// Arbitrary JSON object, such as ticket IDs or owners
+    #[schema(value_type = Option<Object>)]
+    pub metadata: Option<serde_json::Value>,
+    /// Return an existing active link to the same destination with the same options,
+    /// title, description, metadata and tags instead of creating a new one. Defaults to
+    /// the server's `DEDUPE_TARGETS` setting; links with an alias or an expiry are never
+    /// reused.
+    pub dedupe: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[schema(example = json!({
+    "interstitial": true,
+    "og_title": "Spring campaign"
+}))]
+pub struct UpdateUrlRequest {
+    /// New destination of the link
+    pub url: Option<String>,
+    /// New expiry (RFC 3339); `null` makes the link permanent
+    #[serde(default, deserialize_with = "deserialize_some_rfc3339")]
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub expires_at: Option<Option<OffsetDateTime>>,
+    /// Flag or unflag the link for a warning page before redirecting
+    pub interstitial: Option<bool>,
+    /// Require or stop requiring signed links to redirect
+    pub require_signature: Option<bool>,
+    /// Open Graph title override; an empty string removes it
+    pub og_title: Option<String>,
+    /// Open Graph description override; an empty string removes it
+    pub og_description: Option<String>,
+    /// Open Graph image override; an empty string removes it
+    pub og_image: Option<String>,
+    /// Destination to switch to if the original one is found broken; an empty string removes it
+    pub fallback_url: Option<String>,
+    /// Replaces all tags of the link
+    pub tags: Option<Vec<String>>,
+    /// Moves the link to a collection; `null` removes it from its collection
+    #[serde(default, deserialize_with = "deserialize_some")]
+    #[schema(value_type = Option<Uuid>)]
+    pub collection_id: Option<Option<Uuid>>,
+    /// Human-readable title; an empty string removes it
+    pub title: Option<String>,
+    /// Free-form notes; an empty string removes them
+    pub description: Option<String>,
+    /// Replaces the whole metadata object
+    #[schema(value_type = Option<Object>)]
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// Distinguishes an explicit `null` (`Some(None)`) from a missing field (`None`)
+fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// Like `deserialize_some`, for RFC 3339 timestamps
+fn deserialize_some_rfc3339<'de, D>(
+    deserializer: D,
+) -> Result<Option<Option<OffsetDateTime>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    time::serde::rfc3339::option::deserialize(deserializer).map(Some)
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RedirectQuery {
+    /// Show a preview page instead of redirecting
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    pub preview: Option<bool>,
+    /// Continue past the warning page of a link that requires one
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    pub confirm: Option<bool>,
+    /// Expiry of a signed link (Unix timestamp). Kept as text so a mangled value fails the
+    /// signature check instead of the whole request.
+    pub exp: Option<String>,
+    /// Recipient a signed link was minted for
+    pub rcpt: Option<String>,
+    /// ID of the key a signed link was signed with
+    pub kid: Option<String>,
+    /// Signature of a signed link
+    pub sig: Option<String>,
+}
+
+impl RedirectQuery {
+    /// The signature parameters, if the request carries any
+    pub fn signature(&self) -> Option<LinkSignature> {
+        if self.exp.is_none() && self.kid.is_none() && self.sig.is_none() && self.rcpt.is_none() {
+            return None;
+        }
+        Some(LinkSignature {
+            // An expiry that is not a number cannot have been signed
+            expires: self.exp.as_deref().and_then(|exp| exp.parse().ok()),
+            recipient: self.rcpt.clone(),
+            key_id: self.kid.clone(),
+            signature: self.sig.clone(),
+        })
+    }
+}
+
+/// Accepts `1` and `true` (or a bare flag) for boolean query flags. Any other value means
+/// false rather than rejecting the request, so a mangled link still redirects.
+fn deserialize_flag<'de, D>(deserializer: D) -> Result<Option<bool>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value: Option<String> = Option::deserialize(deserializer)?;
+    Ok(value.map(|value| matches!(value.as_str(), "1" | "true" | "")))
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -29,6 +277,55 @@ pub struct CreateUrlResponse {
     pub original_url: String,
     pub short_url: String,
     pub expires_at: Option<OffsetDateTime>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    #[schema(value_type = Object)]
+    pub metadata: serde_json::Value,
+    /// `false` when an existing link was returned instead of creating a new one
+    pub created: bool,
+}
+
+/// How a batch behaves when some of its items fail
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchMode {
+    /// Create all links or none of them
+    #[default]
+    Transactional,
+    /// Create every valid link and report the failures
+    Partial,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[schema(example = json!({
+    "mode": "partial",
+    "urls": [
+        { "url": "https://example.com/spring", "tags": ["campaign"] },
+        { "url": "https://example.com/summer", "tags": ["campaign"] }
+    ]
+}))]
+pub struct BatchCreateUrlRequest {
+    /// Defaults to `transactional`
+    pub mode: Option<BatchMode>,
+    pub urls: Vec<CreateUrlRequest>,
+}
+
+/// Outcome of one item of a batch, in the same position as in the request
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchItemResult {
+    pub index: usize,
+    pub url: Option<CreateUrlResponse>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchCreateUrlResponse {
+    /// Items that created a new link
+    pub created: usize,
+    /// Items answered with an existing link through deduplication
+    pub reused: usize,
+    pub failed: usize,
+    pub results: Vec<BatchItemResult>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -36,4 +333,80 @@ pub struct StatsResponse {
     pub total_urls: i64,
     pub total_visits: i64,
     pub urls: Vec<Url>,
+    /// Link and visit totals per tag
+    pub tags: Vec<TagStats>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TagStats {
+    pub name: String,
+    pub total_urls: i64,
+    pub total_visits: i64,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StatsQuery {
+    /// Only include links with this tag
+    pub tag: Option<String>,
+    /// Only include links in this collection
+    pub collection_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UrlListQuery {
+    /// Only include links with this tag
+    pub tag: Option<String>,
+    /// Only include links in this collection
+    pub collection_id: Option<Uuid>,
+    /// Only include links whose metadata has this key
+    pub metadata_key: Option<String>,
+    /// Only include links whose metadata value for `metadata_key` equals this
+    pub metadata_value: Option<String>,
+    /// List the links in the trash instead of the active ones
+    pub deleted: Option<bool>,
+    /// Order of the links (default `created_at`, newest first)
+    pub sort: Option<UrlSort>,
+    /// Maximum number of links to return (1-1000, default 100)
+    pub limit: Option<i64>,
+    /// Number of links to skip
+    pub offset: Option<i64>,
+}
+
+/// Order of a link listing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UrlSort {
+    /// Newest first
+    #[default]
+    CreatedAt,
+    /// Most visited first, newest first among equals
+    Visits,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UrlListResponse {
+    /// Number of links matching the filters
+    pub total: i64,
+    pub urls: Vec<Url>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageQuery {
+    /// Maximum number of items to return (1-1000, default 100)
+    pub limit: Option<i64>,
+    /// Number of items to skip
+    pub offset: Option<i64>,
+}
+
+impl PageQuery {
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(100).clamp(1, 1000)
+    }
+
+    pub fn offset(&self) -> i64 {
+        self.offset.unwrap_or(0).max(0)
+    }
 }
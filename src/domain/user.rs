@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::domain::{
+    revision::CHANGED_BY_API,
+    workspace::{LinkScope, WorkspaceAccess, WorkspaceRole},
+};
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct User {
+    pub id: Uuid,
+    pub email: String,
+    /// Admins see and manage every link
+    pub is_admin: bool,
+    pub created_at: OffsetDateTime,
+}
+
+/// Who is making a request
+#[derive(Debug, Clone)]
+pub enum Principal {
+    /// A visitor without credentials
+    Anonymous,
+    /// The server's API key, with access to everything
+    ApiKey,
+    User(User),
+    /// An account acting in one of its workspaces
+    Member(User, WorkspaceAccess),
+    /// A workspace API key
+    WorkspaceKey {
+        key_id: Uuid,
+        access: WorkspaceAccess,
+    },
+}
+
+impl Principal {
+    pub fn is_admin(&self) -> bool {
+        match self {
+            Principal::Anonymous => false,
+            Principal::ApiKey => true,
+            Principal::User(user) | Principal::Member(user, _) => user.is_admin,
+            Principal::WorkspaceKey { .. } => false,
+        }
+    }
+
+    /// The account that creates links as this principal
+    pub fn user_id(&self) -> Option<Uuid> {
+        match self {
+            Principal::User(user) | Principal::Member(user, _) => Some(user.id),
+            _ => None,
+        }
+    }
+
+    /// The workspace this principal acts in
+    pub fn workspace(&self) -> Option<WorkspaceAccess> {
+        match self {
+            Principal::Member(_, access) | Principal::WorkspaceKey { access, .. } => Some(*access),
+            _ => None,
+        }
+    }
+
+    /// Links listings are restricted to. Workspace principals see their workspace, admins
+    /// every link and anyone else their personal links.
+    pub fn link_scope(&self) -> LinkScope {
+        match self.workspace() {
+            Some(access) => LinkScope {
+                workspace_id: Some(access.workspace_id),
+                owner_id: None,
+            },
+            None if self.is_admin() => LinkScope::default(),
+            // Anonymous principals own nothing, and no link has the nil id as owner
+            None => LinkScope {
+                owner_id: Some(self.user_id().unwrap_or(Uuid::nil())),
+                workspace_id: None,
+            },
+        }
+    }
+
+    /// Whether this principal may access a link with the given owner and workspace, with
+    /// at least `role` if the link belongs to a workspace
+    pub fn can_access(
+        &self,
+        owner_id: Option<Uuid>,
+        workspace_id: Option<Uuid>,
+        role: WorkspaceRole,
+    ) -> bool {
+        if self.is_admin() {
+            return true;
+        }
+        match (self.workspace(), workspace_id) {
+            (Some(access), Some(workspace_id)) => {
+                access.workspace_id == workspace_id && access.role >= role
+            }
+            (None, None) => owner_id.is_some() && owner_id == self.user_id(),
+            _ => false,
+        }
+    }
+
+    /// Recorded as the author of link revisions
+    pub fn changed_by(&self) -> String {
+        match self {
+            Principal::Anonymous => "anonymous".to_string(),
+            Principal::ApiKey => CHANGED_BY_API.to_string(),
+            Principal::User(user) | Principal::Member(user, _) => user.email.clone(),
+            Principal::WorkspaceKey { key_id, .. } => format!("workspace-key:{}", key_id),
+        }
+    }
+
+    /// Keeps idempotency keys of different principals apart
+    pub fn scope(&self) -> String {
+        match self {
+            Principal::Anonymous => "anonymous".to_string(),
+            Principal::ApiKey => "api-key".to_string(),
+            Principal::User(user) => format!("user:{}", user.id),
+            Principal::Member(user, access) => {
+                format!("user:{}:workspace:{}", user.id, access.workspace_id)
+            }
+            Principal::WorkspaceKey { key_id, .. } => format!("workspace-key:{}", key_id),
+        }
+    }
+}
+
+// No `Debug`, so passwords cannot end up in logs
+#[derive(Deserialize, ToSchema)]
+#[schema(example = json!({
+    "email": "jane@example.com",
+    "password": "correct horse battery staple"
+}))]
+pub struct CreateUserRequest {
+    pub email: String,
+    /// At least 8 characters
+    pub password: String,
+    /// Only admins can create admins
+    #[serde(default)]
+    pub is_admin: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[schema(example = json!({
+    "email": "jane@example.com",
+    "password": "correct horse battery staple"
+}))]
+pub struct LoginRequest {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LoginResponse {
+    /// Bearer token for the `Authorization` header
+    pub token: String,
+    pub expires_at: OffsetDateTime,
+    pub user: User,
+}
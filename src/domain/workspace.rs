@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::time::OffsetDateTime;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// What a member or API key may do in a workspace. Each role can do everything the roles
+/// before it can.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema, sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum WorkspaceRole {
+    /// Sees the workspace's links and stats
+    Viewer,
+    /// Creates, edits and deletes links
+    Editor,
+    /// Manages members, invitations and API keys
+    Admin,
+    /// Can also manage admins and other owners
+    Owner,
+}
+
+impl WorkspaceRole {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WorkspaceRole::Viewer => "viewer",
+            WorkspaceRole::Editor => "editor",
+            WorkspaceRole::Admin => "admin",
+            WorkspaceRole::Owner => "owner",
+        }
+    }
+}
+
+/// The workspace a request acts in, and the role it acts with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkspaceAccess {
+    pub workspace_id: Uuid,
+    pub role: WorkspaceRole,
+}
+
+/// Which links a listing covers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LinkScope {
+    /// Personal links of this account, outside any workspace
+    pub owner_id: Option<Uuid>,
+    /// Links of this workspace
+    pub workspace_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Workspace {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: OffsetDateTime,
+    /// Role of the caller; `null` for server admins who are not members
+    pub role: Option<WorkspaceRole>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[schema(example = json!({"name": "Marketing"}))]
+pub struct CreateWorkspaceRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WorkspaceMember {
+    pub user_id: Uuid,
+    pub email: String,
+    pub role: WorkspaceRole,
+    pub joined_at: OffsetDateTime,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[schema(example = json!({"role": "editor"}))]
+pub struct UpdateMemberRequest {
+    pub role: WorkspaceRole,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Invitation {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub email: String,
+    pub role: WorkspaceRole,
+    pub invited_by: Option<Uuid>,
+    pub created_at: OffsetDateTime,
+    pub expires_at: OffsetDateTime,
+    /// Token the invited account accepts the invitation with; only returned on creation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[schema(example = json!({"email": "jane@example.com", "role": "editor"}))]
+pub struct CreateInvitationRequest {
+    pub email: String,
+    pub role: WorkspaceRole,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AcceptInvitationRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WorkspaceApiKey {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub name: String,
+    pub role: WorkspaceRole,
+    /// The start of the key, to recognize it by
+    pub key_prefix: String,
+    pub created_by: Option<Uuid>,
+    pub created_at: OffsetDateTime,
+    pub last_used_at: Option<OffsetDateTime>,
+    /// The key for the `X-API-Key` header; only returned on creation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[schema(example = json!({"name": "CI", "role": "editor"}))]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub role: WorkspaceRole,
+}
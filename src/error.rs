@@ -25,9 +25,24 @@ pub enum AppError {
     #[error("Unauthorized")]
     Unauthorized,
 
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
     #[error("Bad request: {0}")]
     BadRequest(String),
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Gone: {0}")]
+    Gone(String),
+
+    #[error("Unprocessable entity: {0}")]
+    UnprocessableEntity(String),
+
+    #[error("Too many requests: {0}")]
+    TooManyRequests(String),
+
     #[error("Internal server error: {0}")]
     Internal(String),
 }
@@ -52,9 +67,24 @@ impl AppError {
             AppError::Unauthorized => {
                 tracing::warn!("Unauthorized access attempt");
             }
+            AppError::Forbidden(ref message) => {
+                tracing::warn!("Forbidden: {}", message);
+            }
             AppError::BadRequest(ref message) => {
                 tracing::debug!("Bad request: {}", message);
             }
+            AppError::Conflict(ref message) => {
+                tracing::debug!("Conflict: {}", message);
+            }
+            AppError::Gone(ref message) => {
+                tracing::debug!("Gone: {}", message);
+            }
+            AppError::UnprocessableEntity(ref message) => {
+                tracing::debug!("Unprocessable entity: {}", message);
+            }
+            AppError::TooManyRequests(ref message) => {
+                tracing::debug!("Too many requests: {}", message);
+            }
             AppError::Internal(ref message) => {
                 tracing::error!("Internal server error: {}", message);
             }
@@ -62,7 +92,7 @@ impl AppError {
     }
 
     // Get user-friendly message based on environment
-    fn user_message(&self, env: &Environment) -> String {
+    pub fn user_message(&self, env: &Environment) -> String {
         match env {
             Environment::Development => {
                 // In development, provide detailed error messages
@@ -71,7 +101,12 @@ impl AppError {
                     AppError::Redis(ref e) => format!("Cache error: {}", e),
                     AppError::NotFound(ref message) => message.clone(),
                     AppError::Unauthorized => "Unauthorized".to_string(),
+                    AppError::Forbidden(ref message) => message.clone(),
                     AppError::BadRequest(ref message) => message.clone(),
+                    AppError::Conflict(ref message) => message.clone(),
+                    AppError::Gone(ref message) => message.clone(),
+                    AppError::UnprocessableEntity(ref message) => message.clone(),
+                    AppError::TooManyRequests(ref message) => message.clone(),
                     AppError::Internal(ref message) => format!("Internal error: {}", message),
                 }
             }
@@ -86,7 +121,12 @@ impl AppError {
                     }
                     AppError::NotFound(ref message) => message.clone(),
                     AppError::Unauthorized => "Unauthorized".to_string(),
+                    AppError::Forbidden(ref message) => message.clone(),
                     AppError::BadRequest(ref message) => message.clone(),
+                    AppError::Conflict(ref message) => message.clone(),
+                    AppError::Gone(ref message) => message.clone(),
+                    AppError::UnprocessableEntity(ref message) => message.clone(),
+                    AppError::TooManyRequests(ref message) => message.clone(),
                     AppError::Internal(_) => {
                         "An internal error occurred. Please try again later.".to_string()
                     }
@@ -121,7 +161,12 @@ impl IntoResponse for AppError {
             AppError::Redis(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::NotFound(_) => StatusCode::NOT_FOUND,
             AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
             AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Gone(_) => StatusCode::GONE,
+            AppError::UnprocessableEntity(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
             AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
 
@@ -149,7 +194,12 @@ impl IntoResponse for ErrorResponse {
             AppError::Redis(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::NotFound(_) => StatusCode::NOT_FOUND,
             AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
             AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Gone(_) => StatusCode::GONE,
+            AppError::UnprocessableEntity(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
             AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
 
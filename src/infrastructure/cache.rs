@@ -1,9 +1,10 @@
 use redis::aio::ConnectionManager;
 use redis::AsyncCommands;
+use serde::{de::DeserializeOwned, Serialize};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
-use crate::error::Result;
+use crate::error::{AppError, Result};
 
 #[derive(Clone)]
 pub struct Cache {
@@ -17,6 +18,19 @@ impl Cache {
         }
     }
 
+    /// Connects to the Redis instance named by `REDIS_URL`, for tests
+    #[cfg(test)]
+    pub async fn connect_for_tests() -> Self {
+        let url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6379".into());
+        let client = redis::Client::open(url).expect("invalid REDIS_URL");
+        Self::new(
+            client
+                .get_connection_manager()
+                .await
+                .expect("Redis is required for this test"),
+        )
+    }
+
     pub async fn get(&self, key: &str) -> Result<Option<String>> {
         let mut conn = self.client.lock().await;
         let result: Option<String> = conn.get(key).await?;
@@ -30,6 +44,133 @@ impl Cache {
         Ok(())
     }
 
+    /// Gets a JSON value, treating entries that no longer deserialize as missing
+    pub async fn get_json<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        Ok(self
+            .get(key)
+            .await?
+            .and_then(|value| match serde_json::from_str(&value) {
+                Ok(value) => Some(value),
+                Err(e) => {
+                    tracing::debug!("Ignoring unreadable cache entry {}: {}", key, e);
+                    None
+                }
+            }))
+    }
+
+    pub async fn set_json_with_expiry<T: Serialize>(
+        &self,
+        key: &str,
+        value: &T,
+        expiry_secs: u64,
+    ) -> Result<()> {
+        let value = serde_json::to_string(value)
+            .map_err(|e| AppError::Internal(format!("Failed to serialize cache entry: {}", e)))?;
+        self.set_with_expiry(key, &value, expiry_secs).await
+    }
+
+    /// Sets a value only if the key does not exist yet, returning whether it was set
+    pub async fn set_nx_with_expiry(
+        &self,
+        key: &str,
+        value: &str,
+        expiry_secs: u64,
+    ) -> Result<bool> {
+        let mut conn = self.client.lock().await;
+        let result: Option<String> = redis::cmd("SET")
+            .arg(key)
+            .arg(value)
+            .arg("NX")
+            .arg("EX")
+            .arg(expiry_secs)
+            .query_async(&mut *conn)
+            .await?;
+        Ok(result.is_some())
+    }
+
+    /// Sets several JSON values in a single pipelined round trip
+    pub async fn set_many_json_with_expiry<T: Serialize>(
+        &self,
+        entries: &[(String, T)],
+        expiry_secs: u64,
+    ) -> Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let mut pipe = redis::pipe();
+        for (key, value) in entries {
+            let value = serde_json::to_string(value).map_err(|e| {
+                AppError::Internal(format!("Failed to serialize cache entry: {}", e))
+            })?;
+            pipe.set_ex(key, value, expiry_secs).ignore();
+        }
+
+        let mut conn = self.client.lock().await;
+        let _: () = pipe.query_async(&mut *conn).await?;
+        Ok(())
+    }
+
+    /// Resets the expiry of a key, returning `false` when the key no longer exists
+    pub async fn expire(&self, key: &str, expiry_secs: u64) -> Result<bool> {
+        let mut conn = self.client.lock().await;
+        let result: bool = conn.expire(key, expiry_secs as i64).await?;
+        Ok(result)
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<()> {
+        let mut conn = self.client.lock().await;
+        let _: () = conn.del(key).await?;
+        Ok(())
+    }
+
+    pub async fn get_bytes(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let mut conn = self.client.lock().await;
+        let result: Option<Vec<u8>> = conn.get(key).await?;
+        Ok(result)
+    }
+
+    pub async fn set_bytes_with_expiry(
+        &self,
+        key: &str,
+        value: &[u8],
+        expiry_secs: u64,
+    ) -> Result<()> {
+        let mut conn = self.client.lock().await;
+        let _: () = conn.set_ex(key, value, expiry_secs).await?;
+        Ok(())
+    }
+
+    /// Adds `delta` to several counters in one round trip, returning their new values.
+    /// Counters given an expiry have it renewed with every change.
+    pub async fn incr_many(
+        &self,
+        counters: &[(String, Option<u64>)],
+        delta: i64,
+    ) -> Result<Vec<i64>> {
+        let mut pipe = redis::pipe();
+        for (key, expiry_secs) in counters {
+            pipe.cmd("INCRBY").arg(key).arg(delta);
+            if let Some(expiry_secs) = expiry_secs {
+                pipe.cmd("EXPIRE").arg(key).arg(*expiry_secs).ignore();
+            }
+        }
+
+        let mut conn = self.client.lock().await;
+        let values: Vec<i64> = pipe.query_async(&mut *conn).await?;
+        Ok(values)
+    }
+
+    /// Reads several counters, with missing ones as zero
+    pub async fn get_counters(&self, keys: &[String]) -> Result<Vec<i64>> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut conn = self.client.lock().await;
+        let values: Vec<Option<i64>> = conn.mget(keys).await?;
+        Ok(values.into_iter().map(Option::unwrap_or_default).collect())
+    }
+
     pub fn url_cache_key(short_code: &str) -> String {
         format!("url:{}", short_code)
     }
@@ -1,27 +1,249 @@
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgExecutor, PgPool};
 use uuid::Uuid;
 
-use crate::domain::url::Url;
+use crate::domain::{
+    audit::{AuditEvent, AuditQuery, NewAuditEvent},
+    collection::Collection,
+    job::{JobOutcome, JobStatus},
+    quota::{Quota, QuotaSubject},
+    revision::{RevisionChange, UrlRevision},
+    transfer::{
+        AccountBackup, ApiKeyBackup, ImportError, ImportJob, ImportStatus, MembershipBackup,
+        WorkspaceBackup,
+    },
+    url::{DestinationMetadata, LinkToCheck, TagStats, Url, UrlSort},
+    user::User,
+    workspace::{
+        Invitation, LinkScope, Workspace, WorkspaceAccess, WorkspaceApiKey, WorkspaceMember,
+        WorkspaceRole,
+    },
+};
 use crate::error::Result;
 
-pub async fn create_url(
-    pool: &PgPool,
-    original_url: &str,
-    short_code: &str,
-    expires_at: Option<time::OffsetDateTime>,
-) -> Result<Url> {
+/// Column values for a link about to be inserted
+pub struct NewUrl<'a> {
+    pub original_url: &'a str,
+    pub short_code: &'a str,
+    pub expires_at: Option<time::OffsetDateTime>,
+    pub interstitial: bool,
+    pub require_signature: bool,
+    pub og_title: Option<&'a str>,
+    pub og_description: Option<&'a str>,
+    pub og_image: Option<&'a str>,
+    pub fallback_url: Option<&'a str>,
+    pub collection_id: Option<Uuid>,
+    pub title: Option<&'a str>,
+    pub description: Option<&'a str>,
+    pub metadata: &'a serde_json::Value,
+    /// Hash of the normalized destination
+    pub target_hash: &'a str,
+    pub owner_id: Option<Uuid>,
+    pub workspace_id: Option<Uuid>,
+}
+
+/// Who a link belongs to
+pub struct UrlOwner {
+    pub id: Uuid,
+    pub owner_id: Option<Uuid>,
+    pub workspace_id: Option<Uuid>,
+}
+
+/// Column values for updating a link; `None` leaves a column unchanged and
+/// empty strings clear optional text columns
+#[derive(Default)]
+pub struct UrlChanges<'a> {
+    /// New destination, with the hash of its normalized form
+    pub original_url: Option<(&'a str, &'a str)>,
+    /// `Some(None)` removes the expiry
+    pub expires_at: Option<Option<time::OffsetDateTime>>,
+    pub interstitial: Option<bool>,
+    pub require_signature: Option<bool>,
+    pub og_title: Option<&'a str>,
+    pub og_description: Option<&'a str>,
+    pub og_image: Option<&'a str>,
+    pub fallback_url: Option<&'a str>,
+    /// `Some(None)` removes the link from its collection
+    pub collection_id: Option<Option<Uuid>>,
+    pub title: Option<&'a str>,
+    pub description: Option<&'a str>,
+    /// Replaces the whole metadata object
+    pub metadata: Option<&'a serde_json::Value>,
+}
+
+/// Conditions for listing links and aggregating their statistics
+#[derive(Default)]
+pub struct UrlFilter<'a> {
+    pub tag: Option<&'a str>,
+    pub collection_id: Option<Uuid>,
+    /// Only links whose metadata has this top-level key
+    pub metadata_key: Option<&'a str>,
+    /// Only links whose value for `metadata_key` has this text representation
+    pub metadata_value: Option<&'a str>,
+    /// Links in the trash instead of active ones
+    pub deleted: bool,
+    /// Only links of this account or workspace
+    pub scope: LinkScope,
+}
+
+pub async fn create_url(conn: &mut PgConnection, new_url: &NewUrl<'_>) -> Result<Uuid> {
+    let id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO urls (
+            original_url, short_code, expires_at, interstitial,
+            og_title, og_description, og_image, fallback_url, collection_id,
+            title, description, metadata, target_hash, owner_id, workspace_id,
+            require_signature
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
+        RETURNING id
+        "#,
+        new_url.original_url,
+        new_url.short_code,
+        new_url.expires_at,
+        new_url.interstitial,
+        new_url.og_title,
+        new_url.og_description,
+        new_url.og_image,
+        new_url.fallback_url,
+        new_url.collection_id,
+        new_url.title,
+        new_url.description,
+        new_url.metadata,
+        new_url.target_hash,
+        new_url.owner_id,
+        new_url.workspace_id,
+        new_url.require_signature,
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(id)
+}
+
+/// Serializes link creation for one destination until the transaction ends, so concurrent
+/// requests cannot both miss an existing link and insert a duplicate
+pub async fn lock_target_hash(conn: &mut PgConnection, target_hash: &str) -> Result<()> {
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended($1, 0))")
+        .bind(target_hash)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Finds an active, non-expiring link to the same destination with the same redirect options,
+/// notes, metadata and tags, so reusing it does not drop any of the requested values
+pub async fn find_reusable_url(
+    conn: &mut PgConnection,
+    new_url: &NewUrl<'_>,
+    tags: &[String],
+) -> Result<Option<Uuid>> {
+    let id = sqlx::query_scalar!(
+        r#"
+        SELECT id
+        FROM urls
+        WHERE target_hash = $1
+        AND expires_at IS NULL
+        AND interstitial = $2
+        AND og_title IS NOT DISTINCT FROM $3
+        AND og_description IS NOT DISTINCT FROM $4
+        AND og_image IS NOT DISTINCT FROM $5
+        AND fallback_url IS NOT DISTINCT FROM $6
+        AND collection_id IS NOT DISTINCT FROM $7
+        AND owner_id IS NOT DISTINCT FROM $8
+        AND workspace_id IS NOT DISTINCT FROM $9
+        AND require_signature = $10
+        AND title IS NOT DISTINCT FROM $11
+        AND description IS NOT DISTINCT FROM $12
+        AND metadata = $13
+        AND ARRAY(
+                SELECT t.name FROM url_tags ut JOIN tags t ON t.id = ut.tag_id
+                WHERE ut.url_id = urls.id ORDER BY t.name
+            ) = ARRAY(SELECT unnest($14::TEXT[]) ORDER BY 1)
+        AND deleted_at IS NULL
+        ORDER BY created_at
+        LIMIT 1
+        "#,
+        new_url.target_hash,
+        new_url.interstitial,
+        new_url.og_title,
+        new_url.og_description,
+        new_url.og_image,
+        new_url.fallback_url,
+        new_url.collection_id,
+        new_url.owner_id,
+        new_url.workspace_id,
+        new_url.require_signature,
+        new_url.title,
+        new_url.description,
+        new_url.metadata,
+        tags,
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(id)
+}
+
+/// Replaces the tags of a link, creating tags that do not exist yet
+pub async fn set_url_tags(conn: &mut PgConnection, url_id: Uuid, tags: &[String]) -> Result<()> {
+    sqlx::query!("DELETE FROM url_tags WHERE url_id = $1", url_id)
+        .execute(&mut *conn)
+        .await?;
+
+    if tags.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO tags (name)
+        SELECT unnest($1::TEXT[])
+        ON CONFLICT (name) DO NOTHING
+        "#,
+        tags
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO url_tags (url_id, tag_id)
+        SELECT $1, id FROM tags WHERE name = ANY($2)
+        "#,
+        url_id,
+        tags
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_url_by_id<'e, E: PgExecutor<'e>>(
+    executor: E,
+    url_id: Uuid,
+) -> Result<Option<Url>> {
     let url = sqlx::query_as!(
         Url,
         r#"
-        INSERT INTO urls (original_url, short_code, expires_at)
-        VALUES ($1, $2, $3)
-        RETURNING id, original_url, short_code, visits, created_at, expires_at
+        SELECT id, original_url, short_code, visits, created_at, expires_at,
+            interstitial, interstitial_views, interstitial_confirmations, require_signature,
+            og_title, og_description, og_image,
+            page_title, favicon_url, http_status, metadata_fetched_at,
+            fallback_url, last_check_status, last_check_latency_ms, last_checked_at,
+            check_failures, collection_id, title, description, metadata, deleted_at, owner_id,
+            workspace_id,
+            ARRAY(
+                SELECT t.name FROM url_tags ut JOIN tags t ON t.id = ut.tag_id
+                WHERE ut.url_id = urls.id ORDER BY t.name
+            ) AS "tags!"
+        FROM urls
+        WHERE id = $1
         "#,
-        original_url,
-        short_code,
-        expires_at,
+        url_id
     )
-    .fetch_one(pool)
+    .fetch_optional(executor)
     .await?;
 
     Ok(url)
@@ -31,7 +253,17 @@ pub async fn get_url_by_code(pool: &PgPool, short_code: &str) -> Result<Option<U
     let url = sqlx::query_as!(
         Url,
         r#"
-        SELECT id, original_url, short_code, visits, created_at, expires_at
+        SELECT id, original_url, short_code, visits, created_at, expires_at,
+            interstitial, interstitial_views, interstitial_confirmations, require_signature,
+            og_title, og_description, og_image,
+            page_title, favicon_url, http_status, metadata_fetched_at,
+            fallback_url, last_check_status, last_check_latency_ms, last_checked_at,
+            check_failures, collection_id, title, description, metadata, deleted_at, owner_id,
+            workspace_id,
+            ARRAY(
+                SELECT t.name FROM url_tags ut JOIN tags t ON t.id = ut.tag_id
+                WHERE ut.url_id = urls.id ORDER BY t.name
+            ) AS "tags!"
         FROM urls
         WHERE short_code = $1
         AND (expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP)
@@ -44,15 +276,103 @@ pub async fn get_url_by_code(pool: &PgPool, short_code: &str) -> Result<Option<U
     Ok(url)
 }
 
-pub async fn get_url_stats(pool: &PgPool) -> Result<Vec<Url>> {
+pub async fn get_url_stats(pool: &PgPool, filter: &UrlFilter<'_>) -> Result<Vec<Url>> {
+    list_urls(pool, filter, UrlSort::Visits, 10, 0).await
+}
+
+/// Lists all links in creation order, one page at a time. Pass the `created_at` and `id`
+/// of the last link of the previous page to get the next one.
+pub async fn list_urls_after<'e, E: PgExecutor<'e>>(
+    executor: E,
+    after: Option<(time::OffsetDateTime, Uuid)>,
+    limit: i64,
+    include_deleted: bool,
+    scope: LinkScope,
+) -> Result<Vec<Url>> {
+    let (after_created_at, after_id) = after.unzip();
     let urls = sqlx::query_as!(
         Url,
         r#"
-        SELECT id, original_url, short_code, visits, created_at, expires_at
+        SELECT id, original_url, short_code, visits, created_at, expires_at,
+            interstitial, interstitial_views, interstitial_confirmations, require_signature,
+            og_title, og_description, og_image,
+            page_title, favicon_url, http_status, metadata_fetched_at,
+            fallback_url, last_check_status, last_check_latency_ms, last_checked_at,
+            check_failures, collection_id, title, description, metadata, deleted_at, owner_id,
+            workspace_id,
+            ARRAY(
+                SELECT t.name FROM url_tags ut JOIN tags t ON t.id = ut.tag_id
+                WHERE ut.url_id = urls.id ORDER BY t.name
+            ) AS "tags!"
         FROM urls
-        ORDER BY visits DESC
-        LIMIT 10
-        "#
+        WHERE ($1::TIMESTAMPTZ IS NULL OR (created_at, id) > ($1, $2))
+        AND ($4 OR deleted_at IS NULL)
+        AND ($5::UUID IS NULL OR (owner_id = $5 AND workspace_id IS NULL))
+        AND ($6::UUID IS NULL OR workspace_id = $6)
+        ORDER BY created_at, id
+        LIMIT $3
+        "#,
+        after_created_at,
+        after_id,
+        limit,
+        include_deleted,
+        scope.owner_id,
+        scope.workspace_id
+    )
+    .fetch_all(executor)
+    .await?;
+
+    Ok(urls)
+}
+
+/// Lists links matching the filter in the given order
+pub async fn list_urls(
+    pool: &PgPool,
+    filter: &UrlFilter<'_>,
+    sort: UrlSort,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<Url>> {
+    let urls = sqlx::query_as!(
+        Url,
+        r#"
+        SELECT id, original_url, short_code, visits, created_at, expires_at,
+            interstitial, interstitial_views, interstitial_confirmations, require_signature,
+            og_title, og_description, og_image,
+            page_title, favicon_url, http_status, metadata_fetched_at,
+            fallback_url, last_check_status, last_check_latency_ms, last_checked_at,
+            check_failures, collection_id, title, description, metadata, deleted_at, owner_id,
+            workspace_id,
+            ARRAY(
+                SELECT t.name FROM url_tags ut JOIN tags t ON t.id = ut.tag_id
+                WHERE ut.url_id = urls.id ORDER BY t.name
+            ) AS "tags!"
+        FROM urls
+        WHERE ($1::TEXT IS NULL OR EXISTS (
+                SELECT 1 FROM url_tags ut JOIN tags t ON t.id = ut.tag_id
+                WHERE ut.url_id = urls.id AND t.name = $1
+            ))
+        AND ($2::UUID IS NULL OR collection_id = $2)
+        AND ($3::TEXT IS NULL OR (metadata ? $3
+            AND ($4::TEXT IS NULL OR metadata ->> $3 = $4)))
+        AND (deleted_at IS NOT NULL) = $8
+        AND ($9::UUID IS NULL OR (owner_id = $9 AND workspace_id IS NULL))
+        AND ($10::UUID IS NULL OR workspace_id = $10)
+        ORDER BY
+            CASE WHEN $5 THEN visits END DESC,
+            created_at DESC
+        LIMIT $6 OFFSET $7
+        "#,
+        filter.tag,
+        filter.collection_id,
+        filter.metadata_key,
+        filter.metadata_value,
+        sort == UrlSort::Visits,
+        limit,
+        offset,
+        filter.deleted,
+        filter.scope.owner_id,
+        filter.scope.workspace_id
     )
     .fetch_all(pool)
     .await?;
@@ -60,14 +380,31 @@ pub async fn get_url_stats(pool: &PgPool) -> Result<Vec<Url>> {
     Ok(urls)
 }
 
-pub async fn get_stats_summary(pool: &PgPool) -> Result<(i64, i64)> {
+pub async fn get_stats_summary(pool: &PgPool, filter: &UrlFilter<'_>) -> Result<(i64, i64)> {
     let row = sqlx::query!(
         r#"
         SELECT 
             COUNT(*) as "total_urls!: i64",
             COALESCE(SUM(visits), 0) as "total_visits"
         FROM urls
-        "#
+        WHERE ($1::TEXT IS NULL OR EXISTS (
+                SELECT 1 FROM url_tags ut JOIN tags t ON t.id = ut.tag_id
+                WHERE ut.url_id = urls.id AND t.name = $1
+            ))
+        AND ($2::UUID IS NULL OR collection_id = $2)
+        AND ($3::TEXT IS NULL OR (metadata ? $3
+            AND ($4::TEXT IS NULL OR metadata ->> $3 = $4)))
+        AND (deleted_at IS NOT NULL) = $5
+        AND ($6::UUID IS NULL OR (owner_id = $6 AND workspace_id IS NULL))
+        AND ($7::UUID IS NULL OR workspace_id = $7)
+        "#,
+        filter.tag,
+        filter.collection_id,
+        filter.metadata_key,
+        filter.metadata_value,
+        filter.deleted,
+        filter.scope.owner_id,
+        filter.scope.workspace_id
     )
     .fetch_one(pool)
     .await?;
@@ -81,6 +418,45 @@ pub async fn get_stats_summary(pool: &PgPool) -> Result<(i64, i64)> {
     Ok((row.total_urls, total_visits))
 }
 
+/// Link and visit totals per tag for the links matching the filter, busiest tags first
+pub async fn get_tag_stats(pool: &PgPool, filter: &UrlFilter<'_>) -> Result<Vec<TagStats>> {
+    let tags = sqlx::query_as!(
+        TagStats,
+        r#"
+        SELECT t.name,
+            COUNT(*) AS "total_urls!",
+            COALESCE(SUM(u.visits), 0)::BIGINT AS "total_visits!"
+        FROM tags t
+        JOIN url_tags ut ON ut.tag_id = t.id
+        JOIN urls u ON u.id = ut.url_id
+        WHERE ($1::TEXT IS NULL OR EXISTS (
+                SELECT 1 FROM url_tags fut JOIN tags ft ON ft.id = fut.tag_id
+                WHERE fut.url_id = u.id AND ft.name = $1
+            ))
+        AND ($2::UUID IS NULL OR u.collection_id = $2)
+        AND ($3::TEXT IS NULL OR (u.metadata ? $3
+            AND ($4::TEXT IS NULL OR u.metadata ->> $3 = $4)))
+        AND (u.deleted_at IS NOT NULL) = $5
+        AND ($6::UUID IS NULL OR (u.owner_id = $6 AND u.workspace_id IS NULL))
+        AND ($7::UUID IS NULL OR u.workspace_id = $7)
+        GROUP BY t.name
+        ORDER BY 3 DESC, t.name
+        LIMIT 50
+        "#,
+        filter.tag,
+        filter.collection_id,
+        filter.metadata_key,
+        filter.metadata_value,
+        filter.deleted,
+        filter.scope.owner_id,
+        filter.scope.workspace_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(tags)
+}
+
 pub async fn increment_visits(pool: &PgPool, url_id: Uuid) -> Result<()> {
     sqlx::query!(
         r#"
@@ -95,3 +471,2228 @@ pub async fn increment_visits(pool: &PgPool, url_id: Uuid) -> Result<()> {
 
     Ok(())
 }
+
+pub async fn record_interstitial_view(pool: &PgPool, url_id: Uuid) -> Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE urls
+        SET interstitial_views = interstitial_views + 1
+        WHERE id = $1
+        "#,
+        url_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn record_interstitial_confirmation(pool: &PgPool, url_id: Uuid) -> Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE urls
+        SET interstitial_confirmations = interstitial_confirmations + 1,
+            visits = visits + 1
+        WHERE id = $1
+        "#,
+        url_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Applies the provided changes to a link, returning its id if it exists. A new destination
+/// also resets the check results and fetched metadata of the old one.
+pub async fn update_url(
+    conn: &mut PgConnection,
+    short_code: &str,
+    changes: &UrlChanges<'_>,
+) -> Result<Option<Uuid>> {
+    let id = sqlx::query_scalar!(
+        r#"
+        UPDATE urls
+        SET interstitial = COALESCE($2, interstitial),
+            og_title = CASE WHEN $3::TEXT IS NULL THEN og_title ELSE NULLIF($3, '') END,
+            og_description = CASE WHEN $4::TEXT IS NULL THEN og_description ELSE NULLIF($4, '') END,
+            og_image = CASE WHEN $5::TEXT IS NULL THEN og_image ELSE NULLIF($5, '') END,
+            fallback_url = CASE WHEN $6::TEXT IS NULL THEN fallback_url ELSE NULLIF($6, '') END,
+            collection_id = CASE WHEN $7 THEN $8 ELSE collection_id END,
+            title = CASE WHEN $9::TEXT IS NULL THEN title ELSE NULLIF($9, '') END,
+            description = CASE WHEN $10::TEXT IS NULL THEN description ELSE NULLIF($10, '') END,
+            metadata = COALESCE($11, metadata),
+            original_url = COALESCE($12, original_url),
+            target_hash = CASE WHEN $12::TEXT IS NULL THEN target_hash ELSE $13 END,
+            expires_at = CASE WHEN $14 THEN $15 ELSE expires_at END,
+            check_failures = CASE WHEN $12 <> original_url THEN 0 ELSE check_failures END,
+            last_check_status = CASE WHEN $12 <> original_url THEN NULL ELSE last_check_status END,
+            last_check_latency_ms = CASE WHEN $12 <> original_url THEN NULL ELSE last_check_latency_ms END,
+            last_checked_at = CASE WHEN $12 <> original_url THEN NULL ELSE last_checked_at END,
+            page_title = CASE WHEN $12 <> original_url THEN NULL ELSE page_title END,
+            favicon_url = CASE WHEN $12 <> original_url THEN NULL ELSE favicon_url END,
+            http_status = CASE WHEN $12 <> original_url THEN NULL ELSE http_status END,
+            metadata_fetched_at = CASE WHEN $12 <> original_url THEN NULL ELSE metadata_fetched_at END,
+            require_signature = COALESCE($16, require_signature)
+        WHERE short_code = $1 AND deleted_at IS NULL
+        RETURNING id
+        "#,
+        short_code,
+        changes.interstitial,
+        changes.og_title,
+        changes.og_description,
+        changes.og_image,
+        changes.fallback_url,
+        changes.collection_id.is_some(),
+        changes.collection_id.flatten(),
+        changes.title,
+        changes.description,
+        changes.metadata,
+        changes.original_url.map(|(url, _)| url),
+        changes.original_url.map(|(_, hash)| hash),
+        changes.expires_at.is_some(),
+        changes.expires_at.flatten(),
+        changes.require_signature,
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(id)
+}
+
+pub async fn store_destination_metadata(
+    pool: &PgPool,
+    url_id: Uuid,
+    metadata: &DestinationMetadata,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE urls
+        SET page_title = $2,
+            favicon_url = $3,
+            http_status = $4,
+            metadata_fetched_at = CURRENT_TIMESTAMP
+        WHERE id = $1
+        "#,
+        url_id,
+        metadata.page_title,
+        metadata.favicon_url,
+        metadata.http_status
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Active links whose destination has not been checked within `recheck_secs`
+pub async fn get_links_due_for_check(
+    pool: &PgPool,
+    recheck_secs: i64,
+    limit: i64,
+) -> Result<Vec<LinkToCheck>> {
+    let links = sqlx::query_as!(
+        LinkToCheck,
+        r#"
+        SELECT id, short_code, original_url
+        FROM urls
+        WHERE (expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP)
+        AND deleted_at IS NULL
+        AND (last_checked_at IS NULL
+            OR last_checked_at < CURRENT_TIMESTAMP - make_interval(secs => $1))
+        ORDER BY last_checked_at NULLS FIRST
+        LIMIT $2
+        "#,
+        recheck_secs as f64,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(links)
+}
+
+/// Stores a check result and returns the number of consecutive failed checks
+pub async fn record_link_check(
+    pool: &PgPool,
+    url_id: Uuid,
+    status: Option<i32>,
+    latency_ms: Option<i32>,
+    broken: bool,
+) -> Result<i32> {
+    let failures = sqlx::query_scalar!(
+        r#"
+        UPDATE urls
+        SET last_check_status = $2,
+            last_check_latency_ms = $3,
+            last_checked_at = CURRENT_TIMESTAMP,
+            check_failures = CASE WHEN $4 THEN check_failures + 1 ELSE 0 END
+        WHERE id = $1
+        RETURNING check_failures
+        "#,
+        url_id,
+        status,
+        latency_ms,
+        broken
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(failures)
+}
+
+/// Promotes a link's fallback to its destination and clears the fallback, so the broken
+/// destination is not switched back to later. The target hash is cleared so the link is no
+/// longer reused for either destination. Returns the short code, or `None` when the link
+/// has no fallback.
+pub async fn switch_to_fallback(conn: &mut PgConnection, url_id: Uuid) -> Result<Option<String>> {
+    let short_code = sqlx::query_scalar!(
+        r#"
+        UPDATE urls
+        SET original_url = fallback_url,
+            fallback_url = NULL,
+            target_hash = NULL,
+            check_failures = 0,
+            last_check_status = NULL,
+            last_checked_at = NULL
+        WHERE id = $1 AND fallback_url IS NOT NULL
+        RETURNING short_code
+        "#,
+        url_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(short_code)
+}
+
+/// Active links whose latest check failed, most persistently broken first
+pub async fn get_broken_links(
+    pool: &PgPool,
+    scope: LinkScope,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<Url>> {
+    let urls = sqlx::query_as!(
+        Url,
+        r#"
+        SELECT id, original_url, short_code, visits, created_at, expires_at,
+            interstitial, interstitial_views, interstitial_confirmations, require_signature,
+            og_title, og_description, og_image,
+            page_title, favicon_url, http_status, metadata_fetched_at,
+            fallback_url, last_check_status, last_check_latency_ms, last_checked_at,
+            check_failures, collection_id, title, description, metadata, deleted_at, owner_id,
+            workspace_id,
+            ARRAY(
+                SELECT t.name FROM url_tags ut JOIN tags t ON t.id = ut.tag_id
+                WHERE ut.url_id = urls.id ORDER BY t.name
+            ) AS "tags!"
+        FROM urls
+        WHERE check_failures > 0
+        AND (expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP)
+        AND deleted_at IS NULL
+        AND ($3::UUID IS NULL OR (owner_id = $3 AND workspace_id IS NULL))
+        AND ($4::UUID IS NULL OR workspace_id = $4)
+        ORDER BY check_failures DESC, last_checked_at DESC
+        LIMIT $1 OFFSET $2
+        "#,
+        limit,
+        offset,
+        scope.owner_id,
+        scope.workspace_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(urls)
+}
+
+/// Finds a link and its owner by short code, whether it is active, expired or in the trash
+pub async fn get_url_owner(pool: &PgPool, short_code: &str) -> Result<Option<UrlOwner>> {
+    let owner = sqlx::query_as!(
+        UrlOwner,
+        "SELECT id, owner_id, workspace_id FROM urls WHERE short_code = $1",
+        short_code
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(owner)
+}
+
+/// Snapshots the redirect settings of a link as its next revision, unless they are the same
+/// as in the latest revision
+pub async fn record_revision(
+    conn: &mut PgConnection,
+    url_id: Uuid,
+    change: RevisionChange,
+    changed_by: Option<&str>,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        WITH latest AS (
+            SELECT * FROM url_revisions
+            WHERE url_id = $1
+            ORDER BY revision DESC
+            LIMIT 1
+        )
+        INSERT INTO url_revisions (
+            url_id, revision, change, changed_by, original_url, expires_at, interstitial,
+            fallback_url, og_title, og_description, og_image, require_signature
+        )
+        SELECT u.id, COALESCE((SELECT revision FROM latest), 0) + 1, $2, $3,
+            u.original_url, u.expires_at, u.interstitial,
+            u.fallback_url, u.og_title, u.og_description, u.og_image, u.require_signature
+        FROM urls u
+        WHERE u.id = $1
+        AND NOT EXISTS (
+            SELECT 1 FROM latest l
+            WHERE l.original_url = u.original_url
+            AND l.expires_at IS NOT DISTINCT FROM u.expires_at
+            AND l.interstitial = u.interstitial
+            AND l.fallback_url IS NOT DISTINCT FROM u.fallback_url
+            AND l.og_title IS NOT DISTINCT FROM u.og_title
+            AND l.og_description IS NOT DISTINCT FROM u.og_description
+            AND l.og_image IS NOT DISTINCT FROM u.og_image
+            AND l.require_signature = u.require_signature
+        )
+        "#,
+        url_id,
+        change as RevisionChange,
+        changed_by
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Revisions of a link, newest first
+pub async fn list_url_revisions(
+    pool: &PgPool,
+    url_id: Uuid,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<UrlRevision>> {
+    let revisions = sqlx::query_as!(
+        UrlRevision,
+        r#"
+        SELECT revision, change AS "change: RevisionChange", changed_by,
+            original_url, expires_at, interstitial,
+            fallback_url, og_title, og_description, og_image, require_signature, created_at
+        FROM url_revisions
+        WHERE url_id = $1
+        ORDER BY revision DESC
+        LIMIT $2 OFFSET $3
+        "#,
+        url_id,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(revisions)
+}
+
+pub async fn get_url_revision(
+    conn: &mut PgConnection,
+    url_id: Uuid,
+    revision: i32,
+) -> Result<Option<UrlRevision>> {
+    let revision = sqlx::query_as!(
+        UrlRevision,
+        r#"
+        SELECT revision, change AS "change: RevisionChange", changed_by,
+            original_url, expires_at, interstitial,
+            fallback_url, og_title, og_description, og_image, require_signature, created_at
+        FROM url_revisions
+        WHERE url_id = $1 AND revision = $2
+        "#,
+        url_id,
+        revision
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(revision)
+}
+
+/// Moves a link to the trash, returning whether an active link was found
+pub async fn soft_delete_url<'e, E: PgExecutor<'e>>(executor: E, short_code: &str) -> Result<bool> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE urls
+        SET deleted_at = CURRENT_TIMESTAMP
+        WHERE short_code = $1 AND deleted_at IS NULL
+        "#,
+        short_code
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Takes a link out of the trash, returning its id if it was there
+pub async fn restore_deleted_url<'e, E: PgExecutor<'e>>(
+    executor: E,
+    short_code: &str,
+) -> Result<Option<Uuid>> {
+    let id = sqlx::query_scalar!(
+        r#"
+        UPDATE urls
+        SET deleted_at = NULL
+        WHERE short_code = $1 AND deleted_at IS NOT NULL
+        RETURNING id
+        "#,
+        short_code
+    )
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(id)
+}
+
+/// Permanently deletes up to `limit` links that have been in the trash longer than
+/// `retention_secs` and retires their short codes like `remove_expired_urls` does. Returns
+/// the purged short codes.
+pub async fn purge_deleted_urls(
+    pool: &PgPool,
+    retention_secs: i64,
+    limit: i64,
+    reuse_after_secs: Option<i64>,
+) -> Result<Vec<String>> {
+    let short_codes = sqlx::query_scalar!(
+        r#"
+        WITH purged AS (
+            DELETE FROM urls
+            WHERE id IN (
+                SELECT id FROM urls
+                WHERE deleted_at < CURRENT_TIMESTAMP - make_interval(secs => $1)
+                ORDER BY deleted_at
+                LIMIT $2
+            )
+            RETURNING short_code
+        ),
+        retired AS (
+            INSERT INTO retired_short_codes (short_code, reusable_at)
+            SELECT short_code, CURRENT_TIMESTAMP + make_interval(secs => $3)
+            FROM purged
+            ON CONFLICT (short_code) DO UPDATE
+            SET retired_at = EXCLUDED.retired_at, reusable_at = EXCLUDED.reusable_at
+        )
+        SELECT short_code AS "short_code!" FROM purged
+        "#,
+        retention_secs as f64,
+        limit,
+        reuse_after_secs.map(|secs| secs as f64)
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(short_codes)
+}
+
+/// Removes links expired for longer than the grace period, copying them to `archived_urls`
+/// when `archive` is set, and retires their short codes. The codes become reusable after
+/// `reuse_after_secs`, or never when it is `None`. Returns the removed short codes.
+pub async fn remove_expired_urls(
+    pool: &PgPool,
+    grace_secs: i64,
+    limit: i64,
+    archive: bool,
+    reuse_after_secs: Option<i64>,
+) -> Result<Vec<String>> {
+    // Every part of the statement sees the tags as they were before the links were deleted
+    let short_codes = sqlx::query_scalar!(
+        r#"
+        WITH expired AS (
+            SELECT id FROM urls
+            WHERE expires_at < CURRENT_TIMESTAMP - make_interval(secs => $1)
+            AND deleted_at IS NULL
+            ORDER BY expires_at
+            LIMIT $2
+            FOR UPDATE SKIP LOCKED
+        ),
+        removed AS (
+            DELETE FROM urls u
+            USING expired e
+            WHERE u.id = e.id
+            RETURNING u.id, u.short_code, u.original_url, u.visits, u.created_at, u.expires_at,
+                u.title, u.description, u.metadata
+        ),
+        archived AS (
+            INSERT INTO archived_urls (
+                id, short_code, original_url, visits, created_at, expires_at,
+                title, description, metadata, tags
+            )
+            SELECT r.id, r.short_code, r.original_url, r.visits, r.created_at, r.expires_at,
+                r.title, r.description, r.metadata,
+                ARRAY(
+                    SELECT t.name FROM url_tags ut JOIN tags t ON t.id = ut.tag_id
+                    WHERE ut.url_id = r.id ORDER BY t.name
+                )
+            FROM removed r
+            WHERE $3
+            ON CONFLICT (id) DO NOTHING
+        ),
+        retired AS (
+            INSERT INTO retired_short_codes (short_code, reusable_at)
+            SELECT short_code, CURRENT_TIMESTAMP + make_interval(secs => $4)
+            FROM removed
+            ON CONFLICT (short_code) DO UPDATE
+            SET retired_at = EXCLUDED.retired_at, reusable_at = EXCLUDED.reusable_at
+        )
+        SELECT short_code AS "short_code!" FROM removed
+        "#,
+        grace_secs as f64,
+        limit,
+        archive,
+        reuse_after_secs.map(|secs| secs as f64)
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(short_codes)
+}
+
+/// Frees retired short codes whose quarantine has ended, returning how many were freed
+pub async fn release_retired_short_codes(pool: &PgPool) -> Result<u64> {
+    let result =
+        sqlx::query!("DELETE FROM retired_short_codes WHERE reusable_at <= CURRENT_TIMESTAMP")
+            .execute(pool)
+            .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Checks whether a short code belonged to a cleaned up link and is still quarantined
+pub async fn is_short_code_retired(conn: &mut PgConnection, short_code: &str) -> Result<bool> {
+    let retired = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM retired_short_codes
+            WHERE short_code = $1
+            AND (reusable_at IS NULL OR reusable_at > CURRENT_TIMESTAMP)
+        ) AS "retired!"
+        "#,
+        short_code
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(retired)
+}
+
+/// Adds a background job to the shared job list, or updates its schedule
+pub async fn register_job(pool: &PgPool, name: &str, schedule: &str) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO scheduled_jobs (name, schedule)
+        VALUES ($1, $2)
+        ON CONFLICT (name) DO UPDATE SET schedule = EXCLUDED.schedule
+        "#,
+        name,
+        schedule
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Marks a job as started unless any instance started it at or after `due_before`,
+/// returning whether this instance should run it
+pub async fn claim_job_run(
+    pool: &PgPool,
+    name: &str,
+    due_before: time::OffsetDateTime,
+    instance: &str,
+) -> Result<bool> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE scheduled_jobs
+        SET last_started_at = CURRENT_TIMESTAMP,
+            last_finished_at = NULL,
+            last_duration_ms = NULL,
+            last_outcome = $3,
+            last_message = NULL,
+            last_instance = $4
+        WHERE name = $1
+        AND (last_started_at IS NULL OR last_started_at < $2)
+        "#,
+        name,
+        due_before,
+        JobOutcome::Running as JobOutcome,
+        instance
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+pub async fn finish_job_run(
+    pool: &PgPool,
+    name: &str,
+    outcome: JobOutcome,
+    message: &str,
+    duration_ms: i64,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE scheduled_jobs
+        SET last_finished_at = CURRENT_TIMESTAMP,
+            last_duration_ms = $4,
+            last_outcome = $2,
+            last_message = $3,
+            run_count = run_count + 1,
+            failure_count = failure_count + CASE WHEN $2 = 'failed' THEN 1 ELSE 0 END
+        WHERE name = $1
+        "#,
+        name,
+        outcome as JobOutcome,
+        message,
+        duration_ms
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn list_scheduled_jobs(pool: &PgPool) -> Result<Vec<JobStatus>> {
+    let jobs = sqlx::query_as!(
+        JobStatus,
+        r#"
+        SELECT name, schedule, last_started_at, last_finished_at, last_duration_ms,
+            last_outcome AS "last_outcome: JobOutcome", last_message, last_instance,
+            run_count, failure_count
+        FROM scheduled_jobs
+        ORDER BY name
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(jobs)
+}
+
+/// Takes a session-level advisory lock if no other session holds it
+pub async fn try_advisory_lock(conn: &mut PgConnection, key: i64) -> Result<bool> {
+    let locked = sqlx::query_scalar!(r#"SELECT pg_try_advisory_lock($1) AS "locked!""#, key)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    Ok(locked)
+}
+
+pub async fn advisory_unlock(conn: &mut PgConnection, key: i64) -> Result<()> {
+    sqlx::query_scalar!(r#"SELECT pg_advisory_unlock($1) AS "unlocked!""#, key)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn create_user(
+    pool: &PgPool,
+    email: &str,
+    password_hash: &str,
+    is_admin: bool,
+) -> Result<User> {
+    let user = sqlx::query_as!(
+        User,
+        r#"
+        INSERT INTO users (email, password_hash, is_admin)
+        VALUES ($1, $2, $3)
+        RETURNING id, email, is_admin, created_at
+        "#,
+        email,
+        password_hash,
+        is_admin
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(user)
+}
+
+/// Finds an account with its password hash for logging in. Accounts of an identity
+/// provider have no password.
+pub async fn get_user_credentials(
+    pool: &PgPool,
+    email: &str,
+) -> Result<Option<(User, Option<String>)>> {
+    let row = sqlx::query!(
+        r#"
+        SELECT id, email, is_admin, created_at, password_hash
+        FROM users
+        WHERE email = $1
+        "#,
+        email
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|row| {
+        (
+            User {
+                id: row.id,
+                email: row.email,
+                is_admin: row.is_admin,
+                created_at: row.created_at,
+            },
+            row.password_hash,
+        )
+    }))
+}
+
+/// Finds the account of an identity provider's subject
+pub async fn get_external_user(pool: &PgPool, subject: &str) -> Result<Option<User>> {
+    let user = sqlx::query_as!(
+        User,
+        r#"
+        SELECT id, email, is_admin, created_at
+        FROM users
+        WHERE external_subject = $1
+        "#,
+        subject
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(user)
+}
+
+/// Brings an identity provider's account up to date with the claims of its latest token
+pub async fn update_external_user(
+    pool: &PgPool,
+    id: Uuid,
+    email: &str,
+    is_admin: bool,
+) -> Result<User> {
+    let user = sqlx::query_as!(
+        User,
+        r#"
+        UPDATE users
+        SET email = $2, is_admin = $3
+        WHERE id = $1
+        RETURNING id, email, is_admin, created_at
+        "#,
+        id,
+        email,
+        is_admin
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(user)
+}
+
+/// Creates the account of an identity provider's subject, or links the existing account
+/// with the same email to it when the email is verified. Returns `None` if that account
+/// is linked to another subject or the email is not verified.
+pub async fn link_external_user(
+    pool: &PgPool,
+    subject: &str,
+    email: &str,
+    email_verified: bool,
+    is_admin: bool,
+) -> Result<Option<User>> {
+    let user = sqlx::query_as!(
+        User,
+        r#"
+        INSERT INTO users (email, is_admin, external_subject)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (email) DO UPDATE
+        SET external_subject = EXCLUDED.external_subject, is_admin = EXCLUDED.is_admin
+        WHERE users.external_subject IS NULL AND $4
+        RETURNING id, email, is_admin, created_at
+        "#,
+        email,
+        is_admin,
+        subject,
+        email_verified
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(user)
+}
+
+pub async fn list_users(pool: &PgPool, limit: i64, offset: i64) -> Result<Vec<User>> {
+    let users = sqlx::query_as!(
+        User,
+        r#"
+        SELECT id, email, is_admin, created_at
+        FROM users
+        ORDER BY created_at
+        LIMIT $1 OFFSET $2
+        "#,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(users)
+}
+
+/// Stores a new session, dropping the expired sessions of the same account
+pub async fn create_session(
+    pool: &PgPool,
+    token_hash: &str,
+    user_id: Uuid,
+    expires_at: time::OffsetDateTime,
+) -> Result<()> {
+    sqlx::query!(
+        "DELETE FROM user_sessions WHERE user_id = $1 AND expires_at <= CURRENT_TIMESTAMP",
+        user_id
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO user_sessions (token_hash, user_id, expires_at)
+        VALUES ($1, $2, $3)
+        "#,
+        token_hash,
+        user_id,
+        expires_at
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Finds the account of an unexpired session
+pub async fn get_session_user(pool: &PgPool, token_hash: &str) -> Result<Option<User>> {
+    let user = sqlx::query_as!(
+        User,
+        r#"
+        SELECT u.id, u.email, u.is_admin, u.created_at
+        FROM user_sessions s
+        JOIN users u ON u.id = s.user_id
+        WHERE s.token_hash = $1 AND s.expires_at > CURRENT_TIMESTAMP
+        "#,
+        token_hash
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(user)
+}
+
+pub async fn delete_session(pool: &PgPool, token_hash: &str) -> Result<()> {
+    sqlx::query!(
+        "DELETE FROM user_sessions WHERE token_hash = $1",
+        token_hash
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Creates a workspace with its creator as owner
+pub async fn create_workspace(
+    conn: &mut PgConnection,
+    name: &str,
+    owner: Uuid,
+) -> Result<Workspace> {
+    let workspace = sqlx::query_as!(
+        Workspace,
+        r#"
+        INSERT INTO workspaces (name)
+        VALUES ($1)
+        RETURNING id, name, created_at, 'owner' AS "role?: WorkspaceRole"
+        "#,
+        name
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO workspace_members (workspace_id, user_id, role) VALUES ($1, $2, 'owner')",
+        workspace.id,
+        owner
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(workspace)
+}
+
+/// Lists the workspaces an account is a member of, or all of them, with the account's role
+/// in each
+pub async fn list_workspaces(
+    pool: &PgPool,
+    user_id: Option<Uuid>,
+    all: bool,
+) -> Result<Vec<Workspace>> {
+    let workspaces = sqlx::query_as!(
+        Workspace,
+        r#"
+        SELECT w.id, w.name, w.created_at, m.role AS "role?: WorkspaceRole"
+        FROM workspaces w
+        LEFT JOIN workspace_members m ON m.workspace_id = w.id AND m.user_id = $1
+        WHERE $2 OR m.user_id IS NOT NULL
+        ORDER BY w.name, w.id
+        "#,
+        user_id,
+        all
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(workspaces)
+}
+
+pub async fn get_workspace(pool: &PgPool, id: Uuid, user_id: Uuid) -> Result<Option<Workspace>> {
+    let workspace = sqlx::query_as!(
+        Workspace,
+        r#"
+        SELECT w.id, w.name, w.created_at, m.role AS "role?: WorkspaceRole"
+        FROM workspaces w
+        LEFT JOIN workspace_members m ON m.workspace_id = w.id AND m.user_id = $2
+        WHERE w.id = $1
+        "#,
+        id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(workspace)
+}
+
+pub async fn workspace_exists<'e, E: PgExecutor<'e>>(executor: E, id: Uuid) -> Result<bool> {
+    let exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM workspaces WHERE id = $1) AS "exists!""#,
+        id
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok(exists)
+}
+
+pub async fn get_member_role<'e, E: PgExecutor<'e>>(
+    executor: E,
+    workspace_id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<WorkspaceRole>> {
+    let role = sqlx::query_scalar!(
+        r#"
+        SELECT role AS "role: WorkspaceRole"
+        FROM workspace_members
+        WHERE workspace_id = $1 AND user_id = $2
+        "#,
+        workspace_id,
+        user_id
+    )
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(role)
+}
+
+pub async fn list_members(pool: &PgPool, workspace_id: Uuid) -> Result<Vec<WorkspaceMember>> {
+    let members = sqlx::query_as!(
+        WorkspaceMember,
+        r#"
+        SELECT m.user_id, u.email, m.role AS "role: WorkspaceRole", m.created_at AS joined_at
+        FROM workspace_members m
+        JOIN users u ON u.id = m.user_id
+        WHERE m.workspace_id = $1
+        ORDER BY m.created_at, u.email
+        "#,
+        workspace_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(members)
+}
+
+/// Locks a workspace until the transaction ends, so membership changes that depend on the
+/// number of owners run one at a time
+pub async fn lock_workspace(conn: &mut PgConnection, workspace_id: Uuid) -> Result<()> {
+    sqlx::query!(
+        "SELECT id FROM workspaces WHERE id = $1 FOR UPDATE",
+        workspace_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn count_owners(conn: &mut PgConnection, workspace_id: Uuid) -> Result<i64> {
+    let owners = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM workspace_members
+        WHERE workspace_id = $1 AND role = 'owner'
+        "#,
+        workspace_id
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(owners)
+}
+
+pub async fn set_member_role(
+    conn: &mut PgConnection,
+    workspace_id: Uuid,
+    user_id: Uuid,
+    role: WorkspaceRole,
+) -> Result<()> {
+    sqlx::query!(
+        "UPDATE workspace_members SET role = $3 WHERE workspace_id = $1 AND user_id = $2",
+        workspace_id,
+        user_id,
+        role as WorkspaceRole
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn remove_member(
+    conn: &mut PgConnection,
+    workspace_id: Uuid,
+    user_id: Uuid,
+) -> Result<()> {
+    sqlx::query!(
+        "DELETE FROM workspace_members WHERE workspace_id = $1 AND user_id = $2",
+        workspace_id,
+        user_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn create_invitation(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    email: &str,
+    role: WorkspaceRole,
+    token_hash: &str,
+    invited_by: Option<Uuid>,
+    expires_at: time::OffsetDateTime,
+) -> Result<Invitation> {
+    let invitation = sqlx::query_as!(
+        Invitation,
+        r#"
+        INSERT INTO workspace_invitations
+            (workspace_id, email, role, token_hash, invited_by, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, workspace_id, email, role AS "role: WorkspaceRole", invited_by,
+            created_at, expires_at, NULL::TEXT AS token
+        "#,
+        workspace_id,
+        email,
+        role as WorkspaceRole,
+        token_hash,
+        invited_by,
+        expires_at
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(invitation)
+}
+
+/// Invitations that were neither accepted nor expired yet, newest first
+pub async fn list_invitations(pool: &PgPool, workspace_id: Uuid) -> Result<Vec<Invitation>> {
+    let invitations = sqlx::query_as!(
+        Invitation,
+        r#"
+        SELECT id, workspace_id, email, role AS "role: WorkspaceRole", invited_by,
+            created_at, expires_at, NULL::TEXT AS token
+        FROM workspace_invitations
+        WHERE workspace_id = $1
+        AND accepted_at IS NULL
+        AND expires_at > CURRENT_TIMESTAMP
+        ORDER BY created_at DESC
+        "#,
+        workspace_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(invitations)
+}
+
+/// Deletes a pending invitation, returning whether there was one
+pub async fn delete_invitation(pool: &PgPool, workspace_id: Uuid, id: Uuid) -> Result<bool> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM workspace_invitations
+        WHERE workspace_id = $1 AND id = $2 AND accepted_at IS NULL
+        "#,
+        workspace_id,
+        id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Marks a pending invitation for this email as accepted, returning its workspace and role
+pub async fn claim_invitation(
+    conn: &mut PgConnection,
+    token_hash: &str,
+    email: &str,
+) -> Result<Option<(Uuid, WorkspaceRole)>> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE workspace_invitations
+        SET accepted_at = CURRENT_TIMESTAMP
+        WHERE token_hash = $1
+        AND lower(email) = $2
+        AND accepted_at IS NULL
+        AND expires_at > CURRENT_TIMESTAMP
+        RETURNING workspace_id, role AS "role: WorkspaceRole"
+        "#,
+        token_hash,
+        email
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(row.map(|row| (row.workspace_id, row.role)))
+}
+
+/// Adds a member, or gives an existing member the role if it is higher than theirs
+pub async fn add_member(
+    conn: &mut PgConnection,
+    workspace_id: Uuid,
+    user_id: Uuid,
+    role: WorkspaceRole,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO workspace_members (workspace_id, user_id, role)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (workspace_id, user_id) DO UPDATE
+        SET role = EXCLUDED.role
+        WHERE array_position(ARRAY['viewer', 'editor', 'admin', 'owner'], EXCLUDED.role)
+            > array_position(ARRAY['viewer', 'editor', 'admin', 'owner'], workspace_members.role)
+        "#,
+        workspace_id,
+        user_id,
+        role as WorkspaceRole
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn create_workspace_api_key(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    name: &str,
+    role: WorkspaceRole,
+    key_hash: &str,
+    key_prefix: &str,
+    created_by: Option<Uuid>,
+) -> Result<WorkspaceApiKey> {
+    let key = sqlx::query_as!(
+        WorkspaceApiKey,
+        r#"
+        INSERT INTO workspace_api_keys
+            (workspace_id, name, role, key_hash, key_prefix, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, workspace_id, name, role AS "role: WorkspaceRole", key_prefix,
+            created_by, created_at, last_used_at, NULL::TEXT AS key
+        "#,
+        workspace_id,
+        name,
+        role as WorkspaceRole,
+        key_hash,
+        key_prefix,
+        created_by
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(key)
+}
+
+/// Keys of a workspace that were not revoked, newest first
+pub async fn list_workspace_api_keys(
+    pool: &PgPool,
+    workspace_id: Uuid,
+) -> Result<Vec<WorkspaceApiKey>> {
+    let keys = sqlx::query_as!(
+        WorkspaceApiKey,
+        r#"
+        SELECT id, workspace_id, name, role AS "role: WorkspaceRole", key_prefix,
+            created_by, created_at, last_used_at, NULL::TEXT AS key
+        FROM workspace_api_keys
+        WHERE workspace_id = $1 AND revoked_at IS NULL
+        ORDER BY created_at DESC
+        "#,
+        workspace_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(keys)
+}
+
+/// Revokes a key, returning whether an active key was found
+pub async fn revoke_workspace_api_key(pool: &PgPool, workspace_id: Uuid, id: Uuid) -> Result<bool> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE workspace_api_keys
+        SET revoked_at = CURRENT_TIMESTAMP
+        WHERE workspace_id = $1 AND id = $2 AND revoked_at IS NULL
+        "#,
+        workspace_id,
+        id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Finds an active key by hash and records that it was used
+pub async fn use_workspace_api_key(
+    pool: &PgPool,
+    key_hash: &str,
+) -> Result<Option<(Uuid, WorkspaceAccess)>> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE workspace_api_keys
+        SET last_used_at = CURRENT_TIMESTAMP
+        WHERE key_hash = $1 AND revoked_at IS NULL
+        RETURNING id, workspace_id, role AS "role: WorkspaceRole"
+        "#,
+        key_hash
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|row| {
+        (
+            row.id,
+            WorkspaceAccess {
+                workspace_id: row.workspace_id,
+                role: row.role,
+            },
+        )
+    }))
+}
+
+pub async fn create_collection(
+    pool: &PgPool,
+    name: &str,
+    description: Option<&str>,
+    workspace_id: Option<Uuid>,
+) -> Result<Collection> {
+    let collection = sqlx::query_as!(
+        Collection,
+        r#"
+        INSERT INTO collections (name, description, workspace_id)
+        VALUES ($1, $2, $3)
+        RETURNING id, name, description, workspace_id, created_at, 0::BIGINT AS "url_count!"
+        "#,
+        name,
+        description,
+        workspace_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(collection)
+}
+
+/// Collections visible in `scope`: those of its workspace, those outside any workspace for
+/// personal scopes, and every collection for the default scope
+pub async fn list_collections<'e, E: PgExecutor<'e>>(
+    executor: E,
+    scope: LinkScope,
+) -> Result<Vec<Collection>> {
+    let collections = sqlx::query_as!(
+        Collection,
+        r#"
+        SELECT c.id, c.name, c.description, c.workspace_id, c.created_at,
+            (SELECT COUNT(*) FROM urls u WHERE u.collection_id = c.id) AS "url_count!"
+        FROM collections c
+        WHERE ($1::UUID IS NULL OR c.workspace_id IS NULL)
+        AND ($2::UUID IS NULL OR c.workspace_id = $2)
+        ORDER BY c.name
+        "#,
+        scope.owner_id,
+        scope.workspace_id
+    )
+    .fetch_all(executor)
+    .await?;
+
+    Ok(collections)
+}
+
+pub async fn get_collection(
+    pool: &PgPool,
+    id: Uuid,
+    scope: LinkScope,
+) -> Result<Option<Collection>> {
+    let collection = sqlx::query_as!(
+        Collection,
+        r#"
+        SELECT c.id, c.name, c.description, c.workspace_id, c.created_at,
+            (SELECT COUNT(*) FROM urls u WHERE u.collection_id = c.id) AS "url_count!"
+        FROM collections c
+        WHERE c.id = $1
+        AND ($2::UUID IS NULL OR c.workspace_id IS NULL)
+        AND ($3::UUID IS NULL OR c.workspace_id = $3)
+        "#,
+        id,
+        scope.owner_id,
+        scope.workspace_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(collection)
+}
+
+/// Renames a collection or changes its description; an empty description removes it
+pub async fn update_collection(
+    pool: &PgPool,
+    id: Uuid,
+    scope: LinkScope,
+    name: Option<&str>,
+    description: Option<&str>,
+) -> Result<Option<Collection>> {
+    let collection = sqlx::query_as!(
+        Collection,
+        r#"
+        UPDATE collections c
+        SET name = COALESCE($4, c.name),
+            description = CASE WHEN $5::TEXT IS NULL THEN c.description ELSE NULLIF($5, '') END
+        WHERE c.id = $1
+        AND ($2::UUID IS NULL OR c.workspace_id IS NULL)
+        AND ($3::UUID IS NULL OR c.workspace_id = $3)
+        RETURNING c.id, c.name, c.description, c.workspace_id, c.created_at,
+            (SELECT COUNT(*) FROM urls u WHERE u.collection_id = c.id) AS "url_count!"
+        "#,
+        id,
+        scope.owner_id,
+        scope.workspace_id,
+        name,
+        description
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(collection)
+}
+
+/// Deletes a collection; its links stay but no longer belong to a collection
+pub async fn delete_collection(pool: &PgPool, id: Uuid, scope: LinkScope) -> Result<bool> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM collections
+        WHERE id = $1
+        AND ($2::UUID IS NULL OR workspace_id IS NULL)
+        AND ($3::UUID IS NULL OR workspace_id = $3)
+        "#,
+        id,
+        scope.owner_id,
+        scope.workspace_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn list_tag_names<'e, E: PgExecutor<'e>>(executor: E) -> Result<Vec<String>> {
+    let names = sqlx::query_scalar!("SELECT name FROM tags ORDER BY name")
+        .fetch_all(executor)
+        .await?;
+
+    Ok(names)
+}
+
+/// Creates tags that do not exist yet, returning how many were created
+pub async fn create_tags(conn: &mut PgConnection, names: &[String]) -> Result<u64> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO tags (name)
+        SELECT unnest($1::TEXT[])
+        ON CONFLICT (name) DO NOTHING
+        "#,
+        names
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Finds the collection a restored one collides with, by ID first and then by name within
+/// the same workspace
+pub async fn find_existing_collection(
+    conn: &mut PgConnection,
+    collection: &Collection,
+) -> Result<Option<Uuid>> {
+    let existing = sqlx::query_scalar!(
+        r#"
+        SELECT id
+        FROM collections
+        WHERE id = $1 OR (name = $2 AND workspace_id IS NOT DISTINCT FROM $3)
+        ORDER BY id = $1 DESC
+        LIMIT 1
+        "#,
+        collection.id,
+        collection.name,
+        collection.workspace_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(existing)
+}
+
+/// Inserts a collection from a backup, keeping its ID and creation time
+pub async fn restore_collection(conn: &mut PgConnection, collection: &Collection) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO collections (id, name, description, workspace_id, created_at)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        collection.id,
+        collection.name,
+        collection.description,
+        collection.workspace_id,
+        collection.created_at
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Gives an existing collection the name and description of a restored one
+pub async fn overwrite_collection(
+    conn: &mut PgConnection,
+    id: Uuid,
+    collection: &Collection,
+) -> Result<()> {
+    sqlx::query!(
+        "UPDATE collections SET name = $2, description = $3 WHERE id = $1",
+        id,
+        collection.name,
+        collection.description
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Every account with the columns a backup keeps
+pub async fn list_backup_accounts<'e, E: PgExecutor<'e>>(
+    executor: E,
+) -> Result<Vec<AccountBackup>> {
+    let accounts = sqlx::query_as!(
+        AccountBackup,
+        r#"
+        SELECT id, email, password_hash, is_admin, external_subject, created_at
+        FROM users
+        ORDER BY created_at, id
+        "#
+    )
+    .fetch_all(executor)
+    .await?;
+
+    Ok(accounts)
+}
+
+pub async fn list_backup_workspaces<'e, E: PgExecutor<'e>>(
+    executor: E,
+) -> Result<Vec<WorkspaceBackup>> {
+    let workspaces = sqlx::query_as!(
+        WorkspaceBackup,
+        "SELECT id, name, created_at FROM workspaces ORDER BY created_at, id"
+    )
+    .fetch_all(executor)
+    .await?;
+
+    Ok(workspaces)
+}
+
+pub async fn list_backup_memberships<'e, E: PgExecutor<'e>>(
+    executor: E,
+) -> Result<Vec<MembershipBackup>> {
+    let memberships = sqlx::query_as!(
+        MembershipBackup,
+        r#"
+        SELECT workspace_id, user_id, role AS "role: WorkspaceRole", created_at
+        FROM workspace_members
+        ORDER BY workspace_id, created_at, user_id
+        "#
+    )
+    .fetch_all(executor)
+    .await?;
+
+    Ok(memberships)
+}
+
+/// Every workspace API key, including revoked ones, by its hash
+pub async fn list_backup_api_keys<'e, E: PgExecutor<'e>>(executor: E) -> Result<Vec<ApiKeyBackup>> {
+    let keys = sqlx::query_as!(
+        ApiKeyBackup,
+        r#"
+        SELECT id, workspace_id, name, role AS "role: WorkspaceRole", key_hash, key_prefix,
+            created_by, created_at, last_used_at, revoked_at
+        FROM workspace_api_keys
+        ORDER BY created_at, id
+        "#
+    )
+    .fetch_all(executor)
+    .await?;
+
+    Ok(keys)
+}
+
+/// Finds the account a restored one collides with, by ID first and then by email or
+/// identity provider subject
+pub async fn find_existing_account(
+    conn: &mut PgConnection,
+    account: &AccountBackup,
+) -> Result<Option<Uuid>> {
+    let existing = sqlx::query_scalar!(
+        r#"
+        SELECT id
+        FROM users
+        WHERE id = $1 OR email = $2 OR external_subject = $3
+        ORDER BY id = $1 DESC
+        LIMIT 1
+        "#,
+        account.id,
+        account.email,
+        account.external_subject
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(existing)
+}
+
+/// Inserts an account from a backup, keeping its ID, password hash and creation time
+pub async fn restore_account(conn: &mut PgConnection, account: &AccountBackup) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO users (id, email, password_hash, is_admin, external_subject, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        account.id,
+        account.email,
+        account.password_hash,
+        account.is_admin,
+        account.external_subject,
+        account.created_at
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Gives an existing account the email, credentials and admin flag of a restored one
+pub async fn overwrite_account(
+    conn: &mut PgConnection,
+    id: Uuid,
+    account: &AccountBackup,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET email = $2, password_hash = $3, is_admin = $4, external_subject = $5
+        WHERE id = $1
+        "#,
+        id,
+        account.email,
+        account.password_hash,
+        account.is_admin,
+        account.external_subject
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Inserts a workspace from a backup, or renames the existing one with its ID.
+/// Returns `false` when the workspace already existed.
+pub async fn restore_workspace(
+    conn: &mut PgConnection,
+    workspace: &WorkspaceBackup,
+    overwrite: bool,
+) -> Result<bool> {
+    let inserted = sqlx::query_scalar!(
+        r#"
+        INSERT INTO workspaces (id, name, created_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (id) DO UPDATE SET name = EXCLUDED.name WHERE $4
+        RETURNING (xmax = 0) AS "inserted!"
+        "#,
+        workspace.id,
+        workspace.name,
+        workspace.created_at,
+        overwrite
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(inserted.unwrap_or(false))
+}
+
+/// Inserts a membership from a backup, or changes the role of the existing one.
+/// Returns `false` when the membership already existed.
+pub async fn restore_membership(
+    conn: &mut PgConnection,
+    workspace_id: Uuid,
+    user_id: Uuid,
+    membership: &MembershipBackup,
+    overwrite: bool,
+) -> Result<bool> {
+    let inserted = sqlx::query_scalar!(
+        r#"
+        INSERT INTO workspace_members (workspace_id, user_id, role, created_at)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (workspace_id, user_id) DO UPDATE SET role = EXCLUDED.role WHERE $5
+        RETURNING (xmax = 0) AS "inserted!"
+        "#,
+        workspace_id,
+        user_id,
+        membership.role as WorkspaceRole,
+        membership.created_at,
+        overwrite
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(inserted.unwrap_or(false))
+}
+
+/// Finds the API key a restored one collides with, by ID first and then by hash
+pub async fn find_existing_api_key(
+    conn: &mut PgConnection,
+    id: Uuid,
+    key_hash: &str,
+) -> Result<Option<Uuid>> {
+    let existing = sqlx::query_scalar!(
+        r#"
+        SELECT id
+        FROM workspace_api_keys
+        WHERE id = $1 OR key_hash = $2
+        ORDER BY id = $1 DESC
+        LIMIT 1
+        "#,
+        id,
+        key_hash
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    Ok(existing)
+}
+
+/// Inserts a workspace API key from a backup, or replaces the existing key with `id`
+pub async fn restore_api_key(
+    conn: &mut PgConnection,
+    id: Uuid,
+    key: &ApiKeyBackup,
+    workspace_id: Uuid,
+    created_by: Option<Uuid>,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO workspace_api_keys (
+            id, workspace_id, name, role, key_hash, key_prefix, created_by, created_at,
+            last_used_at, revoked_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        ON CONFLICT (id) DO UPDATE
+        SET workspace_id = EXCLUDED.workspace_id,
+            name = EXCLUDED.name,
+            role = EXCLUDED.role,
+            key_hash = EXCLUDED.key_hash,
+            key_prefix = EXCLUDED.key_prefix,
+            created_by = EXCLUDED.created_by,
+            last_used_at = EXCLUDED.last_used_at,
+            revoked_at = EXCLUDED.revoked_at
+        "#,
+        id,
+        workspace_id,
+        key.name,
+        key.role as WorkspaceRole,
+        key.key_hash,
+        key.key_prefix,
+        created_by,
+        key.created_at,
+        key.last_used_at,
+        key.revoked_at
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn account_exists<'e, E: PgExecutor<'e>>(executor: E, id: Uuid) -> Result<bool> {
+    let exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM users WHERE id = $1) AS "exists!""#,
+        id
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok(exists)
+}
+
+/// Whether a link with the ID or short code of a restored link already exists
+pub async fn url_conflicts(conn: &mut PgConnection, id: Uuid, short_code: &str) -> Result<bool> {
+    let exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM urls WHERE id = $1 OR short_code = $2) AS "exists!""#,
+        id,
+        short_code
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(exists)
+}
+
+/// Deletes the links a restored link replaces, returning their short codes
+pub async fn delete_conflicting_urls(
+    conn: &mut PgConnection,
+    id: Uuid,
+    short_code: &str,
+) -> Result<Vec<String>> {
+    let short_codes = sqlx::query_scalar!(
+        "DELETE FROM urls WHERE id = $1 OR short_code = $2 RETURNING short_code",
+        id,
+        short_code
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(short_codes)
+}
+
+/// Inserts a link from a backup with all of its columns and statistics
+pub async fn restore_url(conn: &mut PgConnection, url: &Url, target_hash: &str) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO urls (
+            id, original_url, short_code, visits, created_at, expires_at,
+            interstitial, interstitial_views, interstitial_confirmations,
+            og_title, og_description, og_image,
+            page_title, favicon_url, http_status, metadata_fetched_at,
+            fallback_url, last_check_status, last_check_latency_ms, last_checked_at,
+            check_failures, collection_id, title, description, metadata, target_hash,
+            deleted_at, owner_id, workspace_id, require_signature
+        )
+        VALUES (
+            $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13,
+            $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27,
+            $28, $29, $30
+        )
+        "#,
+        url.id,
+        url.original_url,
+        url.short_code,
+        url.visits,
+        url.created_at,
+        url.expires_at,
+        url.interstitial,
+        url.interstitial_views,
+        url.interstitial_confirmations,
+        url.og_title,
+        url.og_description,
+        url.og_image,
+        url.page_title,
+        url.favicon_url,
+        url.http_status,
+        url.metadata_fetched_at,
+        url.fallback_url,
+        url.last_check_status,
+        url.last_check_latency_ms,
+        url.last_checked_at,
+        url.check_failures,
+        url.collection_id,
+        url.title,
+        url.description,
+        url.metadata,
+        target_hash,
+        url.deleted_at,
+        url.owner_id,
+        url.workspace_id,
+        url.require_signature,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn create_import_job(pool: &PgPool, source: &str, total_rows: i32) -> Result<ImportJob> {
+    let job = sqlx::query_as!(
+        ImportJob,
+        r#"
+        INSERT INTO import_jobs (source, total_rows)
+        VALUES ($1, $2)
+        RETURNING id, source, status AS "status: ImportStatus", total_rows, processed_rows,
+            created_count, reused_count, failed_count, error, created_at, finished_at
+        "#,
+        source,
+        total_rows
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(job)
+}
+
+pub async fn get_import_job(pool: &PgPool, id: Uuid) -> Result<Option<ImportJob>> {
+    let job = sqlx::query_as!(
+        ImportJob,
+        r#"
+        SELECT id, source, status AS "status: ImportStatus", total_rows, processed_rows,
+            created_count, reused_count, failed_count, error, created_at, finished_at
+        FROM import_jobs
+        WHERE id = $1
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(job)
+}
+
+pub async fn list_import_jobs(pool: &PgPool, limit: i64, offset: i64) -> Result<Vec<ImportJob>> {
+    let jobs = sqlx::query_as!(
+        ImportJob,
+        r#"
+        SELECT id, source, status AS "status: ImportStatus", total_rows, processed_rows,
+            created_count, reused_count, failed_count, error, created_at, finished_at
+        FROM import_jobs
+        ORDER BY created_at DESC
+        LIMIT $1 OFFSET $2
+        "#,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(jobs)
+}
+
+pub async fn set_import_job_status(
+    pool: &PgPool,
+    id: Uuid,
+    status: ImportStatus,
+    error: Option<&str>,
+) -> Result<()> {
+    let finished = matches!(status, ImportStatus::Completed | ImportStatus::Failed);
+    sqlx::query!(
+        r#"
+        UPDATE import_jobs
+        SET status = $2,
+            error = $3,
+            finished_at = CASE WHEN $4 THEN CURRENT_TIMESTAMP ELSE finished_at END
+        WHERE id = $1
+        "#,
+        id,
+        status as ImportStatus,
+        error,
+        finished
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Adds the outcome of a chunk of rows to a job's counters and stores the failed rows
+pub async fn record_import_progress(
+    pool: &PgPool,
+    id: Uuid,
+    processed: i32,
+    created: i32,
+    reused: i32,
+    errors: &[ImportError],
+) -> Result<()> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE import_jobs
+        SET processed_rows = processed_rows + $2,
+            created_count = created_count + $3,
+            reused_count = reused_count + $4,
+            failed_count = failed_count + $5
+        WHERE id = $1
+        "#,
+        id,
+        processed,
+        created,
+        reused,
+        errors.len() as i32
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if !errors.is_empty() {
+        let row_numbers: Vec<i32> = errors.iter().map(|e| e.row_number).collect();
+        let original_urls: Vec<Option<String>> =
+            errors.iter().map(|e| e.original_url.clone()).collect();
+        let messages: Vec<String> = errors.iter().map(|e| e.error.clone()).collect();
+        sqlx::query!(
+            r#"
+            INSERT INTO import_job_errors (job_id, row_number, original_url, error)
+            SELECT $1, * FROM UNNEST($2::INTEGER[], $3::TEXT[], $4::TEXT[])
+            ON CONFLICT (job_id, row_number) DO NOTHING
+            "#,
+            id,
+            &row_numbers,
+            &original_urls as &[Option<String>],
+            &messages
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Carries over the creation time and visit count an imported link had in its previous system
+pub async fn restore_link_history(
+    pool: &PgPool,
+    ids: &[Uuid],
+    created_ats: &[Option<time::OffsetDateTime>],
+    visits: &[i64],
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE urls
+        SET created_at = COALESCE(history.created_at, urls.created_at),
+            visits = urls.visits + history.visits
+        FROM UNNEST($1::UUID[], $2::TIMESTAMPTZ[], $3::BIGINT[])
+            AS history(id, created_at, visits)
+        WHERE urls.id = history.id
+        "#,
+        ids,
+        created_ats as &[Option<time::OffsetDateTime>],
+        visits
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_import_errors(pool: &PgPool, id: Uuid) -> Result<Vec<ImportError>> {
+    let errors = sqlx::query_as!(
+        ImportError,
+        r#"
+        SELECT row_number, original_url, error
+        FROM import_job_errors
+        WHERE job_id = $1
+        ORDER BY row_number
+        "#,
+        id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(errors)
+}
+
+/// Marks jobs that were still queued or running as failed, since their input was lost
+/// when the server stopped. Returns the number of affected jobs.
+pub async fn fail_interrupted_import_jobs(pool: &PgPool) -> Result<u64> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE import_jobs
+        SET status = 'failed',
+            error = 'Interrupted by a server restart',
+            finished_at = CURRENT_TIMESTAMP
+        WHERE status IN ('queued', 'running')
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Appends an event to the audit log
+pub async fn insert_audit_event<'e, E: PgExecutor<'e>>(
+    executor: E,
+    event: &NewAuditEvent,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO audit_events (
+            actor, actor_id, action, target_type, target_id, workspace_id, changes,
+            client_ip, request_id
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        "#,
+        event.actor,
+        event.actor_id,
+        event.action,
+        event.target_type(),
+        event.target_id,
+        event.workspace_id,
+        event.changes,
+        event.client_ip,
+        event.request_id
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// Lists audit events matching the query, newest first, older than the event `before`
+pub async fn list_audit_events(
+    pool: &PgPool,
+    query: &AuditQuery,
+    before: Option<i64>,
+    limit: i64,
+) -> Result<Vec<AuditEvent>> {
+    let events = sqlx::query_as!(
+        AuditEvent,
+        r#"
+        SELECT id, occurred_at, actor, actor_id, action, target_type, target_id, workspace_id,
+            changes, client_ip, request_id
+        FROM audit_events
+        WHERE ($1::TEXT IS NULL OR actor = $1)
+        AND ($2::TEXT IS NULL OR action = $2)
+        AND ($3::TEXT IS NULL OR target_type = $3)
+        AND ($4::TEXT IS NULL OR target_id = $4)
+        AND ($5::UUID IS NULL OR workspace_id = $5)
+        AND ($6::TIMESTAMPTZ IS NULL OR occurred_at >= $6)
+        AND ($7::TIMESTAMPTZ IS NULL OR occurred_at < $7)
+        AND ($8::BIGINT IS NULL OR id < $8)
+        ORDER BY id DESC
+        LIMIT $9
+        "#,
+        query.actor,
+        query.action,
+        query.target_type,
+        query.target_id,
+        query.workspace_id,
+        query.since,
+        query.until,
+        before,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(events)
+}
+
+/// Whether the account, workspace or workspace API key a quota would limit exists
+pub async fn quota_subject_exists(
+    pool: &PgPool,
+    subject_type: QuotaSubject,
+    subject_id: Uuid,
+) -> Result<bool> {
+    let exists = sqlx::query_scalar!(
+        r#"
+        SELECT CASE $1
+            WHEN 'user' THEN EXISTS(SELECT 1 FROM users WHERE id = $2)
+            WHEN 'workspace' THEN EXISTS(SELECT 1 FROM workspaces WHERE id = $2)
+            ELSE EXISTS(SELECT 1 FROM workspace_api_keys WHERE id = $2)
+        END AS "exists!"
+        "#,
+        subject_type as QuotaSubject,
+        subject_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(exists)
+}
+
+/// Creates or replaces the quota of a subject
+pub async fn set_quota(
+    pool: &PgPool,
+    subject_type: QuotaSubject,
+    subject_id: Uuid,
+    daily_links: Option<i64>,
+    total_links: Option<i64>,
+    active_links: Option<i64>,
+) -> Result<Quota> {
+    let quota = sqlx::query_as!(
+        Quota,
+        r#"
+        INSERT INTO quotas (subject_type, subject_id, daily_links, total_links, active_links)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (subject_type, subject_id) DO UPDATE
+        SET daily_links = EXCLUDED.daily_links,
+            total_links = EXCLUDED.total_links,
+            active_links = EXCLUDED.active_links,
+            updated_at = CURRENT_TIMESTAMP
+        RETURNING subject_type AS "subject_type: QuotaSubject", subject_id,
+            daily_links, total_links, active_links, updated_at
+        "#,
+        subject_type as QuotaSubject,
+        subject_id,
+        daily_links,
+        total_links,
+        active_links
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(quota)
+}
+
+pub async fn delete_quota(
+    pool: &PgPool,
+    subject_type: QuotaSubject,
+    subject_id: Uuid,
+) -> Result<bool> {
+    let result = sqlx::query!(
+        "DELETE FROM quotas WHERE subject_type = $1 AND subject_id = $2",
+        subject_type as QuotaSubject,
+        subject_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn list_quotas(pool: &PgPool, limit: i64, offset: i64) -> Result<Vec<Quota>> {
+    let quotas = sqlx::query_as!(
+        Quota,
+        r#"
+        SELECT subject_type AS "subject_type: QuotaSubject", subject_id,
+            daily_links, total_links, active_links, updated_at
+        FROM quotas
+        ORDER BY updated_at DESC
+        LIMIT $1 OFFSET $2
+        "#,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(quotas)
+}
+
+pub async fn get_quota(
+    pool: &PgPool,
+    subject_type: QuotaSubject,
+    subject_id: Uuid,
+) -> Result<Option<Quota>> {
+    let quota = sqlx::query_as!(
+        Quota,
+        r#"
+        SELECT subject_type AS "subject_type: QuotaSubject", subject_id,
+            daily_links, total_links, active_links, updated_at
+        FROM quotas
+        WHERE subject_type = $1 AND subject_id = $2
+        "#,
+        subject_type as QuotaSubject,
+        subject_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(quota)
+}
+
+/// Counts the links of an account or workspace that are neither deleted nor expired
+/// Adds `delta` to the links a quota subject has created, returning the new count
+pub async fn add_quota_total(
+    pool: &PgPool,
+    subject_type: QuotaSubject,
+    subject_id: Uuid,
+    delta: i64,
+) -> Result<i64> {
+    let links = sqlx::query_scalar!(
+        r#"
+        INSERT INTO quota_totals (subject_type, subject_id, links)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (subject_type, subject_id) DO UPDATE
+        SET links = quota_totals.links + EXCLUDED.links
+        RETURNING links
+        "#,
+        subject_type as QuotaSubject,
+        subject_id,
+        delta
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(links)
+}
+
+/// The links a quota subject has created
+pub async fn get_quota_total(
+    pool: &PgPool,
+    subject_type: QuotaSubject,
+    subject_id: Uuid,
+) -> Result<i64> {
+    let links = sqlx::query_scalar!(
+        r#"
+        SELECT links FROM quota_totals
+        WHERE subject_type = $1 AND subject_id = $2
+        "#,
+        subject_type as QuotaSubject,
+        subject_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(links.unwrap_or_default())
+}
+
+pub async fn count_active_links(pool: &PgPool, scope: LinkScope) -> Result<i64> {
+    let count = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM urls
+        WHERE deleted_at IS NULL
+        AND (expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP)
+        AND ($1::UUID IS NULL OR (owner_id = $1 AND workspace_id IS NULL))
+        AND ($2::UUID IS NULL OR workspace_id = $2)
+        "#,
+        scope.owner_id,
+        scope.workspace_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(count)
+}
@@ -0,0 +1,396 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use reqwest::{header, redirect, Method, StatusCode};
+use thiserror::Error;
+use url::Url;
+
+const MAX_REDIRECTS: usize = 5;
+const USER_AGENT: &str = concat!("tommy-shortener/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Error, Debug)]
+pub enum FetchError {
+    #[error("Invalid URL: {0}")]
+    InvalidUrl(String),
+
+    #[error("Destination not allowed: {0}")]
+    Blocked(String),
+
+    #[error("Too many redirects")]
+    TooManyRedirects,
+
+    #[error("Timed out")]
+    Timeout,
+
+    #[error("DNS resolution failed: {0}")]
+    Dns(#[from] std::io::Error),
+
+    #[error("Request failed: {0}")]
+    Request(#[from] reqwest::Error),
+}
+
+/// Outcome of fetching an external URL
+#[derive(Debug)]
+pub struct FetchResponse {
+    pub status: StatusCode,
+    /// URL of the last response after following redirects
+    pub url: Url,
+    pub content_type: Option<String>,
+    /// Body, truncated to the configured limit (empty for HEAD requests)
+    pub body: Vec<u8>,
+    /// Time until the response headers of the last hop arrived
+    pub elapsed: Duration,
+}
+
+/// HTTP client for fetching user-supplied destinations.
+///
+/// Every hop (including redirects) is resolved up front and rejected when it points at a
+/// private, loopback or otherwise non-public address. The connection is then pinned to the
+/// checked address so a second DNS lookup cannot swap it for an internal one.
+#[derive(Clone, Debug)]
+pub struct SafeHttpClient {
+    timeout: Duration,
+    max_body_bytes: usize,
+    allow_private_ips: bool,
+}
+
+impl SafeHttpClient {
+    pub fn new(timeout: Duration, max_body_bytes: usize, allow_private_ips: bool) -> Self {
+        Self {
+            timeout,
+            max_body_bytes,
+            allow_private_ips,
+        }
+    }
+
+    pub async fn fetch(&self, method: Method, url: &str) -> Result<FetchResponse, FetchError> {
+        let started = Instant::now();
+        let mut url = Url::parse(url).map_err(|e| FetchError::InvalidUrl(e.to_string()))?;
+
+        for _ in 0..=MAX_REDIRECTS {
+            let response = self.send(method.clone(), &url, started).await?;
+
+            if response.status().is_redirection() {
+                if let Some(location) = response
+                    .headers()
+                    .get(header::LOCATION)
+                    .and_then(|value| value.to_str().ok())
+                {
+                    url = url
+                        .join(location)
+                        .map_err(|e| FetchError::InvalidUrl(e.to_string()))?;
+                    continue;
+                }
+            }
+
+            let elapsed = started.elapsed();
+            return self.read_response(response, url, elapsed).await;
+        }
+
+        Err(FetchError::TooManyRedirects)
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        url: &Url,
+        started: Instant,
+    ) -> Result<reqwest::Response, FetchError> {
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return Err(FetchError::Blocked(format!(
+                "unsupported scheme {}",
+                url.scheme()
+            )));
+        }
+
+        let host = url
+            .host_str()
+            .ok_or_else(|| FetchError::InvalidUrl("missing host".to_string()))?;
+        let port = url
+            .port_or_known_default()
+            .ok_or_else(|| FetchError::InvalidUrl("missing port".to_string()))?;
+        let address = self.resolve(host, port).await?;
+
+        // The whole fetch, including redirects, has to fit in the timeout
+        let remaining = self
+            .timeout
+            .checked_sub(started.elapsed())
+            .filter(|remaining| !remaining.is_zero())
+            .ok_or(FetchError::Timeout)?;
+
+        let client = reqwest::Client::builder()
+            .redirect(redirect::Policy::none())
+            .connect_timeout(remaining.min(Duration::from_secs(3)))
+            .timeout(remaining)
+            .user_agent(USER_AGENT)
+            .resolve(host.trim_start_matches('[').trim_end_matches(']'), address)
+            .build()?;
+
+        Ok(client.request(method, url.clone()).send().await?)
+    }
+
+    async fn resolve(&self, host: &str, port: u16) -> Result<SocketAddr, FetchError> {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let addresses: Vec<SocketAddr> = match host.parse::<IpAddr>() {
+            Ok(ip) => vec![SocketAddr::new(ip, port)],
+            Err(_) => tokio::net::lookup_host((host, port)).await?.collect(),
+        };
+
+        if !self.allow_private_ips {
+            // Reject the host if any of its addresses is internal, not only the first one
+            if let Some(address) = addresses.iter().find(|a| !is_public_ip(a.ip())) {
+                return Err(FetchError::Blocked(format!(
+                    "{} resolves to non-public address {}",
+                    host,
+                    address.ip()
+                )));
+            }
+        }
+
+        addresses
+            .into_iter()
+            .next()
+            .ok_or_else(|| FetchError::Blocked(format!("{} has no addresses", host)))
+    }
+
+    async fn read_response(
+        &self,
+        mut response: reqwest::Response,
+        url: Url,
+        elapsed: Duration,
+    ) -> Result<FetchResponse, FetchError> {
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            let remaining = self.max_body_bytes - body.len();
+            if chunk.len() >= remaining {
+                body.extend_from_slice(&chunk[..remaining]);
+                break;
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        Ok(FetchResponse {
+            status,
+            url,
+            content_type,
+            body,
+            elapsed,
+        })
+    }
+}
+
+/// Whether an address is routable on the public internet
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Shared address space (100.64.0.0/10)
+                || (a == 100 && (64..128).contains(&b))
+                // "This network" and reserved ranges
+                || a == 0
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => match embedded_ipv4(ip) {
+            Some(embedded) => is_public_ip(IpAddr::V4(embedded)),
+            None => {
+                let [a, b, ..] = ip.segments();
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+                    // Documentation (2001:db8::/32)
+                    || (a == 0x2001 && b == 0x0db8))
+            }
+        },
+    }
+}
+
+/// The IPv4 address carried inside an IPv6 address by the transition mechanisms that
+/// reach IPv4 hosts: IPv4-mapped (::ffff:0:0/96), IPv4-compatible (::/96), NAT64
+/// (64:ff9b::/96) and 6to4 (2002::/16)
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    if let Some(mapped) = ip.to_ipv4_mapped() {
+        return Some(mapped);
+    }
+
+    let segments = ip.segments();
+    let octets = ip.octets();
+    let last_four = Ipv4Addr::new(octets[12], octets[13], octets[14], octets[15]);
+    match segments {
+        // Leave :: and ::1 to the unspecified and loopback checks
+        [0, 0, 0, 0, 0, 0, ..] if !ip.is_unspecified() && !ip.is_loopback() => Some(last_four),
+        [0x64, 0xff9b, 0, 0, 0, 0, ..] => Some(last_four),
+        [0x2002, ..] => Some(Ipv4Addr::new(octets[2], octets[3], octets[4], octets[5])),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn client(max_body_bytes: usize, allow_private_ips: bool) -> SafeHttpClient {
+        SafeHttpClient::new(Duration::from_secs(5), max_body_bytes, allow_private_ips)
+    }
+
+    fn redirect_to(location: &str) -> ResponseTemplate {
+        ResponseTemplate::new(302).insert_header("Location", location)
+    }
+
+    #[tokio::test]
+    async fn follows_redirects_to_the_final_response() {
+        let server = MockServer::start().await;
+        Mock::given(path("/start"))
+            .respond_with(redirect_to("/middle"))
+            .mount(&server)
+            .await;
+        Mock::given(path("/middle"))
+            .respond_with(redirect_to("/end"))
+            .mount(&server)
+            .await;
+        Mock::given(path("/end"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("done"))
+            .mount(&server)
+            .await;
+
+        let response = client(1024, true)
+            .fetch(Method::GET, &format!("{}/start", server.uri()))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.url.path(), "/end");
+        assert_eq!(response.body, b"done");
+    }
+
+    #[tokio::test]
+    async fn stops_after_the_redirect_limit() {
+        let server = MockServer::start().await;
+        for hop in 0..MAX_REDIRECTS {
+            Mock::given(path(format!("/{}", hop)))
+                .respond_with(redirect_to(&format!("/{}", hop + 1)))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(path(format!("/{}", MAX_REDIRECTS)))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        Mock::given(path(format!("/{}", MAX_REDIRECTS + 1)))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        // Exactly the limit is still followed
+        let response = client(1024, true)
+            .fetch(Method::GET, &format!("{}/0", server.uri()))
+            .await
+            .unwrap();
+        assert_eq!(response.url.path(), format!("/{}", MAX_REDIRECTS));
+
+        // One more hop is not
+        Mock::given(path(format!("/{}", MAX_REDIRECTS)))
+            .respond_with(redirect_to(&format!("/{}", MAX_REDIRECTS + 1)))
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        let result = client(1024, true)
+            .fetch(Method::GET, &format!("{}/0", server.uri()))
+            .await;
+        assert_matches!(result, Err(FetchError::TooManyRedirects));
+    }
+
+    #[tokio::test]
+    async fn truncates_the_body_to_the_limit() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("a".repeat(10_000)))
+            .mount(&server)
+            .await;
+
+        let response = client(100, true)
+            .fetch(Method::GET, &server.uri())
+            .await
+            .unwrap();
+
+        assert_eq!(response.body.len(), 100);
+    }
+
+    #[tokio::test]
+    async fn refuses_private_addresses() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+
+        let result = client(1024, false).fetch(Method::GET, &server.uri()).await;
+
+        assert_matches!(result, Err(FetchError::Blocked(_)));
+    }
+
+    #[tokio::test]
+    async fn refuses_embedded_internal_addresses_and_other_schemes() {
+        let result = client(1024, false)
+            .fetch(Method::GET, "http://[64:ff9b::7f00:1]/")
+            .await;
+        assert_matches!(result, Err(FetchError::Blocked(_)));
+
+        let result = client(1024, false)
+            .fetch(Method::GET, "ftp://example.com/")
+            .await;
+        assert_matches!(result, Err(FetchError::Blocked(_)));
+    }
+
+    #[test]
+    fn classifies_public_and_internal_addresses() {
+        let public = ["93.184.216.34", "2606:2800:220:1::1", "64:ff9b::5db8:d822"];
+        for ip in public {
+            assert!(is_public_ip(ip.parse().unwrap()), "{} should be public", ip);
+        }
+
+        let internal = [
+            "10.0.0.1",
+            "127.0.0.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            // IPv4-mapped, IPv4-compatible, NAT64 and 6to4 forms of internal addresses
+            "::ffff:127.0.0.1",
+            "::10.0.0.1",
+            "64:ff9b::a9fe:a9fe",
+            "2002:7f00:1::",
+            "2002:a00:1::1",
+            "2001:db8::1",
+        ];
+        for ip in internal {
+            assert!(
+                !is_public_ip(ip.parse().unwrap()),
+                "{} should be internal",
+                ip
+            );
+        }
+    }
+}
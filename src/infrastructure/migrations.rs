@@ -1,35 +1,148 @@
 use sqlx::PgPool;
 
+// Migrations in the order they must be applied. Each one is recorded in
+// `schema_migrations` once it has run, so new entries only need to be appended.
+const MIGRATIONS: &[(&str, &str)] = &[
+    (
+        "20240214_create_urls_table",
+        include_str!("../../migrations/20240214_create_urls_table.sql"),
+    ),
+    (
+        "20261019_add_interstitial_to_urls",
+        include_str!("../../migrations/20261019_add_interstitial_to_urls.sql"),
+    ),
+    (
+        "20261019_add_social_card_to_urls",
+        include_str!("../../migrations/20261019_add_social_card_to_urls.sql"),
+    ),
+    (
+        "20261019_add_destination_metadata_to_urls",
+        include_str!("../../migrations/20261019_add_destination_metadata_to_urls.sql"),
+    ),
+    (
+        "20261019_add_link_checks_to_urls",
+        include_str!("../../migrations/20261019_add_link_checks_to_urls.sql"),
+    ),
+    (
+        "20261019_create_tags_and_collections",
+        include_str!("../../migrations/20261019_create_tags_and_collections.sql"),
+    ),
+    (
+        "20261019_add_notes_and_metadata_to_urls",
+        include_str!("../../migrations/20261019_add_notes_and_metadata_to_urls.sql"),
+    ),
+    (
+        "20261019_add_target_hash_to_urls",
+        include_str!("../../migrations/20261019_add_target_hash_to_urls.sql"),
+    ),
+    (
+        "20261019_allow_custom_aliases",
+        include_str!("../../migrations/20261019_allow_custom_aliases.sql"),
+    ),
+    (
+        "20261019_create_import_jobs",
+        include_str!("../../migrations/20261019_create_import_jobs.sql"),
+    ),
+    (
+        "20261019_add_deleted_at_to_urls",
+        include_str!("../../migrations/20261019_add_deleted_at_to_urls.sql"),
+    ),
+    (
+        "20261019_create_url_revisions",
+        include_str!("../../migrations/20261019_create_url_revisions.sql"),
+    ),
+    (
+        "20261019_create_archived_urls",
+        include_str!("../../migrations/20261019_create_archived_urls.sql"),
+    ),
+    (
+        "20261019_create_scheduled_jobs",
+        include_str!("../../migrations/20261019_create_scheduled_jobs.sql"),
+    ),
+    (
+        "20261019_create_users",
+        include_str!("../../migrations/20261019_create_users.sql"),
+    ),
+    (
+        "20261019_create_workspaces",
+        include_str!("../../migrations/20261019_create_workspaces.sql"),
+    ),
+    (
+        "20261019_add_user_external_subject",
+        include_str!("../../migrations/20261019_add_user_external_subject.sql"),
+    ),
+    (
+        "20261019_create_audit_events",
+        include_str!("../../migrations/20261019_create_audit_events.sql"),
+    ),
+    (
+        "20261019_create_quotas",
+        include_str!("../../migrations/20261019_create_quotas.sql"),
+    ),
+    (
+        "20261019_add_require_signature_to_urls",
+        include_str!("../../migrations/20261019_add_require_signature_to_urls.sql"),
+    ),
+    (
+        "20261019_add_require_signature_to_url_revisions",
+        include_str!("../../migrations/20261019_add_require_signature_to_url_revisions.sql"),
+    ),
+    (
+        "20261019_create_quota_totals",
+        include_str!("../../migrations/20261019_create_quota_totals.sql"),
+    ),
+    (
+        "20261019_add_workspace_to_collections",
+        include_str!("../../migrations/20261019_add_workspace_to_collections.sql"),
+    ),
+    (
+        "20261019_add_reused_count_to_import_jobs",
+        include_str!("../../migrations/20261019_add_reused_count_to_import_jobs.sql"),
+    ),
+];
+
 pub async fn run_migrations_if_needed(pool: &PgPool) -> anyhow::Result<()> {
-    // Check if the table already exists
-    let table_exists = sqlx::query_scalar::<_, bool>(
-        "SELECT EXISTS (
-            SELECT FROM information_schema.tables 
-            WHERE table_name = 'urls'
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            name TEXT PRIMARY KEY,
+            applied_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
         )",
     )
-    .fetch_one(pool)
+    .execute(pool)
     .await?;
 
-    if table_exists {
-        tracing::info!("Tables already exist, skipping migrations");
+    let applied: Vec<String> = sqlx::query_scalar("SELECT name FROM schema_migrations")
+        .fetch_all(pool)
+        .await?;
+
+    let pending: Vec<_> = MIGRATIONS
+        .iter()
+        .filter(|(name, _)| !applied.iter().any(|applied| applied == name))
+        .collect();
+
+    if pending.is_empty() {
+        tracing::info!("Database schema is up to date, skipping migrations");
         return Ok(());
     }
 
-    tracing::info!("Running database migrations...");
-    // Use transaction for safety
-    let mut tx = pool.begin().await?;
-
-    // Execute each statement separately
-    for statement in include_str!("../../migrations/20240214_create_urls_table.sql")
-        .split(';')
-        .map(str::trim)
-        .filter(|s| !s.is_empty())
-    {
-        sqlx::query(statement).execute(&mut *tx).await?;
-    }
+    tracing::info!("Running {} database migration(s)...", pending.len());
+    for (name, sql) in pending {
+        // Use a transaction per migration for safety
+        let mut tx = pool.begin().await?;
 
-    tx.commit().await?;
+        // Execute each statement separately
+        for statement in sql.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+            sqlx::query(statement).execute(&mut *tx).await?;
+        }
+
+        sqlx::query("INSERT INTO schema_migrations (name) VALUES ($1)")
+            .bind(name)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        tracing::info!("Applied migration {}", name);
+    }
     tracing::info!("Migrations completed successfully");
 
     Ok(())
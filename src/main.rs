@@ -1,24 +1,43 @@
 mod api;
 mod application;
+mod cli;
 mod config;
 mod domain;
 mod error;
 mod infrastructure;
-mod models;
 
 use crate::{
     api::{
         api_docs::swagger_routes,
-        routes::{admin_routes, health_routes, url_routes},
+        middleware::request_context,
+        routes::{account_routes, admin_routes, health_routes, url_routes},
     },
-    application::url_service::UrlService,
+    application::{
+        audit::RequestContext,
+        destination_metadata::run_metadata_worker,
+        expired_cleanup::ExpiredLinkCleaner,
+        link_checker::{LinkCheckSettings, LinkChecker},
+        schedule::Schedule,
+        scheduler::Scheduler,
+        trash_purge::TrashPurger,
+        url_service::UrlService,
+    },
+    cli::Command,
     config::AppConfig,
-    infrastructure::cache::Cache,
+    infrastructure::{cache::Cache, http_client::SafeHttpClient},
 };
-use axum::Router;
+use axum::{body::Body, http::Request, middleware, Extension, Router};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::sync::mpsc;
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+// Links created while the queue is full go without metadata
+const METADATA_QUEUE_SIZE: usize = 1024;
+// How long running background jobs get to record their cancellation on shutdown
+const JOB_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
 async fn connect_with_retry<F, Fut, T, E>(
     connect_fn: F,
     max_attempts: usize,
@@ -80,6 +99,7 @@ async fn main() -> anyhow::Result<()> {
 
     // Load configuration
     dotenv::dotenv().ok();
+    let command = Command::from_args()?;
     let config = AppConfig::from_env()?;
 
     // If running in development mode without explicit flag, show warning about detailed errors
@@ -115,21 +135,187 @@ async fn main() -> anyhow::Result<()> {
         connect_with_retry(|| redis_client.get_connection_manager(), 10, "Redis").await?;
     let cache = Cache::new(redis_conn);
 
+    // Command line tasks run to completion without starting the server or its workers
+    if !matches!(command, Command::Serve) {
+        let url_service = UrlService::new(config, postgres_pool, cache, None);
+        return match command {
+            Command::Import { format, path } => cli::import(&url_service, format, &path).await,
+            Command::Backup { path } => cli::backup(&url_service, &path).await,
+            Command::Restore { path, policy } => cli::restore(&url_service, &path, policy).await,
+            Command::Serve => Ok(()),
+        };
+    }
+
+    // Imports keep their input in memory, so jobs cut short by a restart cannot resume
+    let interrupted =
+        infrastructure::database::fail_interrupted_import_jobs(&postgres_pool).await?;
+    if interrupted > 0 {
+        tracing::warn!("Marked {} interrupted import job(s) as failed", interrupted);
+    }
+
+    // Start the background worker fetching destination metadata for new links
+    let metadata_jobs = if config.metadata_fetch_enabled {
+        let (sender, receiver) = mpsc::channel(METADATA_QUEUE_SIZE);
+        let client = SafeHttpClient::new(
+            Duration::from_secs(config.fetch_timeout_secs),
+            config.fetch_max_body_bytes,
+            config.fetch_allow_private_ips,
+        );
+        tokio::spawn(run_metadata_worker(
+            client,
+            postgres_pool.clone(),
+            receiver,
+            config.metadata_fetch_concurrency,
+        ));
+        Some(sender)
+    } else {
+        None
+    };
+
+    let mut scheduler = Scheduler::new(postgres_pool.clone(), &config.database_url);
+
+    // Periodically check for broken destinations
+    if config.link_check_enabled {
+        // Only the status matters, so no response body is read
+        let client = SafeHttpClient::new(
+            Duration::from_secs(config.fetch_timeout_secs),
+            0,
+            config.fetch_allow_private_ips,
+        );
+        let checker = LinkChecker::new(
+            client,
+            postgres_pool.clone(),
+            cache.clone(),
+            LinkCheckSettings::from_config(&config),
+        );
+        let schedule = Schedule::from_config(
+            config.link_check_cron.as_deref(),
+            Duration::from_secs(config.link_check_interval_secs),
+        )
+        .map_err(|e| anyhow::anyhow!("LINK_CHECK_CRON: {}", e))?;
+        scheduler.add(checker, schedule);
+    }
+
+    // Periodically purge links that have been in the trash too long
+    let purger = TrashPurger::new(
+        postgres_pool.clone(),
+        Duration::from_secs(config.trash_retention_days * 24 * 60 * 60),
+        config
+            .expired_code_reuse_days
+            .map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+    );
+    let schedule = Schedule::from_config(
+        config.trash_purge_cron.as_deref(),
+        Duration::from_secs(config.trash_purge_interval_secs),
+    )
+    .map_err(|e| anyhow::anyhow!("TRASH_PURGE_CRON: {}", e))?;
+    scheduler.add(purger, schedule);
+
     // Initialize URL service
-    let url_service = UrlService::new(config, postgres_pool, cache);
+    let url_service = UrlService::new(
+        config.clone(),
+        postgres_pool.clone(),
+        cache.clone(),
+        metadata_jobs,
+    );
+
+    // Surface a misconfigured identity provider at startup rather than on the first request
+    if let Err(err) = url_service.load_jwt_keys().await {
+        tracing::warn!("{}", err);
+    }
+
+    // Periodically clean up links that expired long ago
+    if config.expired_cleanup_enabled {
+        let cleaner =
+            ExpiredLinkCleaner::new(postgres_pool, cache, &config, url_service.cleanup_metrics());
+        let schedule = Schedule::from_config(
+            config.expired_cleanup_cron.as_deref(),
+            Duration::from_secs(config.expired_cleanup_interval_secs),
+        )
+        .map_err(|e| anyhow::anyhow!("EXPIRED_CLEANUP_CRON: {}", e))?;
+        scheduler.add(cleaner, schedule);
+    }
+
+    scheduler.start().await?;
 
     // Create router with all routes
     let app = Router::new()
         .merge(health_routes())
         .merge(url_routes())
+        .merge(account_routes())
         .merge(admin_routes())
         .merge(swagger_routes()) // Add Swagger UI routes
-        .layer(TraceLayer::new_for_http())
+        // The API key middleware reads the service from request extensions
+        .layer(Extension(url_service.clone()))
+        .layer(
+            TraceLayer::new_for_http().make_span_with(|req: &Request<Body>| {
+                let context = req.extensions().get::<RequestContext>();
+                let request_id = context
+                    .map(|context| context.request_id.as_str())
+                    .unwrap_or_default();
+                let client_ip = context
+                    .and_then(|context| context.client_ip)
+                    .map(|ip| ip.to_string())
+                    .unwrap_or_default();
+                tracing::debug_span!(
+                    "request",
+                    method = %req.method(),
+                    uri = %req.uri(),
+                    version = ?req.version(),
+                    request_id,
+                    client_ip
+                )
+            }),
+        )
+        // Outside the trace layer, so request spans carry the request ID and client address
+        .layer(middleware::from_fn_with_state(
+            url_service.clone(),
+            request_context,
+        ))
         .with_state(url_service);
 
     // Start server
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
     tracing::info!("listening on 0.0.0.0:3000");
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await?;
+
+    tracing::info!("Shutting down background jobs");
+    scheduler.shutdown(JOB_SHUTDOWN_TIMEOUT).await;
     Ok(())
 }
+
+/// Resolves on Ctrl+C, or on SIGTERM where supported
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl+C: {}", err);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(err) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", err);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    tracing::info!("Shutdown signal received");
+}